@@ -0,0 +1,90 @@
+//! Colormaps, for false-color images of scalar fields
+
+use crate::tonemap::srgb_inverse;
+
+/// A gradient through a list of colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colormap {
+    /// Dark blue to yellow, and perceptually uniform (from matplotlib)
+    Viridis,
+    /// Black to pale yellow, through red (from matplotlib)
+    Inferno,
+    /// Black, red, yellow and white
+    Heat,
+    /// Blue to white to red, for values with a sign (e.g., errors).
+    /// The middle of the range is white.
+    Diverging,
+}
+
+/// sRGB colors, evenly spaced along each gradient
+const VIRIDIS: [u32; 10] = [
+    0x440154, 0x482878, 0x3e4989, 0x31688e, 0x26828e, 0x1f9e89, 0x35b779, 0x6ece58, 0xb5de2b,
+    0xfde725,
+];
+const INFERNO: [u32; 10] = [
+    0x000004, 0x1b0c41, 0x4a0c6b, 0x781c6d, 0xa52c60, 0xcf4446, 0xed6925, 0xfb9b06, 0xf7d13d,
+    0xfcffa4,
+];
+const HEAT: [u32; 4] = [0x000000, 0xff0000, 0xffff00, 0xffffff];
+const DIVERGING: [u32; 5] = [0x3b4cc0, 0x8db0fe, 0xf7f7f7, 0xf4987a, 0xb40426];
+
+impl Colormap {
+    fn stops(&self) -> &'static [u32] {
+        match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Inferno => &INFERNO,
+            Colormap::Heat => &HEAT,
+            Colormap::Diverging => &DIVERGING,
+        }
+    }
+
+    /// The linear RGB color at `t`, which is clamped to `[0, 1]`.
+    /// Colors are interpolated in linear space.
+    pub fn color(&self, t: f64) -> [f64; 3] {
+        let stops = self.stops();
+        let t = if t.is_nan() { 0. } else { t.clamp(0., 1.) };
+        let x = t * (stops.len() - 1) as f64;
+        let i = (x as usize).min(stops.len() - 2);
+        let f = x - i as f64;
+        let (a, b) = (linear(stops[i]), linear(stops[i + 1]));
+        [
+            a[0] + (b[0] - a[0]) * f,
+            a[1] + (b[1] - a[1]) * f,
+            a[2] + (b[2] - a[2]) * f,
+        ]
+    }
+}
+
+/// Decodes an sRGB color, given as `0xRRGGBB`
+fn linear(rgb: u32) -> [f64; 3] {
+    let channel = |shift: u32| srgb_inverse(((rgb >> shift) & 0xff) as f64 / 255.);
+    [channel(16), channel(8), channel(0)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tonemap::to_byte;
+
+    #[test]
+    fn gradients() {
+        let bytes = |c: [f64; 3]| c.map(to_byte);
+        assert_eq!(bytes(Colormap::Viridis.color(0.)), [0x44, 0x01, 0x54]);
+        assert_eq!(bytes(Colormap::Viridis.color(1.)), [0xfd, 0xe7, 0x25]);
+        assert_eq!(bytes(Colormap::Viridis.color(2.)), [0xfd, 0xe7, 0x25]);
+        assert_eq!(bytes(Colormap::Heat.color(0.)), [0, 0, 0]);
+        assert_eq!(bytes(Colormap::Heat.color(1. / 3.)), [255, 0, 0]);
+        assert_eq!(bytes(Colormap::Diverging.color(0.5)), [0xf7, 0xf7, 0xf7]);
+
+        // Viridis and inferno get brighter all along
+        for colormap in [Colormap::Viridis, Colormap::Inferno, Colormap::Heat] {
+            let luminance = |c: [f64; 3]| 0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2];
+            let mut previous = -1.;
+            for i in 0..=100 {
+                let l = luminance(colormap.color(i as f64 / 100.));
+                assert!(l > previous, "{:?} gets darker at {}", colormap, i);
+                previous = l;
+            }
+        }
+    }
+}
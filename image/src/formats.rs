@@ -0,0 +1,164 @@
+//! Encoders for PPM, PFM and Radiance HDR files.
+//!
+//! Pixels are given by a function of their column and row (from the
+//! top left corner), so greyscale and color images can share them.
+
+use crate::tonemap::{to_byte, Tonemap};
+use std::io::Write;
+
+/// Writes a binary PPM (P6). Values are scaled by `exposure`, tonemapped
+/// and encoded with the sRGB transfer function.
+pub fn write_ppm<W: Write, F: Fn(usize, usize) -> [f64; 3]>(
+    writer: &mut W,
+    width: usize,
+    height: usize,
+    pixel: F,
+    exposure: f64,
+    tonemap: Tonemap,
+) -> std::io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", width, height)?;
+    let mut row = Vec::with_capacity(3 * width);
+    for y in 0..height {
+        row.clear();
+        for x in 0..width {
+            row.extend(pixel(x, y).map(|c| to_byte(tonemap.apply(c * exposure))));
+        }
+        writer.write_all(&row)?;
+    }
+    Ok(())
+}
+
+/// Writes a PFM, which keeps the values as they are (as `f32`). There
+/// are either 1 (`Pf`) or 3 (`PF`) channels, and only the first
+/// `channels` of each pixel are written.
+pub fn write_pfm<W: Write, F: Fn(usize, usize) -> [f64; 3]>(
+    writer: &mut W,
+    width: usize,
+    height: usize,
+    channels: usize,
+    pixel: F,
+) -> std::io::Result<()> {
+    let magic = match channels {
+        1 => "Pf",
+        3 => "PF",
+        _ => panic!("expecting 1 or 3 channels... found {}", channels),
+    };
+    // A negative scale means little-endian
+    write!(writer, "{}\n{} {}\n-1.0\n", magic, width, height)?;
+    // ... and rows go from the bottom up
+    for y in (0..height).rev() {
+        for x in 0..width {
+            for c in &pixel(x, y)[..channels] {
+                writer.write_all(&(*c as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes a Radiance HDR file, with uncompressed RGBE scanlines
+pub fn write_hdr<W: Write, F: Fn(usize, usize) -> [f64; 3]>(
+    writer: &mut W,
+    width: usize,
+    height: usize,
+    pixel: F,
+) -> std::io::Result<()> {
+    write!(
+        writer,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )?;
+    let mut row = Vec::with_capacity(4 * width);
+    for y in 0..height {
+        row.clear();
+        for x in 0..width {
+            row.extend(rgbe(pixel(x, y)));
+        }
+        writer.write_all(&row)?;
+    }
+    Ok(())
+}
+
+/// Encodes a color as a shared exponent and three 8-bit mantissas,
+/// as in Ward's "Real Pixels" (Graphics Gems II, 1991). Negative
+/// values become 0.
+pub fn rgbe(color: [f64; 3]) -> [u8; 4] {
+    let color = color.map(|c| c.max(0.));
+    let v = color[0].max(color[1]).max(color[2]);
+    if v < 1e-32 || !v.is_finite() {
+        return [0; 4];
+    }
+    // v = m * 2^e, with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    if v / 2f64.powi(e) >= 1. {
+        e += 1;
+    }
+    let scale = 256. / 2f64.powi(e);
+    let [r, g, b] = color.map(|c| (c * scale).min(255.) as u8);
+    [r, g, b, (e + 128).clamp(0, 255) as u8]
+}
+
+/// Decodes a color encoded by [`rgbe`], taking the centre of each
+/// quantization step
+pub fn rgbe_inverse(rgbe: [u8; 4]) -> [f64; 3] {
+    if rgbe[3] == 0 {
+        return [0.; 3];
+    }
+    let f = 2f64.powi(rgbe[3] as i32 - 128 - 8);
+    [0, 1, 2].map(|i| (rgbe[i] as f64 + 0.5) * f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_exponent() {
+        assert_eq!(rgbe([0., 0., 0.]), [0; 4]);
+        assert_eq!(rgbe([1., 0.5, 0.]), [128, 64, 0, 129]);
+        for v in [1e-6, 0.3, 1., 1.5, 255.9, 1e4, 3.7e9] {
+            let color = [v, v * 0.25, v * 0.9];
+            let decoded = rgbe_inverse(rgbe(color));
+            for i in 0..3 {
+                // Within half a step of the largest channel
+                assert!(
+                    (decoded[i] - color[i]).abs() <= v / 256.,
+                    "{:?} became {:?}",
+                    color,
+                    decoded
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn headers() {
+        let pixel = |x: usize, y: usize| [x as f64, y as f64, 2.];
+        let mut hdr = Vec::new();
+        write_hdr(&mut hdr, 3, 2, pixel).unwrap();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 3\n";
+        assert_eq!(&hdr[..header.len()], header);
+        assert_eq!(hdr.len(), header.len() + 4 * 6);
+        // The last pixel is (2, 1, 2)
+        let last: [u8; 4] = hdr[hdr.len() - 4..].try_into().unwrap();
+        assert_eq!(rgbe_inverse(last).map(|c| c.round()), [2., 1., 2.]);
+
+        let mut pfm = Vec::new();
+        write_pfm(&mut pfm, 3, 2, 3, pixel).unwrap();
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&pfm[..header.len()], header);
+        assert_eq!(pfm.len(), header.len() + 4 * 3 * 6);
+        // The bottom row comes first
+        let first = f32::from_le_bytes(pfm[header.len() + 4..header.len() + 8].try_into().unwrap());
+        assert_eq!(first, 1.);
+
+        let mut ppm = Vec::new();
+        write_ppm(&mut ppm, 3, 2, pixel, 0.5, Tonemap::Reinhard).unwrap();
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(
+            &ppm[ppm.len() - 3..],
+            &[to_byte(0.5), to_byte(1. / 3.), to_byte(0.5)]
+        );
+    }
+}
@@ -0,0 +1,352 @@
+//! Images of scalar fields (e.g., radiance, or a PDF) and colors, and
+//! how to save them as PPM, PFM or Radiance HDR files.
+//!
+//! Scalar images can be inspected directly with a false-color
+//! [`Colormap`], without plotting them with external scripts.
+
+pub mod colormap;
+pub mod formats;
+pub mod tonemap;
+
+pub use crate::colormap::Colormap;
+pub use crate::tonemap::Tonemap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// A grid of scalar values, stored row by row from the top
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<f64>,
+}
+
+/// A grid of linear RGB colors, stored row by row from the top
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<[f64; 3]>,
+}
+
+/// Creates `path`, and lets `write` fill it
+fn save<P: AsRef<Path>, F>(path: P, write: F) -> std::io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+{
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer)?;
+    writer.flush()
+}
+
+impl Image {
+    /// A black image
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0.0; width * height],
+        }
+    }
+
+    /// An image of `f(u, v)` at the centre of each pixel, where `(u, v)`
+    /// span `[0, 1]` from the bottom left corner (as in a plot)
+    pub fn from_fn<F: Fn(f64, f64) -> f64>(width: usize, height: usize, f: F) -> Self {
+        let mut image = Self::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let u = (x as f64 + 0.5) / width as f64;
+                let v = 1. - (y as f64 + 0.5) / height as f64;
+                image.set(x, y, f(u, v));
+            }
+        }
+        image
+    }
+
+    /// The density of `points` over `[0, 1]^2`, with the same layout
+    /// as [`Image::from_fn`]. The values estimate the PDF the points
+    /// were drawn from (so they average 1 if all fall within the square).
+    pub fn histogram<I: IntoIterator<Item = (f64, f64)>>(
+        width: usize,
+        height: usize,
+        points: I,
+    ) -> Self {
+        let mut image = Self::new(width, height);
+        let mut n = 0;
+        for (u, v) in points {
+            n += 1;
+            if !(0. ..=1.).contains(&u) || !(0. ..=1.).contains(&v) {
+                continue;
+            }
+            let x = ((u * width as f64) as usize).min(width - 1);
+            let y = (((1. - v) * height as f64) as usize).min(height - 1);
+            image.pixels[y * width + x] += 1.;
+        }
+        let scale = (width * height) as f64 / n.max(1) as f64;
+        image.map(|c| c * scale)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> f64 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: f64) {
+        self.pixels[y * self.width + x] = value;
+    }
+
+    pub fn pixels(&self) -> &[f64] {
+        &self.pixels
+    }
+
+    /// The average of all pixels
+    pub fn mean(&self) -> f64 {
+        self.pixels.iter().sum::<f64>() / self.pixels.len() as f64
+    }
+
+    /// The smallest and largest finite values, if there are any
+    pub fn range(&self) -> Option<(f64, f64)> {
+        self.pixels
+            .iter()
+            .filter(|v| v.is_finite())
+            .fold(None, |range, v| match range {
+                None => Some((*v, *v)),
+                Some((min, max)) => Some((min.min(*v), max.max(*v))),
+            })
+    }
+
+    /// An image with `f` applied to every pixel (e.g., a logarithm,
+    /// before [`Image::false_color`])
+    pub fn map<F: Fn(f64) -> f64>(&self, f: F) -> Self {
+        Self {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(|v| f(*v)).collect(),
+        }
+    }
+
+    /// Colors each pixel with `colormap`, mapping `range` (or the range
+    /// of the values, if it is `None`) onto the whole gradient
+    pub fn false_color(&self, colormap: Colormap, range: Option<(f64, f64)>) -> RgbImage {
+        let (min, max) = range.or_else(|| self.range()).unwrap_or((0., 1.));
+        let extent = if max > min { max - min } else { 1. };
+        RgbImage {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .pixels
+                .iter()
+                .map(|v| colormap.color((v - min) / extent))
+                .collect(),
+        }
+    }
+
+    /// The same values, in every channel
+    pub fn to_rgb(&self) -> RgbImage {
+        RgbImage {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(|v| [*v; 3]).collect(),
+        }
+    }
+
+    fn channels(&self) -> impl Fn(usize, usize) -> [f64; 3] + '_ {
+        |x, y| [self.get(x, y); 3]
+    }
+
+    /// Writes a binary PPM (see [`formats::write_ppm`])
+    pub fn write_ppm<W: Write>(
+        &self,
+        writer: &mut W,
+        exposure: f64,
+        tonemap: Tonemap,
+    ) -> std::io::Result<()> {
+        formats::write_ppm(
+            writer,
+            self.width,
+            self.height,
+            self.channels(),
+            exposure,
+            tonemap,
+        )
+    }
+
+    /// Writes a greyscale PFM, which keeps the values
+    pub fn write_pfm<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        formats::write_pfm(writer, self.width, self.height, 1, self.channels())
+    }
+
+    /// Writes a Radiance HDR file, which keeps the values (negative ones
+    /// become 0) with about 1% of precision
+    pub fn write_hdr<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        formats::write_hdr(writer, self.width, self.height, self.channels())
+    }
+
+    pub fn save_ppm<P: AsRef<Path>>(
+        &self,
+        path: P,
+        exposure: f64,
+        tonemap: Tonemap,
+    ) -> std::io::Result<()> {
+        save(path, |w| self.write_ppm(w, exposure, tonemap))
+    }
+
+    pub fn save_pfm<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        save(path, |w| self.write_pfm(w))
+    }
+
+    pub fn save_hdr<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        save(path, |w| self.write_hdr(w))
+    }
+}
+
+impl RgbImage {
+    /// A black image
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0.0; 3]; width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> [f64; 3] {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: [f64; 3]) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn pixels(&self) -> &[[f64; 3]] {
+        &self.pixels
+    }
+
+    fn channels(&self) -> impl Fn(usize, usize) -> [f64; 3] + '_ {
+        |x, y| self.get(x, y)
+    }
+
+    /// Writes a binary PPM (see [`formats::write_ppm`])
+    pub fn write_ppm<W: Write>(
+        &self,
+        writer: &mut W,
+        exposure: f64,
+        tonemap: Tonemap,
+    ) -> std::io::Result<()> {
+        formats::write_ppm(
+            writer,
+            self.width,
+            self.height,
+            self.channels(),
+            exposure,
+            tonemap,
+        )
+    }
+
+    pub fn write_pfm<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        formats::write_pfm(writer, self.width, self.height, 3, self.channels())
+    }
+
+    pub fn write_hdr<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        formats::write_hdr(writer, self.width, self.height, self.channels())
+    }
+
+    pub fn save_ppm<P: AsRef<Path>>(
+        &self,
+        path: P,
+        exposure: f64,
+        tonemap: Tonemap,
+    ) -> std::io::Result<()> {
+        save(path, |w| self.write_ppm(w, exposure, tonemap))
+    }
+
+    pub fn save_pfm<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        save(path, |w| self.write_pfm(w))
+    }
+
+    pub fn save_hdr<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        save(path, |w| self.write_hdr(w))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tonemap::to_byte;
+
+    #[test]
+    fn formats() {
+        let mut image = Image::new(3, 2);
+        image.set(0, 0, 1.);
+        image.set(2, 1, 0.5);
+        assert!((image.mean() - 0.25).abs() < 1e-12);
+        assert_eq!(image.range(), Some((0., 1.)));
+
+        let mut ppm = Vec::new();
+        image.write_ppm(&mut ppm, 1., Tonemap::Linear).unwrap();
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 3 * 6);
+        assert_eq!(&ppm[header.len()..header.len() + 3], &[255, 255, 255]);
+        assert_eq!(ppm[ppm.len() - 1], 188);
+
+        let mut pfm = Vec::new();
+        image.write_pfm(&mut pfm).unwrap();
+        let header = b"Pf\n3 2\n-1.0\n";
+        assert_eq!(&pfm[..header.len()], header);
+        assert_eq!(pfm.len(), header.len() + 4 * 6);
+        // The first value is the bottom left pixel
+        let first = f32::from_le_bytes(pfm[header.len()..header.len() + 4].try_into().unwrap());
+        assert_eq!(first, 0.);
+        let last = f32::from_le_bytes(pfm[pfm.len() - 4..].try_into().unwrap());
+        assert_eq!(last, 0.);
+        let top_left = f32::from_le_bytes(
+            pfm[header.len() + 12..header.len() + 16]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(top_left, 1.);
+
+        let mut hdr = Vec::new();
+        image.write_hdr(&mut hdr).unwrap();
+        assert_eq!(&hdr[hdr.len() - 4..], &formats::rgbe([0.5; 3]));
+
+        // Color images are written the same way
+        let mut rgb = Vec::new();
+        image
+            .to_rgb()
+            .write_ppm(&mut rgb, 1., Tonemap::Linear)
+            .unwrap();
+        assert_eq!(rgb, ppm);
+    }
+
+    #[test]
+    fn plots() {
+        // A 2D PDF, and a histogram of points drawn from it
+        let pdf = |u: f64, v: f64| 4. * u * v;
+        let exact = Image::from_fn(8, 4, pdf);
+        assert!((exact.get(7, 0) - pdf(15. / 16., 7. / 8.)).abs() < 1e-12);
+        assert!((exact.mean() - 1.).abs() < 1e-12);
+
+        let mut rng = rand::Rng::with_seed(0);
+        let n = 200_000;
+        let points = (0..n).map(|_| (rng.next_float().sqrt(), rng.next_float().sqrt()));
+        let histogram = Image::histogram(8, 4, points);
+        assert!((histogram.mean() - 1.).abs() < 1e-12);
+        for (h, e) in histogram.pixels().iter().zip(exact.pixels()) {
+            assert!((h - e).abs() < 0.05 + 0.05 * e, "{} vs {}", h, e);
+        }
+
+        let colors = exact.false_color(Colormap::Viridis, None);
+        let (min, max) = exact.range().unwrap();
+        assert_eq!(colors.get(0, 3), Colormap::Viridis.color(0.));
+        assert_eq!(colors.get(7, 0), Colormap::Viridis.color(1.));
+        let middle = exact.map(|v| (v - min) / (max - min)).get(3, 1);
+        assert_eq!(colors.get(3, 1), Colormap::Viridis.color(middle));
+        let fixed = exact.false_color(Colormap::Heat, Some((0., 8.)));
+        assert_eq!(
+            fixed.get(7, 0).map(to_byte),
+            Colormap::Heat.color(max / 8.).map(to_byte)
+        );
+    }
+}
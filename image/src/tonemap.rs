@@ -0,0 +1,83 @@
+//! Operators that squeeze radiance values into `[0, 1]`, for display
+
+/// How exposed values are mapped into `[0, 1]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tonemap {
+    /// Values above 1 are clamped
+    #[default]
+    Linear,
+    /// `v / (1 + v)`, from Reinhard et al. (2002) "Photographic
+    /// Tone Reproduction for Digital Images"
+    Reinhard,
+    /// Narkowicz's (2015) fit of the ACES filmic curve
+    Aces,
+}
+
+impl Tonemap {
+    /// Maps `v` (which has already been scaled by the exposure)
+    pub fn apply(&self, v: f64) -> f64 {
+        let v = v.max(0.);
+        let mapped = match self {
+            Tonemap::Linear => v,
+            Tonemap::Reinhard => v / (1. + v),
+            Tonemap::Aces => (v * (2.51 * v + 0.03)) / (v * (2.43 * v + 0.59) + 0.14),
+        };
+        mapped.clamp(0., 1.)
+    }
+}
+
+/// The sRGB transfer function, clamping `v` between 0 and 1
+pub fn srgb(v: f64) -> f64 {
+    let v = v.clamp(0., 1.);
+    if v <= 0.0031308 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// The inverse of [`srgb`]
+pub fn srgb_inverse(v: f64) -> f64 {
+    let v = v.clamp(0., 1.);
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear value, in `[0, 1]`, as an 8-bit sRGB one
+pub fn to_byte(v: f64) -> u8 {
+    (srgb(v) * 255. + 0.5) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators() {
+        for tonemap in [Tonemap::Linear, Tonemap::Reinhard, Tonemap::Aces] {
+            assert_eq!(tonemap.apply(0.), 0.);
+            assert_eq!(tonemap.apply(-1.), 0.);
+            assert!(tonemap.apply(1e9) <= 1.);
+            // Monotonic
+            let mut previous = 0.;
+            for i in 1..1000 {
+                let v = tonemap.apply(i as f64 * 0.01);
+                assert!(v >= previous, "{:?} decreases at {}", tonemap, i);
+                previous = v;
+            }
+        }
+        assert_eq!(Tonemap::Linear.apply(2.), 1.);
+        assert_eq!(Tonemap::Reinhard.apply(1.), 0.5);
+        assert!((Tonemap::Aces.apply(1e9) - 1.).abs() < 1e-6);
+
+        for i in 0..=100 {
+            let v = i as f64 / 100.;
+            assert!((srgb_inverse(srgb(v)) - v).abs() < 1e-12);
+        }
+        assert_eq!(to_byte(1.), 255);
+        assert_eq!(to_byte(0.5), 188);
+    }
+}
@@ -41,7 +41,7 @@ impl DiscretePdf {
         let mut min = 0.0;
         let mut max = 1.0;
         assert!(
-            y <= 1.0 && y >= 0.0,
+            (0.0..=1.0).contains(&y),
             "expecting y to be within 0 to 1 range... found {:.6}",
             y
         );
@@ -79,18 +79,15 @@ impl DiscretePdf {
     }
 
     pub fn pdf(&self, x: f64) -> f64 {
-        if x < self.min_x {
+        if x < self.min_x || x >= self.max_x {
             return 0.0;
-        } else if x >= self.max_x {
-            return 0.0;
-        } else {
-            for (range_max, range_pdf) in self.steps.iter() {
-                if x < *range_max {
-                    return *range_pdf;
-                }
+        }
+        for (range_max, range_pdf) in self.steps.iter() {
+            if x < *range_max {
+                return *range_pdf;
             }
-            unreachable!()
         }
+        unreachable!()
     }
 
     pub fn cdf(&self, x: f64) -> (f64, f64) {
@@ -111,56 +108,67 @@ impl DiscretePdf {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
-    use std::io::prelude::*;
+    use plot::{Chart, Histogram};
+
+    fn example() -> DiscretePdf {
+        DiscretePdf::new(0.0, vec![0.45, 0.55, 1.0], vec![0.1, 9.1, 0.1])
+    }
 
     #[test]
     fn pdf() {
-        let c = DiscretePdf::new(0.0, vec![0.45, 0.55, 1.0], vec![0.1, 9.1, 0.1]);
-        let mut file = File::create("data/pdf.csv").unwrap();
-        file.write_all(b"x, pdf\n").unwrap();
-        for x in 0..100 {
-            let x = x as f64 / 100.0;
-            let ln = format!("{:.3},{:.3}\n", x, c.pdf(x));
-            file.write_all(ln.as_bytes()).unwrap();
-        }
+        let c = example();
+        assert_eq!(c.pdf(0.2), 0.1);
+        assert_eq!(c.pdf(0.5), 9.1);
+        assert_eq!(c.pdf(0.99), 0.1);
+        assert_eq!(c.pdf(-0.1), 0.0);
+        assert_eq!(c.pdf(1.0), 0.0);
     }
 
     #[test]
     fn cumulative_pdf() {
-        let c = DiscretePdf::new(0.0, vec![0.45, 0.55, 1.0], vec![0.1, 9.1, 0.1]);
-        let mut file = File::create("data/cdf.csv").unwrap();
-        file.write_all(b"x,CDF,PDF\n").unwrap();
+        let c = example();
+        let mut previous = 0.0;
         for x in 0..100 {
             let x = x as f64 / 100.0;
             let (cdf, pdf) = c.cdf(x);
-            let ln = format!("{:.3},{:.3},{:.3}\n", x, cdf, pdf);
-            file.write_all(ln.as_bytes()).unwrap();
+            assert!(cdf >= previous);
+            assert_eq!(pdf, c.pdf(x));
+            previous = cdf;
         }
+        assert!((c.cdf(0.45).0 - 0.045).abs() < 1e-12);
+        assert!((c.cdf(0.55).0 - 0.955).abs() < 1e-12);
     }
 
     #[test]
     fn inv_cumulative_pdf() {
-        let c = DiscretePdf::new(0.0, vec![0.45, 0.55, 1.0], vec![0.1, 9.1, 0.1]);
-        let mut file = File::create("data/inv_cdf.csv").unwrap();
-        file.write_all(b"x,CDF-1,PDF\n").unwrap();
-        for x in 0..100 {
-            let x = x as f64 / 100.0;
-            let (cdf, pdf) = c.inv_cdf(x);
-            let ln = format!("{:.3},{:.3},{:.3}\n", x, cdf, pdf);
-            file.write_all(ln.as_bytes()).unwrap();
+        let c = example();
+        for y in 0..100 {
+            let y = y as f64 / 100.0;
+            let (x, pdf) = c.inv_cdf(y);
+            let (found_y, _) = c.cdf(x);
+            assert!((found_y - y).abs() < 1e-4);
+            assert_eq!(pdf, c.pdf(x));
         }
     }
 
     #[test]
     fn sample_pdf() {
-        let c = DiscretePdf::new(0.0, vec![0.45, 0.55, 1.0], vec![0.1, 9.1, 0.1]);
-        let mut rng = Rng::new();
-        let mut file = File::create("data/samples.txt").unwrap();
-        for _ in 0..8000 {
-            let (x, _pdf) = c.sample(&mut rng);
-            let ln = format!("{}\n", x);
-            file.write_all(ln.as_bytes()).unwrap();
+        let c = example();
+        let mut rng = Rng::with_seed(0);
+        let n = 8000;
+        let mut histogram = Histogram::new(0.0, 1.0, 20);
+        for _ in 0..n {
+            let (x, pdf) = c.sample(&mut rng);
+            assert_eq!(pdf, c.pdf(x));
+            histogram.add(x);
         }
+
+        // 91% of the samples fall in the spike, which spans two bins
+        let in_spike = histogram.counts()[9] + histogram.counts()[10];
+        let fraction = in_spike as f64 / n as f64;
+        assert!((fraction - 0.91).abs() < 0.02, "{:.6}", fraction);
+
+        let chart = Chart::histogram("Samples of a step PDF", histogram, |x| c.pdf(x));
+        println!("{}", chart.to_ascii(60, 16));
     }
 }
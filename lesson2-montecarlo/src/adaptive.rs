@@ -0,0 +1,374 @@
+//! Importance sampling with a proposal that adapts to the integrand as it
+//! is sampled (in the spirit of VEGAS), instead of a hand-crafted one
+//! such as the one of [`crate::importance_sampling`].
+
+use lesson1_sampling::step_pdf::DiscretePdf;
+use rand::Rng;
+
+/// Integrates `f` over `[0, 1]` in rounds. Every round samples a step
+/// PDF with `bins` steps of the same probability, adds up the `|f| / pdf`
+/// of the samples in each step, and moves the edges of the steps so they
+/// split the next round evenly (i.e., narrower steps where `|f|` is
+/// larger).
+pub struct AdaptiveImportance<F: Fn(f64) -> f64> {
+    pub f: F,
+    pub bins: usize,
+    pub iterations: usize,
+    /// Per iteration
+    pub samples: usize,
+    /// How much the edges move in each iteration (none for `0`; VEGAS
+    /// uses `0.5` to `2`)
+    pub alpha: f64,
+}
+
+/// A round of an [`AdaptiveImportance`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Iteration {
+    /// The upper edge of each step of the proposal (the first one starts
+    /// at 0)
+    pub edges: Vec<f64>,
+    pub estimate: f64,
+    /// Of `estimate`
+    pub variance: f64,
+}
+
+impl Iteration {
+    /// The proposal the samples were drawn from
+    pub fn pdf(&self) -> DiscretePdf {
+        DiscretePdf::new(0.0, self.edges.clone(), densities(&self.edges))
+    }
+}
+
+/// The densities of steps of the same probability
+fn densities(edges: &[f64]) -> Vec<f64> {
+    let mut lower = 0.0;
+    edges
+        .iter()
+        .map(|upper| {
+            let density = 1.0 / (edges.len() as f64 * (upper - lower));
+            lower = *upper;
+            density
+        })
+        .collect()
+}
+
+impl<F: Fn(f64) -> f64> AdaptiveImportance<F> {
+    pub fn run(&self, rng: &mut Rng) -> Adaptive {
+        assert!(
+            self.iterations > 0,
+            "expecting at least 1 iteration... found 0"
+        );
+        assert!(
+            self.samples > 1,
+            "expecting at least 2 samples per iteration... found {}",
+            self.samples
+        );
+        let mut grid = Grid::uniform(self.bins);
+        let mut iterations = Vec::new();
+        for _ in 0..self.iterations {
+            let mut contributions = vec![0.0; self.bins];
+            let (mut sum, mut sum_squares) = (0.0, 0.0);
+            for _ in 0..self.samples {
+                let (x, bin, p) = grid.sample(rng.next_float());
+                let weight = (self.f)(x) / p;
+                sum += weight;
+                sum_squares += weight * weight;
+                contributions[bin] += weight.abs();
+            }
+            let n = self.samples as f64;
+            let estimate = sum / n;
+            let variance = (sum_squares / n - estimate * estimate).max(0.0) / (n - 1.0);
+            iterations.push(Iteration {
+                edges: grid.edges().to_vec(),
+                estimate,
+                variance,
+            });
+            grid.refine(&contributions, self.alpha);
+        }
+        Adaptive { iterations }
+    }
+}
+
+/// Steps of the same probability over `[0, 1]`, whose edges move towards
+/// where an integrand is larger
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid {
+    edges: Vec<f64>,
+}
+
+impl Grid {
+    pub fn uniform(bins: usize) -> Self {
+        assert!(bins > 0, "expecting at least 1 bin... found 0");
+        Self {
+            edges: (1..=bins).map(|i| i as f64 / bins as f64).collect(),
+        }
+    }
+
+    /// The upper edge of each step (the first one starts at 0)
+    pub fn edges(&self) -> &[f64] {
+        &self.edges
+    }
+
+    pub fn bins(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// The point a uniform sample `u` maps to, the step it falls in, and
+    /// its density. As all the steps are equally likely, this is exact
+    /// (unlike the bisection of `DiscretePdf::sample`).
+    pub fn sample(&self, u: f64) -> (f64, usize, f64) {
+        let u = u * self.bins() as f64;
+        let bin = (u as usize).min(self.bins() - 1);
+        let lower = if bin == 0 { 0.0 } else { self.edges[bin - 1] };
+        let width = self.edges[bin] - lower;
+        let x = lower + (u - bin as f64) * width;
+        (x, bin, 1.0 / (self.bins() as f64 * width))
+    }
+
+    /// Moves the edges towards the steps with larger `contributions`,
+    /// damped by `alpha` (see [`AdaptiveImportance::alpha`])
+    pub fn refine(&mut self, contributions: &[f64], alpha: f64) {
+        self.edges = refine(&self.edges, contributions, alpha);
+    }
+
+    pub fn pdf(&self) -> DiscretePdf {
+        DiscretePdf::new(0.0, self.edges.clone(), densities(&self.edges))
+    }
+}
+
+/// Moves the edges so each new step holds the same share of the
+/// (smoothed and damped) `contributions` of the old ones
+fn refine(edges: &[f64], contributions: &[f64], alpha: f64) -> Vec<f64> {
+    let n = edges.len();
+    // Averaged with the neighbours, so no step is left empty by chance
+    let smoothed: Vec<f64> = (0..n)
+        .map(|k| {
+            let neighbours = &contributions[k.saturating_sub(1)..(k + 2).min(n)];
+            neighbours.iter().sum::<f64>() / neighbours.len() as f64
+        })
+        .collect();
+    let total: f64 = smoothed.iter().sum();
+    if total <= 0.0 || alpha == 0.0 {
+        return edges.to_vec();
+    }
+    // Damped as in VEGAS, so the edges do not jump around
+    let importance: Vec<f64> = smoothed
+        .iter()
+        .map(|d| {
+            let r = d / total;
+            if r <= 0.0 {
+                0.0
+            } else if (r - 1.0).abs() < 1e-12 {
+                1.0
+            } else {
+                ((r - 1.0) / r.ln()).powf(alpha)
+            }
+        })
+        .collect();
+    let share = importance.iter().sum::<f64>() / n as f64;
+
+    let mut refined = Vec::with_capacity(n);
+    let (mut k, mut lower, mut accumulated) = (0, 0.0, 0.0);
+    for i in 1..n {
+        let target = share * i as f64;
+        while accumulated + importance[k] < target {
+            accumulated += importance[k];
+            lower = edges[k];
+            k += 1;
+        }
+        let fraction = (target - accumulated) / importance[k];
+        refined.push(lower + fraction * (edges[k] - lower));
+    }
+    refined.push(edges[n - 1]);
+    refined
+}
+
+/// Estimates (and their variances) weighted by the inverse of their
+/// variance, and the variance of the result. As each variance is usually
+/// estimated from the same samples as its estimate, the result is
+/// slightly biased towards the estimates that came out low, less so with
+/// more samples per estimate.
+///
+/// Estimates with no variance are left out, as they usually come from
+/// rounds whose samples all missed where the integrand is not 0. If all
+/// of them have no variance (e.g., for a constant integrand), the result
+/// is their mean, with no variance either.
+pub fn inverse_variance<I: IntoIterator<Item = (f64, f64)>>(estimates: I) -> (f64, f64) {
+    let (mut weighted, mut total) = (0.0, 0.0);
+    let (mut unweighted, mut n_unweighted, mut n) = (0.0, 0, 0);
+    for (estimate, variance) in estimates {
+        n += 1;
+        if variance > 0.0 {
+            weighted += estimate / variance;
+            total += 1.0 / variance;
+        } else {
+            unweighted += estimate;
+            n_unweighted += 1;
+        }
+    }
+    assert!(n > 0, "expecting at least 1 estimate... found 0");
+    if n_unweighted == n {
+        return (unweighted / n as f64, 0.0);
+    }
+    (weighted / total, 1.0 / total)
+}
+
+/// The rounds of an [`AdaptiveImportance`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Adaptive {
+    pub iterations: Vec<Iteration>,
+}
+
+impl Adaptive {
+    /// The estimates of all the iterations, combined with
+    /// [`inverse_variance`]
+    pub fn combined(&self) -> (f64, f64) {
+        inverse_variance(self.iterations.iter().map(|i| (i.estimate, i.variance)))
+    }
+
+    /// How consistent the iterations are with each other: about 1 if
+    /// their estimates only differ by their errors, and much larger if
+    /// some of the variances are underestimated. Like [`Self::combined`],
+    /// it leaves out the iterations with no variance.
+    pub fn chi2_per_dof(&self) -> f64 {
+        let (estimate, _) = self.combined();
+        let (mut chi2, mut n) = (0.0, 0usize);
+        for i in self.iterations.iter().filter(|i| i.variance > 0.0) {
+            chi2 += (i.estimate - estimate).powi(2) / i.variance;
+            n += 1;
+        }
+        chi2 / n.saturating_sub(1).max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::importance_sampling::{exp, EXP_INTEGRAL};
+    use plot::{Chart, Series};
+
+    #[test]
+    fn refined_edges() {
+        let edges = [0.25, 0.5, 0.75, 1.0];
+        // Nothing to move towards
+        assert_eq!(refine(&edges, &[1.0; 4], 1.0), edges.to_vec());
+        assert_eq!(refine(&edges, &[0.0; 4], 1.0), edges.to_vec());
+        assert_eq!(refine(&edges, &[0.0, 5.0, 0.0, 1.0], 0.0), edges.to_vec());
+
+        let refined = refine(&edges, &[0.0, 5.0, 0.0, 0.0], 1.0);
+        assert_eq!(refined.len(), 4);
+        assert_eq!(refined[3], 1.0);
+        assert!(refined.windows(2).all(|w| w[0] < w[1]), "{:?}", refined);
+        // Narrower around the second step
+        assert!(refined[1] - refined[0] < 0.25, "{:?}", refined);
+
+        let pdf = DiscretePdf::new(0.0, refined.clone(), densities(&refined));
+        for (i, upper) in refined.iter().enumerate() {
+            let probability = pdf.cdf(*upper - 1e-12).0;
+            assert!((probability - (i + 1) as f64 / 4.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn no_variance() {
+        // Only the estimates with a variance count...
+        let (estimate, variance) = inverse_variance([(0.0, 0.0), (1.0, 0.01), (2.0, 0.01)]);
+        assert!((estimate - 1.5).abs() < 1e-12);
+        assert!((variance - 0.005).abs() < 1e-12);
+        // ... unless none has one
+        assert_eq!(inverse_variance([(2.0, 0.0), (4.0, 0.0)]), (3.0, 0.0));
+
+        let zero = AdaptiveImportance {
+            f: |_| 0.0,
+            bins: 10,
+            iterations: 3,
+            samples: 100,
+            alpha: 0.5,
+        };
+        let run = zero.run(&mut Rng::with_seed(0));
+        assert_eq!(run.combined(), (0.0, 0.0));
+        assert_eq!(run.chi2_per_dof(), 0.0);
+
+        // The first rounds (most likely) miss such a narrow box
+        let width = 1e-3;
+        let narrow = AdaptiveImportance {
+            f: |x| {
+                if (0.3..0.3 + width).contains(&x) {
+                    1.0
+                } else {
+                    0.0
+                }
+            },
+            bins: 50,
+            iterations: 10,
+            samples: 200,
+            alpha: 0.5,
+        };
+        let run = narrow.run(&mut Rng::with_seed(0));
+        assert_eq!(run.iterations[0].variance, 0.0);
+        let (estimate, variance) = run.combined();
+        assert!(variance > 0.0);
+        assert!(
+            (estimate / width - 1.0).abs() < 0.05,
+            "expecting {}... found {} ± {}",
+            width,
+            estimate,
+            variance.sqrt()
+        );
+        assert!(run.chi2_per_dof().is_finite());
+    }
+
+    #[test]
+    fn spike() {
+        let adaptive = AdaptiveImportance {
+            f: exp,
+            bins: 50,
+            iterations: 10,
+            samples: 2000,
+            alpha: 0.5,
+        };
+        let run = adaptive.run(&mut Rng::with_seed(0));
+        assert_eq!(run.iterations.len(), 10);
+
+        // The first iteration is uniform, and the proposal follows the
+        // spike from then on, which shrinks the variance
+        let first = &run.iterations[0];
+        let last = &run.iterations[9];
+        assert!((first.edges[0] - 0.02).abs() < 1e-12);
+        let adapted = run.iterations[5..].iter().map(|i| i.variance).sum::<f64>() / 5.0;
+        assert!(
+            adapted < first.variance / 10.0,
+            "expecting a variance below {:e}... found {:e}",
+            first.variance / 10.0,
+            adapted
+        );
+        let spike = last.pdf().cdf(0.55).0 - last.pdf().cdf(0.45).0;
+        assert!(spike > 0.8, "{:.6}", spike);
+
+        let (estimate, variance) = run.combined();
+        assert!(variance < last.variance);
+        let error = (estimate - EXP_INTEGRAL).abs();
+        assert!(
+            error < 4.0 * variance.sqrt(),
+            "{:.6} {:.6}",
+            estimate,
+            variance
+        );
+        assert!(error / EXP_INTEGRAL < 0.01, "{:.6}", estimate);
+        assert!(run.chi2_per_dof() < 5.0, "{:.6}", run.chi2_per_dof());
+
+        // The proposal of every third iteration
+        let mut chart = Chart::new("Adaptive importance sampling", "x", "pdf");
+        for (i, iteration) in run.iterations.iter().enumerate().step_by(3) {
+            let pdf = iteration.pdf();
+            chart.series.push(Series::from_fn(
+                &format!("iteration {}", i),
+                0.0,
+                1.0,
+                400,
+                |x| pdf.pdf(x.min(0.999_999)),
+            ));
+        }
+        println!("{}", chart.to_ascii(60, 16));
+    }
+}
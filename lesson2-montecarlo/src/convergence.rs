@@ -0,0 +1,254 @@
+//! How the error of an estimator shrinks with the number of samples,
+//! measured over many independent trials per number of samples, as a
+//! single run per `N` is mostly noise.
+
+use crate::montecarlo_integrable::MontecarloIntegrable;
+use rand::Rng;
+
+/// The estimates of all the trials with the same number of samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrialStats {
+    /// Samples per trial
+    pub n: usize,
+    pub mean: f64,
+    /// `mean - expected`
+    pub bias: f64,
+    /// Of the estimates, around their mean (unbiased)
+    pub variance: f64,
+    /// Root mean squared error, around the expected value
+    pub rmse: f64,
+    /// Sorted
+    estimates: Vec<f64>,
+}
+
+impl TrialStats {
+    pub fn new(n: usize, mut estimates: Vec<f64>, expected: f64) -> Self {
+        assert!(
+            !estimates.is_empty(),
+            "expecting at least one trial... found 0"
+        );
+        estimates.sort_by(|a, b| a.total_cmp(b));
+        let m = estimates.len() as f64;
+        let mean = estimates.iter().sum::<f64>() / m;
+        let variance = if estimates.len() > 1 {
+            estimates.iter().map(|e| (e - mean).powi(2)).sum::<f64>() / (m - 1.)
+        } else {
+            0.
+        };
+        let mse = estimates
+            .iter()
+            .map(|e| (e - expected).powi(2))
+            .sum::<f64>()
+            / m;
+        Self {
+            n,
+            mean,
+            bias: mean - expected,
+            variance,
+            rmse: mse.sqrt(),
+            estimates,
+        }
+    }
+
+    pub fn trials(&self) -> usize {
+        self.estimates.len()
+    }
+
+    pub fn estimates(&self) -> &[f64] {
+        &self.estimates
+    }
+
+    /// The `q`-th quantile (e.g., `0.5` for the median) of the estimates,
+    /// interpolating between the two closest ones
+    pub fn quantile(&self, q: f64) -> f64 {
+        assert!(
+            (0.0..=1.0).contains(&q),
+            "expecting a quantile within 0 to 1 range... found {:.6}",
+            q
+        );
+        let position = q * (self.estimates.len() - 1) as f64;
+        let below = position.floor() as usize;
+        let above = position.ceil() as usize;
+        let t = position - below as f64;
+        self.estimates[below] * (1. - t) + self.estimates[above] * t
+    }
+}
+
+/// Runs `trials` independent trials for each number of samples in `counts`
+pub struct ConvergenceStudy {
+    pub counts: Vec<usize>,
+    pub trials: usize,
+    pub seed: u64,
+}
+
+impl ConvergenceStudy {
+    pub fn new(counts: Vec<usize>, trials: usize) -> Self {
+        Self {
+            counts,
+            trials,
+            seed: 0,
+        }
+    }
+
+    /// With `2^min_pow, ..., 2^max_pow` samples
+    pub fn powers_of_two(min_pow: u32, max_pow: u32, trials: usize) -> Self {
+        Self::new(
+            (min_pow..=max_pow).map(|p| 2_usize.pow(p)).collect(),
+            trials,
+        )
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// The generator of one trial, different for every trial, number of
+    /// samples and seed of the study
+    fn rng(&self, level: usize, trial: usize) -> Rng {
+        Rng::with_stream(self.seed, (level * self.trials + trial) as u64)
+    }
+
+    pub fn run<I: MontecarloIntegrable + ?Sized>(
+        &self,
+        integrand: &I,
+        expected: f64,
+    ) -> Convergence {
+        let levels = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(level, n)| {
+                let estimates = (0..self.trials)
+                    .map(|trial| integrand.integrate(*n, self.rng(level, trial)))
+                    .collect();
+                TrialStats::new(*n, estimates, expected)
+            })
+            .collect();
+        Convergence { levels }
+    }
+}
+
+/// The results of a [`ConvergenceStudy`], by number of samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct Convergence {
+    pub levels: Vec<TrialStats>,
+}
+
+impl Convergence {
+    /// The RMSE by number of samples
+    pub fn rmse(&self) -> Vec<(f64, f64)> {
+        self.levels.iter().map(|l| (l.n as f64, l.rmse)).collect()
+    }
+
+    /// The empirical convergence exponent (i.e., `RMSE ~ N^rate`), fitted
+    /// by least squares in log-log space. It is about -0.5 for plain
+    /// Monte Carlo.
+    pub fn rate(&self) -> f64 {
+        log_log_slope(&self.rmse())
+    }
+}
+
+/// The slope of the least-squares line through `points` on log-log axes
+/// (e.g., -0.5 for an error that decreases as `N^-1/2`). Points that are
+/// not positive are left out; it is NaN if fewer than two remain.
+pub fn log_log_slope(points: &[(f64, f64)]) -> f64 {
+    let logs: Vec<(f64, f64)> = points
+        .iter()
+        .filter(|(x, y)| *x > 0. && *y > 0.)
+        .map(|(x, y)| (x.ln(), y.ln()))
+        .collect();
+    let n = logs.len() as f64;
+    if logs.len() < 2 {
+        return f64::NAN;
+    }
+    let mean_x = logs.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = logs.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxy: f64 = logs.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let sxx: f64 = logs.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    sxy / sxx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrands::Quad;
+    use crate::stratified::{QuasiMonteCarlo, Stratified};
+
+    #[test]
+    fn statistics() {
+        let stats = TrialStats::new(4, vec![3., 1., 2., 6.], 2.);
+        assert_eq!(stats.trials(), 4);
+        assert_eq!(stats.estimates(), &[1., 2., 3., 6.]);
+        assert_eq!(stats.mean, 3.);
+        assert_eq!(stats.bias, 1.);
+        assert!((stats.variance - 14. / 3.).abs() < 1e-12);
+        // MSE = variance (biased) + bias^2
+        assert!((stats.rmse - (14. / 4. + 1.0f64).sqrt()).abs() < 1e-12);
+        assert_eq!(stats.quantile(0.), 1.);
+        assert_eq!(stats.quantile(0.5), 2.5);
+        assert_eq!(stats.quantile(1.), 6.);
+    }
+
+    #[test]
+    fn slopes() {
+        let points: Vec<(f64, f64)> = (1..10)
+            .map(|k| {
+                let n = 2f64.powi(k);
+                (n, 3. / n.sqrt())
+            })
+            .collect();
+        assert!((log_log_slope(&points) + 0.5).abs() < 1e-12);
+        assert!(log_log_slope(&points[..1]).is_nan());
+        // Points that are not positive are left out
+        let mut with_zero = points.clone();
+        with_zero.push((1024., 0.));
+        assert!((log_log_slope(&with_zero) + 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn convergence_rates() {
+        let quad = Quad {
+            a: 1.0,
+            b: 2.121,
+            c: 3.0,
+            range: 1.0,
+        };
+        let expected = quad.analytically_integrate();
+        let study = ConvergenceStudy::powers_of_two(4, 12, 64).with_seed(1);
+
+        let plain = study.run(&quad, expected);
+        let stratified = study.run(
+            &Stratified {
+                f: |x| quad.eval(x),
+            },
+            expected,
+        );
+        let qmc = study.run(
+            &QuasiMonteCarlo {
+                f: |x| quad.eval(x),
+            },
+            expected,
+        );
+
+        // Unbiased: the mean is within a few standard errors
+        for level in plain.levels.iter() {
+            let standard_error = (level.variance / level.trials() as f64).sqrt();
+            assert!(level.bias.abs() < 4. * standard_error, "{:?}", level);
+        }
+
+        let rate = plain.rate();
+        assert!((rate + 0.5).abs() < 0.1, "plain {:.6}", rate);
+        // N^-3/2 for smooth integrands
+        let rate = stratified.rate();
+        assert!(rate < -1.3, "stratified {:.6}", rate);
+        // N^-1, give or take a log(N)
+        let rate = qmc.rate();
+        assert!(rate < -0.9, "qmc {:.6}", rate);
+
+        for (p, s) in plain.levels.iter().zip(stratified.levels.iter()).skip(1) {
+            assert!(s.rmse < p.rmse);
+            assert!(p.quantile(0.05) <= p.quantile(0.5) && p.quantile(0.5) <= p.quantile(0.95));
+        }
+    }
+}
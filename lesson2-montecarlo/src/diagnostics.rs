@@ -0,0 +1,123 @@
+//! How far the samples of a Markov chain are from independent ones, and
+//! whether a few chains agree on their distribution
+
+/// Of `samples`, `lag` steps apart (1 at lag 0)
+pub fn autocorrelation(samples: &[f64], lag: usize) -> f64 {
+    let n = samples.len();
+    if lag >= n {
+        return 0.0;
+    }
+    let mean = samples.iter().sum::<f64>() / n as f64;
+    let variance: f64 = samples.iter().map(|x| (x - mean).powi(2)).sum();
+    if variance == 0.0 {
+        return 1.0;
+    }
+    let covariance: f64 = samples
+        .iter()
+        .zip(samples[lag..].iter())
+        .map(|(a, b)| (a - mean) * (b - mean))
+        .sum();
+    covariance / variance
+}
+
+/// How many independent samples would estimate the mean as well as
+/// `samples`, i.e., `n / (1 + 2 sum(autocorrelation))`, summing pairs of
+/// lags while they are positive (Geyer's initial positive sequence)
+pub fn effective_sample_size(samples: &[f64]) -> f64 {
+    let n = samples.len();
+    let mut sum = 0.0;
+    let mut lag = 1;
+    while lag + 1 < n {
+        let pair = autocorrelation(samples, lag) + autocorrelation(samples, lag + 1);
+        if pair <= 0.0 {
+            break;
+        }
+        sum += pair;
+        lag += 2;
+    }
+    (n as f64 / (1.0 + 2.0 * sum)).min(n as f64)
+}
+
+/// The potential scale reduction factor of Gelman and Rubin: about 1 when
+/// `chains` (at least two, of the same length) sample the same
+/// distribution, and larger when they have not mixed yet
+pub fn r_hat(chains: &[Vec<f64>]) -> f64 {
+    assert!(
+        chains.len() > 1,
+        "expecting at least 2 chains... found {}",
+        chains.len()
+    );
+    let n = chains[0].len();
+    assert!(
+        n > 1 && chains.iter().all(|c| c.len() == n),
+        "expecting chains of the same length, with at least 2 samples"
+    );
+    let m = chains.len() as f64;
+    let means: Vec<f64> = chains
+        .iter()
+        .map(|c| c.iter().sum::<f64>() / n as f64)
+        .collect();
+    let grand_mean = means.iter().sum::<f64>() / m;
+    // Between and within chains
+    let between =
+        n as f64 * means.iter().map(|x| (x - grand_mean).powi(2)).sum::<f64>() / (m - 1.0);
+    let within = chains
+        .iter()
+        .zip(means.iter())
+        .map(|(c, mean)| c.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64)
+        .sum::<f64>()
+        / m;
+    let pooled = (n - 1) as f64 / n as f64 * within + between / n as f64;
+    (pooled / within).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    /// `x_t = rho x_(t-1) + noise`, whose autocorrelation is `rho^lag`
+    fn autoregressive(rho: f64, n: usize, seed: u64) -> Vec<f64> {
+        let mut rng = Rng::with_seed(seed);
+        let mut x = 0.0;
+        (0..n)
+            .map(|_| {
+                x = rho * x + (1.0 - rho * rho).sqrt() * rng.next_normal();
+                x
+            })
+            .collect()
+    }
+
+    #[test]
+    fn correlated_samples() {
+        let n = 100_000;
+        let samples = autoregressive(0.9, n, 0);
+        assert_eq!(autocorrelation(&samples, 0), 1.0);
+        for lag in [1, 2, 5, 10] {
+            let found = autocorrelation(&samples, lag);
+            let expected = 0.9f64.powi(lag as i32);
+            assert!((found - expected).abs() < 0.03, "{} {:.6}", lag, found);
+        }
+        // n (1 - rho) / (1 + rho)
+        let ess = effective_sample_size(&samples);
+        let expected = n as f64 * 0.1 / 1.9;
+        assert!((ess / expected - 1.0).abs() < 0.15, "{:.6}", ess);
+
+        let independent = autoregressive(0.0, n, 1);
+        let ess = effective_sample_size(&independent);
+        assert!(ess > 0.9 * n as f64, "{:.6}", ess);
+    }
+
+    #[test]
+    fn gelman_rubin() {
+        let chains: Vec<Vec<f64>> = (0..4).map(|s| autoregressive(0.5, 2000, s)).collect();
+        let found = r_hat(&chains);
+        assert!(found < 1.01, "{:.6}", found);
+
+        // One of them is stuck somewhere else
+        let mut chains = chains;
+        chains[3].iter_mut().for_each(|x| *x += 2.0);
+        let found = r_hat(&chains);
+        assert!(found > 1.2, "{:.6}", found);
+    }
+}
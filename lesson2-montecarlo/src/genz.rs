@@ -0,0 +1,205 @@
+//! The test functions of Genz (1984) over the unit hypercube, each one
+//! hard in a different way for integrators, with their integrals in
+//! closed form
+
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    /// `cos(2 PI u_1 + sum(a_i x_i))`
+    Oscillatory,
+    /// `prod(1 / (a_i^-2 + (x_i - u_i)^2))`
+    ProductPeak,
+    /// `(1 + sum(a_i x_i))^-(d + 1)`
+    CornerPeak,
+    /// `exp(-sum(a_i^2 (x_i - u_i)^2))`
+    Gaussian,
+    /// `exp(-sum(a_i |x_i - u_i|))`, continuous but not differentiable
+    Continuous,
+    /// `exp(sum(a_i x_i))` if `x_1 <= u_1` and `x_2 <= u_2`, and 0
+    /// otherwise
+    Discontinuous,
+}
+
+/// A function of a [`Family`], where `a` sets how hard it is and `u`
+/// where its features are
+#[derive(Debug, Clone, PartialEq)]
+pub struct Genz {
+    pub family: Family,
+    pub a: Vec<f64>,
+    pub u: Vec<f64>,
+}
+
+impl Genz {
+    pub fn new(family: Family, a: Vec<f64>, u: Vec<f64>) -> Self {
+        assert_eq!(a.len(), u.len(), "expecting as many a as u");
+        assert!(
+            a.iter().all(|a| *a > 0.0),
+            "expecting positive a... found {:?}",
+            a
+        );
+        Self { family, a, u }
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.a.len()
+    }
+
+    pub fn eval(&self, x: &[f64]) -> f64 {
+        let terms = self.a.iter().zip(self.u.iter()).zip(x);
+        match self.family {
+            Family::Oscillatory => {
+                let sum: f64 = self.a.iter().zip(x).map(|(a, x)| a * x).sum();
+                (2.0 * PI * self.u[0] + sum).cos()
+            }
+            Family::ProductPeak => terms
+                .map(|((a, u), x)| 1.0 / (a.powi(-2) + (x - u).powi(2)))
+                .product(),
+            Family::CornerPeak => {
+                let sum: f64 = self.a.iter().zip(x).map(|(a, x)| a * x).sum();
+                (1.0 + sum).powi(-(self.dimensions() as i32 + 1))
+            }
+            Family::Gaussian => terms
+                .map(|((a, u), x)| (-(a * a) * (x - u).powi(2)).exp())
+                .product(),
+            Family::Continuous => terms
+                .map(|((a, u), x)| (-a * (x - u).abs()).exp())
+                .product(),
+            Family::Discontinuous => {
+                if x[0] > self.u[0] || (x.len() > 1 && x[1] > self.u[1]) {
+                    0.0
+                } else {
+                    let sum: f64 = self.a.iter().zip(x).map(|(a, x)| a * x).sum();
+                    sum.exp()
+                }
+            }
+        }
+    }
+
+    /// Over the unit hypercube
+    pub fn integral(&self) -> f64 {
+        let terms = self.a.iter().zip(self.u.iter());
+        match self.family {
+            Family::Oscillatory => {
+                // The real part of e^(i 2 PI u_1) prod((e^(i a) - 1) / (i a))
+                let mut product = ((2.0 * PI * self.u[0]).cos(), (2.0 * PI * self.u[0]).sin());
+                for a in self.a.iter() {
+                    let factor = (a.sin() / a, (1.0 - a.cos()) / a);
+                    product = (
+                        product.0 * factor.0 - product.1 * factor.1,
+                        product.0 * factor.1 + product.1 * factor.0,
+                    );
+                }
+                product.0
+            }
+            Family::ProductPeak => terms
+                .map(|(a, u)| a * ((a * (1.0 - u)).atan() + (a * u).atan()))
+                .product(),
+            Family::CornerPeak => {
+                // Inclusion-exclusion over the corners of the hypercube
+                let d = self.dimensions();
+                let mut sum = 0.0;
+                for corner in 0..1_usize << d {
+                    let dot: f64 = (0..d)
+                        .filter(|i| corner & (1 << i) != 0)
+                        .map(|i| self.a[i])
+                        .sum();
+                    let sign = if corner.count_ones() % 2 == 0 {
+                        1.0
+                    } else {
+                        -1.0
+                    };
+                    sum += sign / (1.0 + dot);
+                }
+                let factorial: f64 = (1..=d).map(|i| i as f64).product();
+                sum / (factorial * self.a.iter().product::<f64>())
+            }
+            Family::Gaussian => terms
+                .map(|(a, u)| PI.sqrt() / (2.0 * a) * (erf(a * (1.0 - u)) + erf(a * u)))
+                .product(),
+            Family::Continuous => terms
+                .map(|(a, u)| (2.0 - (-a * u).exp() - (-a * (1.0 - u)).exp()) / a)
+                .product(),
+            Family::Discontinuous => terms
+                .enumerate()
+                .map(|(i, (a, u))| {
+                    let upper = if i < 2 { u.min(1.0) } else { 1.0 };
+                    ((a * upper).exp() - 1.0) / a
+                })
+                .product(),
+        }
+    }
+}
+
+/// The error function, within `1.2e-7` (from the complementary error
+/// function of Numerical Recipes, section 6.2)
+pub fn erf(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let erfc = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0.0 {
+        1.0 - erfc
+    } else {
+        erfc - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// By the midpoint rule, on a `n^2` grid
+    fn quadrature(g: &Genz, n: usize) -> f64 {
+        let mut sum = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let x = [(i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64];
+                sum += g.eval(&x);
+            }
+        }
+        sum / (n * n) as f64
+    }
+
+    #[test]
+    fn error_function() {
+        assert!(erf(0.0).abs() < 1e-7);
+        assert!((erf(0.5) - 0.5204998778).abs() < 2e-7);
+        assert!((erf(-1.0) + 0.8427007929).abs() < 2e-7);
+        assert!((erf(3.0) - 0.9999779095).abs() < 2e-7);
+    }
+
+    #[test]
+    fn integrals() {
+        // Discontinuities on the edges of the grid, so the midpoint rule
+        // is as accurate as for the others
+        let u = vec![0.3, 0.6];
+        for family in [
+            Family::Oscillatory,
+            Family::ProductPeak,
+            Family::CornerPeak,
+            Family::Gaussian,
+            Family::Continuous,
+            Family::Discontinuous,
+        ] {
+            let g = Genz::new(family, vec![5.0, 3.0], u.clone());
+            let expected = quadrature(&g, 1000);
+            let found = g.integral();
+            assert!(
+                (found - expected).abs() < 1e-5 * (1.0 + expected.abs()),
+                "{:?}: expecting {:.6}... found {:.6}",
+                family,
+                expected,
+                found
+            );
+        }
+    }
+}
@@ -2,11 +2,17 @@ use crate::montecarlo_integrable::MontecarloIntegrable;
 use lesson1_sampling::step_pdf::DiscretePdf;
 use rand::Rng;
 
-fn exp(x: f64) -> f64 {
+/// A narrow spike around 0.5, which uniform samples rarely hit
+pub fn exp(x: f64) -> f64 {
     (-1000.0 * (x - 0.5).powi(2)).exp()
 }
 
-struct Uniform {}
+/// The integral of [`exp`] between 0 and 1 (i.e., `sqrt(PI/1000)`,
+/// as the tails outside of `[0, 1]` are negligible)
+pub const EXP_INTEGRAL: f64 = 0.05604991216397929;
+
+/// Integrates [`exp`] with uniform samples
+pub struct Uniform {}
 impl MontecarloIntegrable for Uniform {
     type T = f64;
 
@@ -18,8 +24,9 @@ impl MontecarloIntegrable for Uniform {
     }
 }
 
-struct Importance {
-    pdf: DiscretePdf,
+/// Integrates [`exp`] with samples drawn from `pdf`
+pub struct Importance {
+    pub pdf: DiscretePdf,
 }
 impl MontecarloIntegrable for Importance {
     type T = f64;
@@ -35,40 +42,29 @@ impl MontecarloIntegrable for Importance {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
-    use std::io::prelude::*;
 
     #[test]
     fn importance_sampling() {
-        // Sample from 0 to 1
-        let mut file = File::create("data/importance_montecarlo.csv").unwrap();
-        file.write_all(b"N,Uniform,Importance,Bad Importance\n")
-            .unwrap();
-
-        for pow in 1..22 {
-            let n = (2 as usize).pow(pow) as usize;
+        let error = |v: f64| (v - EXP_INTEGRAL).abs() / EXP_INTEGRAL;
+        let n = 2_usize.pow(16);
 
-            let rng = Rng::new();
-            let uni = Uniform {};
-            let found_uni = uni.integrate(n, rng);
+        let uni = Uniform {};
+        let found_uni = uni.integrate(n, Rng::with_seed(0));
 
-            let rng = Rng::new();
-            let imp = Importance {
-                pdf: DiscretePdf::new(0.0, vec![0.45, 0.55, 1.0], vec![0.1, 9.1, 0.1]),
-            };
-            let found_imp = imp.integrate(n, rng);
+        let imp = Importance {
+            pdf: DiscretePdf::new(0.0, vec![0.45, 0.55, 1.0], vec![0.1, 9.1, 0.1]),
+        };
+        let found_imp = imp.integrate(n, Rng::with_seed(1));
 
-            let rng = Rng::new();
-            let bad_imp = Importance {
-                pdf: DiscretePdf::new(0.0, vec![0.45, 0.55, 1.0], vec![1.1, 0.1, 1.1]),
-            };
-            let found_bad_imp = bad_imp.integrate(n, rng);
+        let bad_imp = Importance {
+            pdf: DiscretePdf::new(0.0, vec![0.45, 0.55, 1.0], vec![1.1, 0.1, 1.1]),
+        };
+        let found_bad_imp = bad_imp.integrate(n, Rng::with_seed(2));
 
-            let ln = format!(
-                "{},{:.3},{:.3},{:.3}\n",
-                pow, found_uni, found_imp, found_bad_imp
-            );
-            file.write_all(ln.as_bytes()).unwrap();
-        }
+        // Following the spike pays off, and avoiding it does not
+        assert!(error(found_imp) < 0.01, "{:.6}", found_imp);
+        assert!(error(found_uni) < 0.05, "{:.6}", found_uni);
+        assert!(error(found_imp) < error(found_uni));
+        assert!(error(found_bad_imp) > error(found_uni));
     }
 }
@@ -0,0 +1,56 @@
+//! Functions with a known integral, to measure the error of estimators
+
+use crate::montecarlo_integrable::MontecarloIntegrable;
+use rand::Rng;
+
+/// `f(x) = x`, integrated uniformly between 0 and `side`
+#[derive(Debug, Clone, Copy)]
+pub struct Triangle {
+    pub side: f64,
+}
+
+impl Triangle {
+    pub fn analytically_integrate(&self) -> f64 {
+        self.side.powi(2) / 2.0
+    }
+}
+
+impl MontecarloIntegrable for Triangle {
+    type T = f64;
+
+    fn sample(&self, rng: &mut Rng) -> (Self::T, f64) {
+        (self.side * rng.next_float(), 1.0 / self.side)
+    }
+    fn eval(&self, x: Self::T) -> f64 {
+        x
+    }
+}
+
+/// `f(x) = a*x^2 + b*x + c`, integrated uniformly between 0 and `range`
+#[derive(Debug, Clone, Copy)]
+pub struct Quad {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub range: f64,
+}
+
+impl Quad {
+    fn eval_integrated(&self, x: f64) -> f64 {
+        self.a * x.powi(3) / 3.0 + self.b * x.powi(2) / 2.0 + self.c * x
+    }
+    pub fn analytically_integrate(&self) -> f64 {
+        self.eval_integrated(self.range) - self.eval_integrated(0.0)
+    }
+}
+
+impl MontecarloIntegrable for Quad {
+    type T = f64;
+
+    fn sample(&self, rng: &mut Rng) -> (Self::T, f64) {
+        (self.range * rng.next_float(), 1.0 / self.range)
+    }
+    fn eval(&self, x: Self::T) -> f64 {
+        self.a * x * x + self.b * x + self.c
+    }
+}
@@ -1,4 +1,13 @@
+pub mod adaptive;
+pub mod convergence;
+pub mod diagnostics;
+pub mod genz;
 pub mod importance_sampling;
+pub mod integrands;
+pub mod mcmc;
+pub mod miser;
 pub mod montecarlo_integrable;
 pub mod multiple_importance_2;
 pub mod multiple_importance_n;
+pub mod stratified;
+pub mod vegas;
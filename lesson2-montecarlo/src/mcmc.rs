@@ -0,0 +1,446 @@
+//! Markov chain Monte Carlo: samples of densities that are only known up
+//! to a constant (and cannot be inverted), at the price of each sample
+//! depending on the previous one. See [`crate::diagnostics`] for how
+//! much that costs.
+
+use rand::Rng;
+
+/// How [`MetropolisHastings`] moves from a state to a candidate
+pub trait Proposal<T> {
+    fn propose(&mut self, current: &T, rng: &mut Rng) -> T;
+
+    /// `ln q(to | from)`, up to a constant. Only the difference between
+    /// both directions matters, so symmetric proposals can leave it at 0.
+    fn log_density(&self, _from: &T, _to: &T) -> f64 {
+        0.0
+    }
+
+    /// Called after every step, with whether the candidate was accepted
+    fn adapt(&mut self, _accepted: bool) {}
+}
+
+/// `x` plus normal noise with standard deviation `scale`
+pub struct RandomWalk {
+    pub scale: f64,
+}
+
+impl Proposal<f64> for RandomWalk {
+    fn propose(&mut self, current: &f64, rng: &mut Rng) -> f64 {
+        current + self.scale * rng.next_normal()
+    }
+}
+
+/// A [`RandomWalk`] that tunes its scale (by Robbins-Monro) towards an
+/// acceptance rate of `target`, during the first `steps` steps only, as
+/// a proposal that keeps changing may not converge to the target
+pub struct AdaptiveRandomWalk {
+    pub scale: f64,
+    /// The acceptance rate to aim for (0.44 is optimal in 1D)
+    pub target: f64,
+    pub steps: usize,
+    step: usize,
+}
+
+impl AdaptiveRandomWalk {
+    pub fn new(scale: f64, target: f64, steps: usize) -> Self {
+        Self {
+            scale,
+            target,
+            steps,
+            step: 0,
+        }
+    }
+}
+
+impl Proposal<f64> for AdaptiveRandomWalk {
+    fn propose(&mut self, current: &f64, rng: &mut Rng) -> f64 {
+        current + self.scale * rng.next_normal()
+    }
+
+    fn adapt(&mut self, accepted: bool) {
+        if self.step < self.steps {
+            self.step += 1;
+            let rate = (self.step as f64).powf(-0.6);
+            let error = if accepted { 1.0 } else { 0.0 } - self.target;
+            self.scale *= (rate * error).exp();
+        }
+    }
+}
+
+/// Candidates drawn from a fixed density, regardless of the current
+/// state (e.g., the proposal of importance sampling)
+pub struct Independent<S: FnMut(&mut Rng) -> f64, D: Fn(f64) -> f64> {
+    pub sample: S,
+    /// The (log) density `sample` draws from
+    pub log_density: D,
+}
+
+impl<S: FnMut(&mut Rng) -> f64, D: Fn(f64) -> f64> Proposal<f64> for Independent<S, D> {
+    fn propose(&mut self, _current: &f64, rng: &mut Rng) -> f64 {
+        (self.sample)(rng)
+    }
+
+    fn log_density(&self, _from: &f64, to: &f64) -> f64 {
+        (self.log_density)(*to)
+    }
+}
+
+/// Moves from `x` to a candidate `y` of `proposal` with probability
+/// `min(1, p(y) q(x | y) / (p(x) q(y | x)))`, and stays otherwise, so the
+/// states are distributed as `p`, which is given by its logarithm
+/// `log_target` (`-inf` where it is 0)
+pub struct MetropolisHastings<T, F: Fn(&T) -> f64, P: Proposal<T>> {
+    pub log_target: F,
+    pub proposal: P,
+    state: T,
+    log_p: f64,
+    steps: usize,
+    accepted: usize,
+}
+
+impl<T: Clone, F: Fn(&T) -> f64, P: Proposal<T>> MetropolisHastings<T, F, P> {
+    pub fn new(log_target: F, proposal: P, initial: T) -> Self {
+        let log_p = log_target(&initial);
+        assert!(
+            log_p > f64::NEG_INFINITY,
+            "expecting an initial state with a positive density... found 0"
+        );
+        Self {
+            log_target,
+            proposal,
+            state: initial,
+            log_p,
+            steps: 0,
+            accepted: 0,
+        }
+    }
+
+    pub fn state(&self) -> &T {
+        &self.state
+    }
+
+    pub fn step(&mut self, rng: &mut Rng) -> &T {
+        let candidate = self.proposal.propose(&self.state, rng);
+        let log_p = (self.log_target)(&candidate);
+        let log_ratio = log_p - self.log_p + self.proposal.log_density(&candidate, &self.state)
+            - self.proposal.log_density(&self.state, &candidate);
+        let accepted = log_p > f64::NEG_INFINITY && rng.next_float().ln() < log_ratio;
+        if accepted {
+            self.state = candidate;
+            self.log_p = log_p;
+            self.accepted += 1;
+        }
+        self.steps += 1;
+        self.proposal.adapt(accepted);
+        &self.state
+    }
+
+    /// The states after each of `n` steps
+    pub fn sample(&mut self, n: usize, rng: &mut Rng) -> Vec<T> {
+        (0..n).map(|_| self.step(rng).clone()).collect()
+    }
+
+    /// The fraction of the candidates accepted so far
+    pub fn acceptance_rate(&self) -> f64 {
+        self.accepted as f64 / self.steps.max(1) as f64
+    }
+}
+
+/// A step of slice sampling (Neal, 2003): a uniform height below the
+/// density at `x`, and then a uniform point among the ones above that
+/// height, found by stepping out from `x` in steps of `width` and
+/// shrinking back. Returns the new point and how many times it
+/// evaluated `log_target`.
+pub fn slice_step<F: Fn(f64) -> f64>(
+    log_target: F,
+    x: f64,
+    width: f64,
+    rng: &mut Rng,
+) -> (f64, usize) {
+    // At most this many steps out on either side
+    const MAX_STEPS: usize = 100;
+    let height = log_target(x) + (1.0 - rng.next_float()).ln();
+    let mut evaluations = 1;
+
+    let mut lower = x - width * rng.next_float();
+    let mut upper = lower + width;
+    let mut steps = 0;
+    while steps < MAX_STEPS && log_target(lower) > height {
+        lower -= width;
+        steps += 1;
+        evaluations += 1;
+    }
+    evaluations += 1;
+    let mut steps = 0;
+    while steps < MAX_STEPS && log_target(upper) > height {
+        upper += width;
+        steps += 1;
+        evaluations += 1;
+    }
+    evaluations += 1;
+
+    loop {
+        let candidate = lower + rng.next_float() * (upper - lower);
+        evaluations += 1;
+        // Not strict, as the height is the density at `x` itself when the
+        // uniform draw is 0, and then only points at that density qualify
+        if log_target(candidate) >= height {
+            return (candidate, evaluations);
+        }
+        if candidate < x {
+            lower = candidate;
+        } else {
+            upper = candidate;
+        }
+    }
+}
+
+/// Slice sampling of a density over the reals, given by its logarithm
+pub struct Slice<F: Fn(f64) -> f64> {
+    pub log_target: F,
+    /// Of the steps out (about the width of the density works well)
+    pub width: f64,
+    x: f64,
+    evaluations: usize,
+}
+
+impl<F: Fn(f64) -> f64> Slice<F> {
+    pub fn new(log_target: F, width: f64, initial: f64) -> Self {
+        assert!(
+            log_target(initial) > f64::NEG_INFINITY,
+            "expecting an initial state with a positive density... found 0"
+        );
+        Self {
+            log_target,
+            width,
+            x: initial,
+            evaluations: 0,
+        }
+    }
+
+    pub fn step(&mut self, rng: &mut Rng) -> f64 {
+        let (x, evaluations) = slice_step(&self.log_target, self.x, self.width, rng);
+        self.x = x;
+        self.evaluations += evaluations;
+        x
+    }
+
+    pub fn sample(&mut self, n: usize, rng: &mut Rng) -> Vec<f64> {
+        (0..n).map(|_| self.step(rng)).collect()
+    }
+
+    /// Of the density, so far (a slice sampler accepts every step, but
+    /// each step takes several of them)
+    pub fn evaluations(&self) -> usize {
+        self.evaluations
+    }
+}
+
+/// Samples each coordinate in turn from its distribution given the
+/// others. `conditional(i, state, rng)` draws a new `state[i]` (exactly,
+/// or with a step of another sampler, such as [`slice_step`]).
+pub struct Gibbs<C: FnMut(usize, &[f64], &mut Rng) -> f64> {
+    pub conditional: C,
+    state: Vec<f64>,
+}
+
+impl<C: FnMut(usize, &[f64], &mut Rng) -> f64> Gibbs<C> {
+    pub fn new(conditional: C, initial: Vec<f64>) -> Self {
+        Self {
+            conditional,
+            state: initial,
+        }
+    }
+
+    /// Updates every coordinate once
+    pub fn step(&mut self, rng: &mut Rng) -> &[f64] {
+        for i in 0..self.state.len() {
+            self.state[i] = (self.conditional)(i, &self.state, rng);
+        }
+        &self.state
+    }
+
+    pub fn sample(&mut self, n: usize, rng: &mut Rng) -> Vec<Vec<f64>> {
+        (0..n).map(|_| self.step(rng).to_vec()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{autocorrelation, effective_sample_size, r_hat};
+    use crate::multiple_importance_2::{fa, fb};
+    use lesson1_sampling::step_pdf::DiscretePdf;
+
+    /// The fraction of `samples` within each tenth of `[0, 1]`
+    fn steps(samples: &[f64]) -> Vec<f64> {
+        let mut counts = [0.0; 10];
+        for x in samples {
+            counts[((x * 10.0) as usize).min(9)] += 1.0;
+        }
+        counts.iter().map(|c| c / samples.len() as f64).collect()
+    }
+
+    /// Whether `samples` are distributed as `pdf` (whose steps are the
+    /// tenths of `[0, 1]`)
+    fn recovers(pdf: &DiscretePdf, samples: &[f64], tolerance: f64) {
+        for (i, found) in steps(samples).iter().enumerate() {
+            let expected = 0.1 * pdf.pdf((i as f64 + 0.5) / 10.0);
+            assert!(
+                (found - expected).abs() < tolerance,
+                "expecting {:.6} within step {}... found {:.6}",
+                expected,
+                i,
+                found
+            );
+        }
+    }
+
+    fn log_density(pdf: &DiscretePdf) -> impl Fn(f64) -> f64 + '_ {
+        |x| pdf.pdf(x).ln()
+    }
+
+    #[test]
+    fn metropolis_hastings() {
+        let (fa, fb) = (fa(), fb());
+        let mut rng = Rng::with_seed(0);
+        let target = log_density(&fa);
+        let mut chain =
+            MetropolisHastings::new(|x: &f64| target(*x), RandomWalk { scale: 0.2 }, 0.5);
+        // Burn-in
+        chain.sample(1000, &mut rng);
+        let samples = chain.sample(200_000, &mut rng);
+        recovers(&fa, &samples, 0.01);
+        let rate = chain.acceptance_rate();
+        assert!((0.2..0.9).contains(&rate), "{:.6}", rate);
+        let ess = effective_sample_size(&samples);
+        assert!(ess > 4000.0 && ess < 200_000.0, "{:.6}", ess);
+
+        // Uniform candidates, regardless of the state, including the
+        // step of `fb` that is never reached
+        let target = log_density(&fb);
+        let uniform = Independent {
+            sample: |rng: &mut Rng| rng.next_float(),
+            log_density: |_| 0.0,
+        };
+        let mut chain = MetropolisHastings::new(|x: &f64| target(*x), uniform, 0.75);
+        let samples = chain.sample(200_000, &mut rng);
+        recovers(&fb, &samples, 0.01);
+        assert_eq!(steps(&samples)[8], 0.0);
+
+        // Candidates from `fa` need the correction of the proposal
+        let proposal = Independent {
+            sample: |rng: &mut Rng| fa.sample(rng).0,
+            log_density: log_density(&fa),
+        };
+        let mut chain = MetropolisHastings::new(|x: &f64| target(*x), proposal, 0.75);
+        let samples = chain.sample(200_000, &mut rng);
+        recovers(&fb, &samples, 0.015);
+    }
+
+    #[test]
+    fn adaptive_random_walk() {
+        let fa = fa();
+        let target = log_density(&fa);
+        let mut rng = Rng::with_seed(1);
+        // Far too wide to begin with
+        let proposal = AdaptiveRandomWalk::new(5.0, 0.44, 5000);
+        let mut chain = MetropolisHastings::new(|x: &f64| target(*x), proposal, 0.5);
+        chain.sample(5000, &mut rng);
+        let scale = chain.proposal.scale;
+        assert!((0.05..1.0).contains(&scale), "{:.6}", scale);
+
+        let samples = chain.sample(100_000, &mut rng);
+        // No longer adapting
+        assert_eq!(chain.proposal.scale, scale);
+        let rate = chain.acceptance_rate();
+        assert!((rate - 0.44).abs() < 0.05, "{:.6}", rate);
+        recovers(&fa, &samples, 0.015);
+    }
+
+    #[test]
+    fn slice_sampling() {
+        let fb = fb();
+        let mut rng = Rng::with_seed(2);
+        let mut slice = Slice::new(log_density(&fb), 0.5, 0.75);
+        let samples = slice.sample(100_000, &mut rng);
+        recovers(&fb, &samples, 0.01);
+        assert_eq!(steps(&samples)[8], 0.0);
+        // A few evaluations per step, but most slices above the lower
+        // steps only span one of the modes, which slows down the mixing
+        assert!(slice.evaluations() < 10 * samples.len());
+        let ess = effective_sample_size(&samples);
+        assert!(ess > 2000.0, "{:.6}", ess);
+
+        // Chains from different places agree
+        let chains: Vec<Vec<f64>> = [0.05, 0.35, 0.75, 0.95]
+            .iter()
+            .map(|x| Slice::new(log_density(&fb), 0.5, *x).sample(5000, &mut rng))
+            .collect();
+        let found = r_hat(&chains);
+        assert!(found < 1.05, "{:.6}", found);
+    }
+
+    #[test]
+    fn slice_at_the_density() {
+        // A seed whose first draw is exactly 0, so the height of the
+        // slice is the density at `x`
+        let seed = 0xa07a3e6c140b8421;
+        assert_eq!(Rng::with_seed(seed).next_float(), 0.0);
+        let flat = |x: f64| {
+            if (0.0..1.0).contains(&x) {
+                0.0
+            } else {
+                f64::NEG_INFINITY
+            }
+        };
+        let (x, _) = slice_step(flat, 0.5, 0.5, &mut Rng::with_seed(seed));
+        assert!((0.0..1.0).contains(&x), "{}", x);
+    }
+
+    #[test]
+    fn gibbs_sampling() {
+        // fa(x) fb(y), sampled exactly along each coordinate
+        let (fa, fb) = (fa(), fb());
+        let mut rng = Rng::with_seed(3);
+        let mut gibbs = Gibbs::new(
+            |i: usize, _: &[f64], rng: &mut Rng| {
+                if i == 0 {
+                    fa.sample(rng).0
+                } else {
+                    fb.sample(rng).0
+                }
+            },
+            vec![0.5, 0.5],
+        );
+        let samples = gibbs.sample(50_000, &mut rng);
+        let xs: Vec<f64> = samples.iter().map(|s| s[0]).collect();
+        let ys: Vec<f64> = samples.iter().map(|s| s[1]).collect();
+        recovers(&fa, &xs, 0.01);
+        recovers(&fb, &ys, 0.01);
+
+        // A normal with correlation rho, with slice steps as conditionals:
+        // the chain of x then has an autocorrelation of rho^2
+        let rho: f64 = 0.8;
+        let log_target = |x: f64, y: f64| -(x * x - 2.0 * rho * x * y + y * y) / 2.0;
+        let mut gibbs = Gibbs::new(
+            |i: usize, state: &[f64], rng: &mut Rng| {
+                let other = state[1 - i];
+                slice_step(|x| log_target(x, other), state[i], 2.0, rng).0
+            },
+            vec![0.0, 0.0],
+        );
+        let samples = gibbs.sample(50_000, &mut rng);
+        let xs: Vec<f64> = samples.iter().map(|s| s[0]).collect();
+        let ys: Vec<f64> = samples.iter().map(|s| s[1]).collect();
+        let variance = xs.iter().map(|x| x * x).sum::<f64>() / xs.len() as f64;
+        // The conditionals have a variance of 1 - rho^2
+        let expected = 1.0 / (1.0 - rho * rho);
+        assert!((variance / expected - 1.0).abs() < 0.1, "{:.6}", variance);
+        let correlation =
+            xs.iter().zip(ys.iter()).map(|(x, y)| x * y).sum::<f64>() / xs.len() as f64 / variance;
+        assert!((correlation - rho).abs() < 0.05, "{:.6}", correlation);
+        let lag = autocorrelation(&xs, 1);
+        assert!((lag - rho * rho).abs() < 0.1, "{:.6}", lag);
+    }
+}
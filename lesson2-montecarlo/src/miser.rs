@@ -0,0 +1,236 @@
+//! MISER (Press and Farrar, 1990): recursive stratified sampling of the
+//! unit hypercube, which spends more samples on the halves where the
+//! integrand varies more
+
+use crate::montecarlo_integrable::{Estimate, MontecarloIntegrable};
+use rand::Rng;
+
+/// Integrates `f` over `[0, 1]^dimensions`. Each region explores a
+/// fraction of its samples to find the dimension whose halves have the
+/// smallest total standard deviation, splits in two along it, and shares
+/// the rest of its samples between the halves in proportion to their
+/// standard deviations.
+pub struct Miser<F: Fn(&[f64]) -> f64> {
+    pub f: F,
+    pub dimensions: usize,
+    /// Regions with fewer samples are not split, but sampled uniformly
+    pub min_samples: usize,
+    /// The fraction of the samples of a region spent choosing how to
+    /// split it (which are not part of the estimate)
+    pub explore: f64,
+}
+
+impl<F: Fn(&[f64]) -> f64> Miser<F> {
+    /// Splitting regions with at least 60 samples, 10% of which explore
+    pub fn new(f: F, dimensions: usize) -> Self {
+        Self {
+            f,
+            dimensions,
+            min_samples: 60,
+            explore: 0.1,
+        }
+    }
+
+    /// Evaluates `f` `budget` times
+    pub fn run(&self, budget: usize, rng: &mut Rng) -> Estimate {
+        assert!(
+            budget > 1,
+            "expecting at least 2 samples... found {}",
+            budget
+        );
+        let mut lower = vec![0.0; self.dimensions];
+        let mut upper = vec![1.0; self.dimensions];
+        let (mean, variance) = self.region(&mut lower, &mut upper, budget, rng);
+        Estimate {
+            value: mean,
+            error: variance.sqrt(),
+        }
+    }
+
+    /// The mean of `f` within the region, and the variance of the mean
+    fn region(&self, lower: &mut [f64], upper: &mut [f64], n: usize, rng: &mut Rng) -> (f64, f64) {
+        // Enough to explore, and to leave a few samples to each half
+        let explore = ((n as f64 * self.explore) as usize).max(15);
+        if n < self.min_samples.max(explore + 4) {
+            return self.uniform(lower, upper, n.max(2), rng);
+        }
+
+        // The samples of each half, along each dimension
+        let mut halves = vec![[Moments::default(), Moments::default()]; self.dimensions];
+        let mut x = vec![0.0; self.dimensions];
+        for _ in 0..explore {
+            self.point(lower, upper, &mut x, rng);
+            let fx = (self.f)(&x);
+            for (d, half) in halves.iter_mut().enumerate() {
+                let middle = 0.5 * (lower[d] + upper[d]);
+                half[(x[d] >= middle) as usize].add(fx);
+            }
+        }
+        let split = halves
+            .iter()
+            .enumerate()
+            .filter(|(_, [l, r])| l.n > 1 && r.n > 1)
+            .map(|(d, [l, r])| (d, l.deviation(), r.deviation()))
+            .min_by(|a, b| (a.1 + a.2).total_cmp(&(b.1 + b.2)));
+        let (d, left, right) = split.unwrap_or((0, 1.0, 1.0));
+
+        // At least 2 samples each, and the rest in proportion to their
+        // standard deviations
+        let rest = n - explore - 4;
+        let fraction = if left + right > 0.0 {
+            left / (left + right)
+        } else {
+            0.5
+        };
+        let n_left = 2 + (rest as f64 * fraction).round() as usize;
+        let n_right = n - explore - n_left;
+
+        let middle = 0.5 * (lower[d] + upper[d]);
+        let old_upper = upper[d];
+        upper[d] = middle;
+        let (mean_left, variance_left) = self.region(lower, upper, n_left, rng);
+        upper[d] = old_upper;
+        let old_lower = lower[d];
+        lower[d] = middle;
+        let (mean_right, variance_right) = self.region(lower, upper, n_right, rng);
+        lower[d] = old_lower;
+
+        (
+            0.5 * (mean_left + mean_right),
+            0.25 * (variance_left + variance_right),
+        )
+    }
+
+    fn uniform(&self, lower: &[f64], upper: &[f64], n: usize, rng: &mut Rng) -> (f64, f64) {
+        let mut moments = Moments::default();
+        let mut x = vec![0.0; self.dimensions];
+        for _ in 0..n {
+            self.point(lower, upper, &mut x, rng);
+            moments.add((self.f)(&x));
+        }
+        (moments.mean(), moments.deviation().powi(2) / n as f64)
+    }
+
+    fn point(&self, lower: &[f64], upper: &[f64], x: &mut [f64], rng: &mut Rng) {
+        for (d, x) in x.iter_mut().enumerate() {
+            *x = lower[d] + rng.next_float() * (upper[d] - lower[d]);
+        }
+    }
+}
+
+/// Of some values, to find their mean and standard deviation
+#[derive(Debug, Clone, Copy, Default)]
+struct Moments {
+    n: usize,
+    sum: f64,
+    sum_squares: f64,
+}
+
+impl Moments {
+    fn add(&mut self, x: f64) {
+        self.n += 1;
+        self.sum += x;
+        self.sum_squares += x * x;
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum / self.n as f64
+    }
+
+    /// Unbiased
+    fn deviation(&self) -> f64 {
+        let n = self.n as f64;
+        ((self.sum_squares - self.sum * self.sum / n).max(0.0) / (n - 1.0)).sqrt()
+    }
+}
+
+impl<F: Fn(&[f64]) -> f64> MontecarloIntegrable for Miser<F> {
+    type T = Vec<f64>;
+
+    /// A uniform sample, as the regions only exist within `integrate`
+    fn sample(&self, rng: &mut Rng) -> (Self::T, f64) {
+        (
+            (0..self.dimensions).map(|_| rng.next_float()).collect(),
+            1.0,
+        )
+    }
+    fn eval(&self, x: Self::T) -> f64 {
+        (self.f)(&x)
+    }
+
+    fn integrate(&self, n: usize, mut rng: Rng) -> f64 {
+        self.run(n, &mut rng).value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genz::{Family, Genz};
+
+    #[test]
+    fn genz() {
+        let u = vec![0.3, 0.6, 0.45];
+        for (family, a) in [
+            (Family::Oscillatory, 2.0),
+            (Family::ProductPeak, 8.0),
+            (Family::CornerPeak, 2.0),
+            (Family::Gaussian, 8.0),
+            (Family::Continuous, 5.0),
+            (Family::Discontinuous, 2.0),
+        ] {
+            let g = Genz::new(family, vec![a; 3], u.clone());
+            let miser = Miser::new(|x: &[f64]| g.eval(x), 3);
+            let found = miser.run(100_000, &mut Rng::with_seed(0));
+            let expected = g.integral();
+            assert!(
+                (found.value - expected).abs() < 4.0 * found.error,
+                "{:?}: expecting {:.6}... found {:.6} +- {:e}",
+                family,
+                expected,
+                found.value,
+                found.error
+            );
+            // The oscillatory one (with both signs, and not separable) is
+            // the hardest to adapt to
+            assert!(
+                found.error < 0.03 * expected.abs(),
+                "{:?} {:?}",
+                family,
+                found
+            );
+        }
+    }
+
+    #[test]
+    fn stratification() {
+        // Most of the variance is in a corner, where MISER keeps splitting
+        let g = Genz::new(Family::Discontinuous, vec![3.0; 2], vec![0.2, 0.3]);
+        let miser = Miser::new(|x: &[f64]| g.eval(x), 2);
+        let expected = g.integral();
+        let n = 50_000;
+        let found = miser.run(n, &mut Rng::with_seed(1));
+        assert!(
+            (found.value - expected).abs() < 4.0 * found.error,
+            "expecting {:.6}... found {:.6} +- {:e}",
+            expected,
+            found.value,
+            found.error
+        );
+
+        // Uniformly, in a single region
+        let mut uniform = Miser::new(|x: &[f64]| g.eval(x), 2);
+        uniform.min_samples = n + 1;
+        let plain = uniform.run(n, &mut Rng::with_seed(1));
+        assert!(found.error < plain.error / 3.0, "{:?} {:?}", found, plain);
+
+        let within: usize = (0..20)
+            .filter(|seed| {
+                let found = miser.run(10_000, &mut Rng::with_seed(10 + seed));
+                (found.value - expected).abs() < 2.0 * found.error
+            })
+            .count();
+        assert!(within >= 15, "{} of 20 within 2 errors", within);
+        assert_eq!(miser.integrate(n, Rng::with_seed(1)), found.value);
+    }
+}
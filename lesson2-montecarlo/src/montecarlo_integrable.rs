@@ -1,6 +1,13 @@
 use rand::Rng;
 use std::fmt::Debug;
 
+/// An integral, estimated with samples, and its standard error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub value: f64,
+    pub error: f64,
+}
+
 pub trait MontecarloIntegrable {
     type T: Debug + Clone;
 
@@ -25,57 +32,14 @@ pub trait MontecarloIntegrable {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
-    use std::io::prelude::*;
-
-    struct Triangle {
-        side: f64,
-    }
-
-    impl MontecarloIntegrable for Triangle {
-        type T = f64;
-
-        fn sample(&self, rng: &mut Rng) -> (Self::T, f64) {
-            (self.side * rng.next_float(), 1.0 / self.side)
-        }
-        fn eval(&self, x: Self::T) -> f64 {
-            x
-        }
-    }
-
-    struct Quad {
-        a: f64,
-        b: f64,
-        c: f64,
-        range: f64,
-    }
-
-    impl Quad {
-        fn eval_integrated(&self, x: f64) -> f64 {
-            self.a * x.powi(3) / 3.0 + self.b * x.powi(2) / 2.0 + self.c * x
-        }
-        fn analytically_integrate(&self) -> f64 {
-            self.eval_integrated(self.range) - self.eval_integrated(0.0)
-        }
-    }
-
-    impl MontecarloIntegrable for Quad {
-        type T = f64;
-
-        fn sample(&self, rng: &mut Rng) -> (Self::T, f64) {
-            (self.range * rng.next_float(), 1.0 / self.range)
-        }
-        fn eval(&self, x: Self::T) -> f64 {
-            self.a * x * x + self.b * x + self.c
-        }
-    }
+    use crate::convergence::log_log_slope;
+    use crate::integrands::{Quad, Triangle};
+    use plot::{Chart, Series};
 
     #[test]
     fn naive_montecarlo() {
-        let mut file = File::create("data/uniform_montecarlo.csv").unwrap();
-        file.write_all(b"pow,err_triangle,err_quad\n").unwrap();
         let tri = Triangle { side: 4.0 };
-        let exp_tri = tri.side.powi(2) / 2.0;
+        let exp_tri = tri.analytically_integrate();
         let quad = Quad {
             range: 4.0,
             a: 1.0,
@@ -84,21 +48,38 @@ mod tests {
         };
         let exp_quad = quad.analytically_integrate();
 
+        let mut errors_triangle = Vec::new();
+        let mut errors_quad = Vec::new();
         for pow in 1..25 {
-            let n = (2 as usize).pow(pow as u32) as usize;
+            let n = 2_usize.pow(pow);
             // Triangle
-            let rng = Rng::new();
+            let rng = Rng::with_seed(2 * pow as u64);
             let found_tri = tri.integrate(n, rng);
             let err_triangle = (exp_tri - found_tri).abs() / exp_tri;
 
             // Quad
-            let rng = Rng::new();
+            let rng = Rng::with_seed(2 * pow as u64 + 1);
             let found_quad = quad.integrate(n, rng);
             let err_quad = (exp_quad - found_quad).abs() / exp_quad;
 
-            // report
-            let ln = format!("{},{:.3},{:.3}\n", pow, err_triangle, err_quad,);
-            file.write_all(ln.as_bytes()).unwrap();
+            errors_triangle.push((n as f64, err_triangle));
+            errors_quad.push((n as f64, err_quad));
         }
+
+        // The error shrinks as N^-1/2 (give or take, as it is a single run)
+        for errors in [&errors_triangle, &errors_quad] {
+            let slope = log_log_slope(errors);
+            assert!((slope + 0.5).abs() < 0.25, "slope {:.6}", slope);
+            assert!(errors.last().unwrap().1 < 1e-3);
+        }
+
+        let chart = Chart::convergence(
+            "Uniform Monte Carlo",
+            vec![
+                Series::new("triangle", errors_triangle),
+                Series::new("quad", errors_quad),
+            ],
+        );
+        println!("{}", chart.to_ascii(60, 16));
     }
 }
@@ -2,18 +2,39 @@ use crate::montecarlo_integrable::MontecarloIntegrable;
 use lesson1_sampling::step_pdf::DiscretePdf;
 use rand::Rng;
 
-struct MISSingle {
-    a: DiscretePdf,
-    b: DiscretePdf,
-    sampling: DiscretePdf,
+/// The integral of `a(x)*b(x)` between 0 and 1 (see [`fa`] and [`fb`])
+pub const EXPECTED: f64 = 0.8448;
+
+/// The first factor of the integrand
+pub fn fa() -> DiscretePdf {
+    DiscretePdf::new(
+        0.0,
+        vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0],
+        vec![0.5, 1.4, 3.2, 3.0, 0.5, 0.1, 0.1, 0.5, 0.1, 0.6],
+    )
+}
+
+/// The second factor of the integrand
+pub fn fb() -> DiscretePdf {
+    DiscretePdf::new(
+        0.0,
+        vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0],
+        vec![2.0, 1.0, 0.8, 0.1, 0.02, 0.04, 0.1, 4.0, 0.0, 1.94],
+    )
+}
+
+/// Integrates `a(x)*b(x)` with samples from a single distribution
+pub struct MISSingle {
+    pub a: DiscretePdf,
+    pub b: DiscretePdf,
+    pub sampling: DiscretePdf,
 }
 
 impl MontecarloIntegrable for MISSingle {
     type T = f64;
 
     fn sample(&self, rng: &mut Rng) -> (Self::T, f64) {
-        let ret = self.sampling.sample(rng);
-        ret
+        self.sampling.sample(rng)
     }
 
     fn eval(&self, x: Self::T) -> f64 {
@@ -25,18 +46,19 @@ impl MontecarloIntegrable for MISSingle {
     }
 }
 
-#[allow(dead_code)]
-enum MISHeuristic {
+pub enum MISHeuristic {
     Balance,
     Power,
 }
 
-struct MIS {
-    a: DiscretePdf,
-    b: DiscretePdf,
-    na: usize,
-    nb: usize,
-    heuristic: MISHeuristic,
+/// Integrates `a(x)*b(x)` combining `na` samples from `a` and
+/// `nb` from `b` with multiple importance sampling
+pub struct MIS {
+    pub a: DiscretePdf,
+    pub b: DiscretePdf,
+    pub na: usize,
+    pub nb: usize,
+    pub heuristic: MISHeuristic,
 }
 
 impl MIS {
@@ -91,25 +113,15 @@ impl MontecarloIntegrable for MIS {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
-    use std::io::prelude::*;
+    use plot::{Chart, Series};
 
     #[test]
     fn multiple_importance() {
-        const EXPECTED: f64 = 0.8448;
         fn error(v: f64) -> f64 {
             (v - EXPECTED).abs() / EXPECTED
         }
-        let fa = DiscretePdf::new(
-            0.0,
-            vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0],
-            vec![0.5, 1.4, 3.2, 3.0, 0.5, 0.1, 0.1, 0.5, 0.1, 0.6],
-        );
-        let fb = DiscretePdf::new(
-            0.0,
-            vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0],
-            vec![2.0, 1.0, 0.8, 0.1, 0.02, 0.04, 0.1, 4.0, 0.0, 1.94],
-        );
+        let fa = fa();
+        let fb = fb();
         let uniform = DiscretePdf::new(0.0, vec![1.0], vec![1.]);
 
         let uni = MISSingle {
@@ -142,38 +154,35 @@ mod tests {
             heuristic: MISHeuristic::Power,
         };
 
-        let mut file = File::create("data/mis_montecarlo.csv").unwrap();
-
-        file.write_all(b"N,Uniform,A,B,Balanced MIS,Power MIS\n")
-            .unwrap();
+        let labels = ["Uniform", "A", "B", "Balanced MIS", "Power MIS"];
+        let mut errors = vec![Vec::new(); labels.len()];
         for pow in 1..16 {
-            let n = (2 as usize).pow(pow) as usize;
-
-            let rng = Rng::new();
-            let found_uni = uni.integrate(n, rng);
-
-            let rng = Rng::new();
-            let found_a = a.integrate(n, rng);
-
-            let rng = Rng::new();
-            let found_b = b.integrate(n, rng);
-
-            let rng = Rng::new();
-            let found_mis = balanced.integrate(n, rng);
-
-            let rng = Rng::new();
-            let found_power_mis = power_mis.integrate(n, rng);
+            let n = 2_usize.pow(pow);
+            let seed = 5 * pow as u64;
+            let found_uni = uni.integrate(n, Rng::with_seed(seed));
+            let found_a = a.integrate(n, Rng::with_seed(seed + 1));
+            let found_b = b.integrate(n, Rng::with_seed(seed + 2));
+            let found_mis = balanced.integrate(n, Rng::with_seed(seed + 3));
+            let found_power_mis = power_mis.integrate(n, Rng::with_seed(seed + 4));
+
+            let found = [found_uni, found_a, found_b, found_mis, found_power_mis];
+            for (e, found) in errors.iter_mut().zip(found) {
+                e.push((n as f64, error(found)));
+            }
+        }
 
-            let ln = format!(
-                "{},{:.3},{:.3},{:.3},{:.3},{:.3}\n",
-                pow,
-                error(found_uni),
-                error(found_a),
-                error(found_b),
-                error(found_mis),
-                error(found_power_mis),
-            );
-            file.write_all(ln.as_bytes()).unwrap();
+        // Every estimator converges
+        for (label, e) in labels.iter().zip(errors.iter()) {
+            let last = e.last().unwrap().1;
+            assert!(last < 0.05, "{} has an error of {:.6}", label, last);
         }
+
+        let series = labels
+            .iter()
+            .zip(errors)
+            .map(|(label, e)| Series::new(label, e))
+            .collect();
+        let chart = Chart::convergence("Multiple importance sampling", series);
+        println!("{}", chart.to_ascii(60, 16));
     }
 }
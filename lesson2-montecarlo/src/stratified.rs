@@ -0,0 +1,101 @@
+//! Samplers of `[0, 1]` that spread their samples more evenly than
+//! independent uniform ones, so the error shrinks faster than `N^-1/2`
+//! for smooth integrands.
+
+use crate::montecarlo_integrable::MontecarloIntegrable;
+use rand::Rng;
+
+/// Integrates `f` over `[0, 1]` with one uniform sample in each of
+/// `n` equal strata
+pub struct Stratified<F: Fn(f64) -> f64> {
+    pub f: F,
+}
+
+impl<F: Fn(f64) -> f64> MontecarloIntegrable for Stratified<F> {
+    type T = f64;
+
+    /// A single sample has a single stratum
+    fn sample(&self, rng: &mut Rng) -> (Self::T, f64) {
+        (rng.next_float(), 1.0)
+    }
+    fn eval(&self, x: Self::T) -> f64 {
+        (self.f)(x)
+    }
+
+    fn integrate(&self, n: usize, mut rng: Rng) -> f64 {
+        let mut res = 0.0;
+        for i in 0..n {
+            let x = (i as f64 + rng.next_float()) / n as f64;
+            res += self.eval(x);
+        }
+        res / n as f64
+    }
+}
+
+/// The radical inverse in base 2 of `i` (i.e., its bits mirrored
+/// around the decimal point), the `i`-th point of the van der Corput
+/// sequence
+pub fn van_der_corput(i: u64) -> f64 {
+    i.reverse_bits() as f64 / 2f64.powi(64)
+}
+
+/// Integrates `f` over `[0, 1]` with the van der Corput sequence,
+/// shifted by a random offset (modulo 1) so the estimate is unbiased
+/// and independent runs give different results (i.e., randomized QMC)
+pub struct QuasiMonteCarlo<F: Fn(f64) -> f64> {
+    pub f: F,
+}
+
+impl<F: Fn(f64) -> f64> MontecarloIntegrable for QuasiMonteCarlo<F> {
+    type T = f64;
+
+    fn sample(&self, rng: &mut Rng) -> (Self::T, f64) {
+        (rng.next_float(), 1.0)
+    }
+    fn eval(&self, x: Self::T) -> f64 {
+        (self.f)(x)
+    }
+
+    fn integrate(&self, n: usize, mut rng: Rng) -> f64 {
+        let shift = rng.next_float();
+        let mut res = 0.0;
+        for i in 0..n {
+            let x = (van_der_corput(i as u64) + shift).fract();
+            res += self.eval(x);
+        }
+        res / n as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn van_der_corput_sequence() {
+        let points: Vec<f64> = (0..8).map(van_der_corput).collect();
+        assert_eq!(
+            points,
+            vec![0., 0.5, 0.25, 0.75, 0.125, 0.625, 0.375, 0.875]
+        );
+    }
+
+    #[test]
+    fn evenly_spread() {
+        // With a single sample, stratification is plain Monte Carlo...
+        let stratified = Stratified {
+            f: |x: f64| 2.0 * x,
+        };
+        let found = stratified.integrate(1, Rng::with_seed(0));
+        assert!((0.0..2.0).contains(&found));
+        // ... but with many, each one only varies within its stratum
+        let found = stratified.integrate(1000, Rng::with_seed(0));
+        assert!((found - 1.0).abs() < 1e-4, "{:.6}", found);
+
+        let qmc = QuasiMonteCarlo {
+            f: |x: f64| 2.0 * x,
+        };
+        let found = qmc.integrate(1024, Rng::with_seed(0));
+        assert!((found - 1.0).abs() < 2e-3, "{:.6}", found);
+    }
+}
@@ -0,0 +1,205 @@
+//! VEGAS (Lepage, 1978): importance sampling of the unit hypercube from
+//! a separable PDF, i.e., an adaptive [`Grid`] per dimension
+
+use crate::adaptive::{inverse_variance, Grid};
+use crate::montecarlo_integrable::{Estimate, MontecarloIntegrable};
+use rand::Rng;
+
+/// Integrates `f` over `[0, 1]^dimensions` in rounds, refining the grid
+/// of each dimension after every round with the `(f / pdf)^2` of the
+/// samples that fell in each of its steps. Works best when `f` is
+/// (close to) a product of functions of each dimension.
+pub struct Vegas<F: Fn(&[f64]) -> f64> {
+    pub f: F,
+    pub dimensions: usize,
+    /// Of each grid
+    pub bins: usize,
+    /// Among which the budget is split
+    pub iterations: usize,
+    /// How much the grids change in each iteration (see
+    /// [`crate::adaptive::AdaptiveImportance::alpha`])
+    pub alpha: f64,
+}
+
+impl<F: Fn(&[f64]) -> f64> Vegas<F> {
+    /// With 50 bins per grid, 10 iterations and an `alpha` of 0.5
+    pub fn new(f: F, dimensions: usize) -> Self {
+        Self {
+            f,
+            dimensions,
+            bins: 50,
+            iterations: 10,
+            alpha: 0.5,
+        }
+    }
+
+    /// Evaluates `f` about `budget` times, and combines the estimates of
+    /// all the iterations with [`inverse_variance`] (so the error is 0 only
+    /// if no iteration found any variance)
+    pub fn run(&self, budget: usize, rng: &mut Rng) -> Estimate {
+        let samples = budget / self.iterations.max(1);
+        assert!(
+            samples > 1,
+            "expecting at least 2 samples per iteration... found {}",
+            samples
+        );
+        let mut grids = vec![Grid::uniform(self.bins); self.dimensions];
+        let mut x = vec![0.0; self.dimensions];
+        let mut bins = vec![0; self.dimensions];
+        let mut estimates = Vec::with_capacity(self.iterations);
+        for _ in 0..self.iterations {
+            let mut contributions = vec![vec![0.0; self.bins]; self.dimensions];
+            let (mut sum, mut sum_squares) = (0.0, 0.0);
+            for _ in 0..samples {
+                let mut p = 1.0;
+                for (d, grid) in grids.iter().enumerate() {
+                    let (xd, bin, pd) = grid.sample(rng.next_float());
+                    x[d] = xd;
+                    bins[d] = bin;
+                    p *= pd;
+                }
+                let weight = (self.f)(&x) / p;
+                sum += weight;
+                sum_squares += weight * weight;
+                for (d, bin) in bins.iter().enumerate() {
+                    contributions[d][*bin] += weight * weight;
+                }
+            }
+            let n = samples as f64;
+            let estimate = sum / n;
+            let variance = (sum_squares / n - estimate * estimate).max(0.0) / (n - 1.0);
+            estimates.push((estimate, variance));
+            for (grid, contributions) in grids.iter_mut().zip(contributions.iter()) {
+                grid.refine(contributions, self.alpha);
+            }
+        }
+        let (value, variance) = inverse_variance(estimates);
+        Estimate {
+            value,
+            error: variance.sqrt(),
+        }
+    }
+}
+
+impl<F: Fn(&[f64]) -> f64> MontecarloIntegrable for Vegas<F> {
+    type T = Vec<f64>;
+
+    /// A uniform sample, as the grids only exist within `integrate`
+    fn sample(&self, rng: &mut Rng) -> (Self::T, f64) {
+        (
+            (0..self.dimensions).map(|_| rng.next_float()).collect(),
+            1.0,
+        )
+    }
+    fn eval(&self, x: Self::T) -> f64 {
+        (self.f)(&x)
+    }
+
+    fn integrate(&self, n: usize, mut rng: Rng) -> f64 {
+        self.run(n, &mut rng).value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genz::{Family, Genz};
+
+    /// The standard error of plain Monte Carlo with `n` samples
+    fn plain_error(g: &Genz, n: usize, rng: &mut Rng) -> f64 {
+        let values: Vec<f64> = (0..n)
+            .map(|_| {
+                let x: Vec<f64> = (0..g.dimensions()).map(|_| rng.next_float()).collect();
+                g.eval(&x)
+            })
+            .collect();
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+        (variance / n as f64).sqrt()
+    }
+
+    #[test]
+    fn genz() {
+        let u = vec![0.3, 0.6, 0.45];
+        for (family, a) in [
+            (Family::Oscillatory, 2.0),
+            (Family::ProductPeak, 8.0),
+            (Family::CornerPeak, 2.0),
+            (Family::Gaussian, 8.0),
+            (Family::Continuous, 5.0),
+            (Family::Discontinuous, 2.0),
+        ] {
+            let g = Genz::new(family, vec![a; 3], u.clone());
+            let vegas = Vegas::new(|x: &[f64]| g.eval(x), 3);
+            let found = vegas.run(100_000, &mut Rng::with_seed(0));
+            let expected = g.integral();
+            assert!(
+                (found.value - expected).abs() < 4.0 * found.error,
+                "{:?}: expecting {:.6}... found {:.6} +- {:e}",
+                family,
+                expected,
+                found.value,
+                found.error
+            );
+            // The oscillatory one (with both signs, and not separable) is
+            // the hardest to adapt to
+            assert!(
+                found.error < 0.03 * expected.abs(),
+                "{:?} {:?}",
+                family,
+                found
+            );
+        }
+    }
+
+    #[test]
+    fn product_peak() {
+        // Narrow in 4 dimensions, so uniform samples rarely hit it
+        let g = Genz::new(Family::Gaussian, vec![20.0; 4], vec![0.4; 4]);
+        let vegas = Vegas::new(|x: &[f64]| g.eval(x), 4);
+        let n = 100_000;
+        let found = vegas.run(n, &mut Rng::with_seed(1));
+        let expected = g.integral();
+        assert!(
+            (found.value - expected).abs() < 4.0 * found.error,
+            "expecting {:e}... found {:e} +- {:e}",
+            expected,
+            found.value,
+            found.error
+        );
+        let plain = plain_error(&g, n, &mut Rng::with_seed(2));
+        assert!(found.error < plain / 20.0, "{:e} {:e}", found.error, plain);
+
+        // It is unbiased: over many runs, the errors are about the
+        // reported ones
+        let within: usize = (0..20)
+            .filter(|seed| {
+                let found = vegas.run(20_000, &mut Rng::with_seed(10 + seed));
+                (found.value - expected).abs() < 2.0 * found.error
+            })
+            .count();
+        assert!(within >= 15, "{} of 20 within 2 errors", within);
+
+        // And works as any other integrator
+        let value = vegas.integrate(n, Rng::with_seed(1));
+        assert_eq!(value, found.value);
+    }
+
+    #[test]
+    fn tiny_box() {
+        // The first iterations (most likely) miss it, and have no variance
+        let side = 0.02;
+        let inside = |x: &[f64]| x.iter().all(|x| (0.3..0.3 + side).contains(x));
+        let vegas = Vegas::new(|x: &[f64]| if inside(x) { 1.0 } else { 0.0 }, 2);
+        let found = vegas.run(4000, &mut Rng::with_seed(0));
+        let expected = side * side;
+        assert!(found.error > 0.0);
+        assert!(
+            (found.value / expected - 1.0).abs() < 0.1,
+            "expecting {:e}... found {:e} +- {:e}",
+            expected,
+            found.value,
+            found.error
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lesson1_sampling::step_pdf::DiscretePdf;
+use lesson3_russian_roulette::MIS;
+use rand::Rng;
+
+fn fa() -> DiscretePdf {
+    DiscretePdf::new(
+        0.0,
+        vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0],
+        vec![0.5, 1.4, 3.2, 3.0, 0.5, 0.1, 0.1, 0.5, 0.1, 0.6],
+    )
+}
+
+fn fb() -> DiscretePdf {
+    DiscretePdf::new(
+        0.0,
+        vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0],
+        vec![2.0, 1.0, 0.8, 0.1, 0.02, 0.04, 0.1, 4.0, 0.0, 1.94],
+    )
+}
+
+fn roulette(c: &mut Criterion) {
+    let uniform = DiscretePdf::new(0.0, vec![1.0], vec![1.]);
+    let q0 = MIS::new(fa(), fb(), uniform.clone(), false);
+    let q2 = MIS::new(fa(), fb(), uniform, true);
+
+    let mut group = c.benchmark_group("roulette");
+    group.bench_function("no-roulette", |b| {
+        b.iter(|| q0.integrate(black_box(1024), Rng::with_seed(0)))
+    });
+    group.bench_function("roulette", |b| {
+        b.iter(|| q2.integrate(black_box(1024), Rng::with_seed(0)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, roulette);
+criterion_main!(benches);
@@ -0,0 +1,158 @@
+use rand::Rng;
+use std::cell::Cell;
+
+/// A function whose evaluations are being counted
+struct CostEntry {
+    name: String,
+    weight: f64,
+    calls: Cell<usize>,
+}
+
+/// Models the cost of an estimator by counting how many times
+/// each of its functions is evaluated.
+///
+/// Every function can have a weight (i.e., the cost of a single call),
+/// so expensive functions can be told apart from cheap ones without
+/// having to time them.
+#[derive(Default)]
+pub struct CostModel {
+    entries: Vec<CostEntry>,
+}
+
+impl CostModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a function with a cost of 1.0 per call,
+    /// returning the index to be used when recording calls
+    pub fn register(&mut self, name: &str) -> usize {
+        self.register_weighted(name, 1.0)
+    }
+
+    /// Registers a function with a cost of `weight` per call,
+    /// returning the index to be used when recording calls
+    pub fn register_weighted(&mut self, name: &str, weight: f64) -> usize {
+        assert!(
+            weight >= 0.0,
+            "expecting cost weight to be non-negative... found {:.6}",
+            weight
+        );
+        self.entries.push(CostEntry {
+            name: name.to_string(),
+            weight,
+            calls: Cell::new(0),
+        });
+        self.entries.len() - 1
+    }
+
+    /// Records one call to the function registered at `index`
+    pub fn record(&self, index: usize) {
+        let calls = &self.entries[index].calls;
+        calls.set(calls.get() + 1);
+    }
+
+    /// Number of times the function called `name` has been evaluated
+    pub fn calls(&self, name: &str) -> usize {
+        self.entries
+            .iter()
+            .find(|e| e.name == name)
+            .map(|e| e.calls.get())
+            .unwrap_or_else(|| panic!("Unknown function '{}' in cost model", name))
+    }
+
+    /// The total cost of all the calls recorded so far
+    pub fn total(&self) -> f64 {
+        self.entries
+            .iter()
+            .map(|e| e.weight * e.calls.get() as f64)
+            .sum()
+    }
+
+    /// Sets all the counters back to zero
+    pub fn reset(&self) {
+        for e in self.entries.iter() {
+            e.calls.set(0);
+        }
+    }
+}
+
+/// The result of running an estimator several times
+#[derive(Debug, Clone, Copy)]
+pub struct EfficiencyReport {
+    /// Number of independent estimates
+    pub trials: usize,
+    /// Mean of the estimates
+    pub mean: f64,
+    /// Variance of the estimates
+    pub variance: f64,
+    /// Average cost of one estimate
+    pub cost: f64,
+}
+
+impl EfficiencyReport {
+    /// Variance times cost. Lower is better.
+    pub fn inefficiency(&self) -> f64 {
+        self.variance * self.cost
+    }
+
+    /// The inverse of variance times cost. Higher is better.
+    pub fn efficiency(&self) -> f64 {
+        1.0 / self.inefficiency()
+    }
+}
+
+/// Runs `estimator` once per trial, each time with its own stream of
+/// `seed` (see [`Rng::with_stream`]), and reports the variance of the
+/// estimates and the average cost recorded in `cost`.
+pub fn measure_efficiency<F>(
+    cost: &CostModel,
+    trials: usize,
+    seed: u64,
+    mut estimator: F,
+) -> EfficiencyReport
+where
+    F: FnMut(Rng) -> f64,
+{
+    assert!(
+        trials > 1,
+        "Need at least 2 trials to estimate the variance"
+    );
+    cost.reset();
+    let estimates: Vec<f64> = (0..trials)
+        .map(|i| estimator(Rng::with_stream(seed, i as u64)))
+        .collect();
+
+    let n = trials as f64;
+    let mean = estimates.iter().sum::<f64>() / n;
+    let variance = estimates.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    EfficiencyReport {
+        trials,
+        mean,
+        variance,
+        cost: cost.total() / n,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_calls() {
+        let mut cost = CostModel::new();
+        let cheap = cost.register("cheap");
+        let expensive = cost.register_weighted("expensive", 10.0);
+        for _ in 0..5 {
+            cost.record(cheap);
+        }
+        cost.record(expensive);
+
+        assert_eq!(cost.calls("cheap"), 5);
+        assert_eq!(cost.calls("expensive"), 1);
+        assert!((cost.total() - 15.0).abs() < 1e-12);
+
+        cost.reset();
+        assert_eq!(cost.total(), 0.0);
+    }
+}
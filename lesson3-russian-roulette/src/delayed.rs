@@ -0,0 +1,195 @@
+use rand::Rng;
+
+/// An integrand that is the product of several factors, each of them
+/// with a different evaluation cost (e.g., `a(x)*b(x)`, where `b(x)`
+/// is expensive).
+pub trait FactoredIntegrand {
+    /// The number of factors in the product
+    fn n_factors(&self) -> usize;
+
+    /// Evaluates the `i`-th factor
+    fn factor(&self, i: usize, x: f64) -> f64;
+
+    /// The cost of evaluating the `i`-th factor once
+    fn factor_cost(&self, i: usize) -> f64;
+
+    /// returns a sampled object, and a PDF
+    fn sample(&self, rng: &mut Rng) -> (f64, f64);
+}
+
+/// Integrates a [`FactoredIntegrand`] evaluating the cheap factors
+/// first, and playing Russian roulette before each of the more expensive
+/// ones.
+///
+/// The estimate built so far, `v`, survives the roulette with probability
+/// `min(1, |v|/t)`. The thresholds `t` (one per roulette) can be tuned
+/// with [`DelayedEvaluation::tune`] so that variance times cost is minimal.
+pub struct DelayedEvaluation<'a, I: FactoredIntegrand> {
+    integrand: &'a I,
+    /// The index of the factors, from the cheapest to the most expensive
+    order: Vec<usize>,
+    /// `thresholds[k]` is used before evaluating `order[k+1]`.
+    thresholds: Vec<f64>,
+}
+
+impl<'a, I: FactoredIntegrand> DelayedEvaluation<'a, I> {
+    /// Creates a new integrator that does not play roulette at all
+    /// (i.e., all thresholds are zero)
+    pub fn new(integrand: &'a I) -> Self {
+        let n = integrand.n_factors();
+        assert!(n > 0, "Expecting at least one factor");
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|a, b| {
+            integrand
+                .factor_cost(*a)
+                .partial_cmp(&integrand.factor_cost(*b))
+                .unwrap()
+        });
+        Self {
+            integrand,
+            order,
+            thresholds: vec![0.0; n - 1],
+        }
+    }
+
+    /// The index of the factors, in the order in which they are evaluated
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// The roulette thresholds. The `k`-th one is used before
+    /// evaluating the `k+1`-th factor in [`DelayedEvaluation::order`]
+    pub fn thresholds(&self) -> &[f64] {
+        &self.thresholds
+    }
+
+    pub fn set_thresholds(&mut self, thresholds: Vec<f64>) {
+        assert_eq!(
+            thresholds.len(),
+            self.thresholds.len(),
+            "Expecting one threshold per roulette"
+        );
+        self.thresholds = thresholds;
+    }
+
+    /// Probability of continuing the evaluation of a sample whose
+    /// partial estimate is `v`
+    fn survival(v: f64, threshold: f64) -> f64 {
+        if threshold <= 0.0 {
+            1.0
+        } else {
+            (v.abs() / threshold).min(1.0)
+        }
+    }
+
+    /// Evaluates all the factors of `n_pilot` samples and chooses the
+    /// thresholds that minimize the estimated variance times cost.
+    ///
+    /// The thresholds are optimized one at a time (i.e., coordinate descent),
+    /// picking from a geometric series of candidates.
+    pub fn tune(&mut self, n_pilot: usize, rng: &mut Rng) {
+        assert!(n_pilot > 1, "Need at least 2 pilot samples");
+        // one row per sample: [1/pdf, factor values (in order)]
+        let pilot: Vec<Vec<f64>> = (0..n_pilot)
+            .map(|_| {
+                let (x, pdf) = self.integrand.sample(rng);
+                let mut row = vec![1.0 / pdf];
+                for i in self.order.iter() {
+                    row.push(self.integrand.factor(*i, x));
+                }
+                row
+            })
+            .collect();
+
+        const ROUNDS: usize = 3;
+        const CANDIDATES: i32 = 40;
+        for _ in 0..ROUNDS {
+            for k in 0..self.thresholds.len() {
+                let max_v = pilot
+                    .iter()
+                    .map(|row| self.partial_estimate(row, k + 1).abs())
+                    .fold(0.0, f64::max);
+                let mut best = (self.inefficiency(&pilot), self.thresholds[k]);
+                for j in 0..=CANDIDATES {
+                    // j == CANDIDATES means no roulette
+                    self.thresholds[k] = if j == CANDIDATES {
+                        0.0
+                    } else {
+                        max_v * 2f64.powi(-j)
+                    };
+                    let ineff = self.inefficiency(&pilot);
+                    if ineff < best.0 {
+                        best = (ineff, self.thresholds[k]);
+                    }
+                }
+                self.thresholds[k] = best.1;
+            }
+        }
+    }
+
+    /// The partial estimate of a pilot sample right before
+    /// evaluating the `k`-th factor (in order)
+    fn partial_estimate(&self, row: &[f64], k: usize) -> f64 {
+        let mut v = row[0] * row[1];
+        for j in 1..k {
+            let p = Self::survival(v, self.thresholds[j - 1]);
+            if p == 0.0 {
+                return 0.0;
+            }
+            v *= row[j + 1] / p;
+        }
+        v
+    }
+
+    /// The expected variance times cost of one sample, calculated
+    /// analytically over the pilot samples
+    fn inefficiency(&self, pilot: &[Vec<f64>]) -> f64 {
+        let n = pilot.len() as f64;
+        let mut first = 0.0;
+        let mut second = 0.0;
+        let mut cost = 0.0;
+        for row in pilot {
+            first += row[1..].iter().product::<f64>() * row[0];
+
+            let mut v = row[0] * row[1];
+            // probability of reaching this stage
+            let mut reach = 1.0;
+            cost += self.integrand.factor_cost(self.order[0]);
+            for (k, i) in self.order.iter().enumerate().skip(1) {
+                let p = Self::survival(v, self.thresholds[k - 1]);
+                reach *= p;
+                if reach == 0.0 {
+                    break;
+                }
+                cost += reach * self.integrand.factor_cost(*i);
+                v *= row[k + 1] / p;
+            }
+            second += reach * v * v;
+        }
+        let mean = first / n;
+        let variance = second / n - mean * mean;
+        variance * cost / n
+    }
+
+    /// integrates
+    pub fn integrate(&self, n: usize, mut rng: Rng) -> f64 {
+        let mut res = 0.0;
+        for _ in 0..n {
+            let (x, pdf) = self.integrand.sample(&mut rng);
+            let mut v = self.integrand.factor(self.order[0], x) / pdf;
+            for (k, i) in self.order.iter().enumerate().skip(1) {
+                let p = Self::survival(v, self.thresholds[k - 1]);
+                if p < 1.0 {
+                    if rng.next_float() >= p {
+                        v = 0.0;
+                        break;
+                    }
+                    v /= p;
+                }
+                v *= self.integrand.factor(*i, x);
+            }
+            res += v;
+        }
+        res / n as f64
+    }
+}
@@ -1,32 +1,63 @@
-use std::{thread, time::Duration};
+pub mod cost;
+pub mod delayed;
 
+use crate::cost::CostModel;
+use crate::delayed::FactoredIntegrand;
 use lesson1_sampling::step_pdf::DiscretePdf;
 use rand::Rng;
 
+/// Index of `a(x)` in the cost model
+const A_COST: usize = 0;
+/// Index of `b(x)` in the cost model
+const B_COST: usize = 1;
+
+/// The cost of one evaluation of `b(x)`, relative to `a(x)`
+pub const B_WEIGHT: f64 = 10.0;
+
 pub struct MIS {
     a: DiscretePdf,
     b: DiscretePdf,
     sampling: DiscretePdf,
     roulette: bool,
+    cost: CostModel,
 }
 
 impl MIS {
+    /// Creates a new integrator of `a(x)*b(x)`, where `b(x)` is
+    /// [`B_WEIGHT`] times more expensive to evaluate than `a(x)`
+    pub fn new(a: DiscretePdf, b: DiscretePdf, sampling: DiscretePdf, roulette: bool) -> Self {
+        let mut cost = CostModel::new();
+        cost.register("a");
+        cost.register_weighted("b", B_WEIGHT);
+        Self {
+            a,
+            b,
+            sampling,
+            roulette,
+            cost,
+        }
+    }
+
+    /// The calls to `a(x)` and `b(x)` made so far
+    pub fn cost(&self) -> &CostModel {
+        &self.cost
+    }
+
     pub fn a(&self, x: f64) -> f64 {
+        self.cost.record(A_COST);
         self.a.pdf(x)
     }
     pub fn b(&self, x: f64) -> f64 {
-        thread::sleep(Duration::from_nanos(10));
+        self.cost.record(B_COST);
         self.b.pdf(x)
     }
 
     pub fn sample(&self, rng: &mut Rng) -> (f64, f64) {
-        let ret = self.sampling.sample(rng);
-        ret
+        self.sampling.sample(rng)
     }
 
     pub fn eval(&self, x: f64) -> f64 {
-        let ret = self.a(x) * self.b(x);
-        ret
+        self.a(x) * self.b(x)
     }
 
     /// integrates
@@ -59,66 +90,130 @@ impl MIS {
     }
 }
 
+impl FactoredIntegrand for MIS {
+    fn n_factors(&self) -> usize {
+        2
+    }
+
+    fn factor(&self, i: usize, x: f64) -> f64 {
+        match i {
+            0 => self.a(x),
+            1 => self.b(x),
+            _ => panic!("MIS has only two factors... found index {}", i),
+        }
+    }
+
+    fn factor_cost(&self, i: usize) -> f64 {
+        match i {
+            0 => 1.0,
+            1 => B_WEIGHT,
+            _ => panic!("MIS has only two factors... found index {}", i),
+        }
+    }
+
+    fn sample(&self, rng: &mut Rng) -> (f64, f64) {
+        MIS::sample(self, rng)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
-    use std::io::prelude::*;
-    use std::time::Instant;
+    use crate::cost::measure_efficiency;
+    use crate::delayed::DelayedEvaluation;
 
-    #[test]
-    fn integrate_russian_roulette() {
-        const EXPECTED: f64 = 0.8448;
-        fn error(v: f64) -> f64 {
-            (v - EXPECTED).abs() / EXPECTED
-        }
-        let fa = DiscretePdf::new(
+    const EXPECTED: f64 = 0.8448;
+
+    fn fa() -> DiscretePdf {
+        DiscretePdf::new(
             0.0,
             vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0],
             vec![0.5, 1.4, 3.2, 3.0, 0.5, 0.1, 0.1, 0.5, 0.1, 0.6],
-        );
-        let fb = DiscretePdf::new(
+        )
+    }
+
+    fn fb() -> DiscretePdf {
+        DiscretePdf::new(
             0.0,
             vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0],
             vec![2.0, 1.0, 0.8, 0.1, 0.02, 0.04, 0.1, 4.0, 0.0, 1.94],
-        );
+        )
+    }
+
+    #[test]
+    fn integrate_russian_roulette() {
+        fn error(v: f64) -> f64 {
+            (v - EXPECTED).abs() / EXPECTED
+        }
         let uniform = DiscretePdf::new(0.0, vec![1.0], vec![1.]);
-        let q0 = MIS {
-            a: fa.clone(),
-            b: fb.clone(),
-            sampling: uniform.clone(),
-            roulette: false,
-        };
-        let q2 = MIS {
-            a: fa.clone(),
-            b: fb.clone(),
-            sampling: uniform.clone(),
-            roulette: true,
-        };
-
-        let header = b"N,no-roulette,roulette\n";
-        let mut results_file = File::create("data/russian-roulette-results.csv").unwrap();
-        let mut time_file = File::create("data/russian-roulette-time.csv").unwrap();
-        results_file.write_all(header).unwrap();
-        time_file.write_all(header).unwrap();
-        for pow in 2..16 {
-            let n = (2 as usize).pow(pow) as usize;
+        let q0 = MIS::new(fa(), fb(), uniform.clone(), false);
+        let q2 = MIS::new(fa(), fb(), uniform.clone(), true);
 
-            let rng = Rng::new();
-            let start = Instant::now();
-            let found0 = q0.integrate(n, rng);
-            let time0 = start.elapsed().as_millis();
+        let mut last = (0.0, 0.0);
+        for pow in 2..16 {
+            let n = 2_usize.pow(pow);
 
-            let rng = Rng::new();
-            let start = Instant::now();
-            let found2 = q2.integrate(n, rng);
-            let time2 = start.elapsed().as_millis();
+            q0.cost().reset();
+            let found0 = q0.integrate(n, Rng::with_seed(2 * pow as u64));
+            let cost0 = q0.cost().total();
 
-            let ln = format!("{},{:.3},{:.3}\n", pow, error(found0), error(found2),);
-            results_file.write_all(ln.as_bytes()).unwrap();
+            q2.cost().reset();
+            let found2 = q2.integrate(n, Rng::with_seed(2 * pow as u64 + 1));
+            let cost2 = q2.cost().total();
 
-            let ln = format!("{},{:.3},{:.3}\n", pow, time0, time2);
-            time_file.write_all(ln.as_bytes()).unwrap();
+            // Roulette never evaluates more than the plain estimator
+            assert!(cost2 <= cost0);
+            last = (error(found0), error(found2));
         }
+        assert!(last.0 < 0.05 && last.1 < 0.05, "{:?}", last);
+    }
+
+    #[test]
+    fn roulette_efficiency() {
+        let uniform = DiscretePdf::new(0.0, vec![1.0], vec![1.]);
+        let q0 = MIS::new(fa(), fb(), uniform.clone(), false);
+        let q2 = MIS::new(fa(), fb(), uniform, true);
+
+        let n = 256;
+        let report0 = measure_efficiency(q0.cost(), 200, 0, |rng| q0.integrate(n, rng));
+        let report2 = measure_efficiency(q2.cost(), 200, 0, |rng| q2.integrate(n, rng));
+
+        // Both are unbiased
+        assert!((report0.mean - EXPECTED).abs() / EXPECTED < 0.05);
+        assert!((report2.mean - EXPECTED).abs() / EXPECTED < 0.05);
+
+        // Without roulette, a(x) and b(x) are evaluated once per sample
+        assert!((report0.cost - n as f64 * (1.0 + B_WEIGHT)).abs() < 1e-9);
+
+        // Roulette skips most evaluations of b(x). It is cheaper, but noisier...
+        assert!(report2.cost < report0.cost);
+        assert!(report2.variance > report0.variance);
+        // ... and, because q only looks at a(x), it kills many samples
+        // where b(x) is large. The extra variance outweighs the savings.
+        assert!(report2.efficiency() < report0.efficiency());
+    }
+
+    #[test]
+    fn delayed_evaluation() {
+        let uniform = DiscretePdf::new(0.0, vec![1.0], vec![1.]);
+        let q0 = MIS::new(fa(), fb(), uniform.clone(), false);
+        let q2 = MIS::new(fa(), fb(), uniform.clone(), true);
+        let mis = MIS::new(fa(), fb(), uniform, false);
+
+        let mut delayed = DelayedEvaluation::new(&mis);
+        // a(x) is cheaper, so it goes first
+        assert_eq!(delayed.order(), &[0, 1]);
+        delayed.tune(4096, &mut Rng::with_seed(1));
+        assert!(delayed.thresholds()[0] > 0.0);
+
+        let n = 256;
+        let report0 = measure_efficiency(q0.cost(), 200, 0, |rng| q0.integrate(n, rng));
+        let report2 = measure_efficiency(q2.cost(), 200, 0, |rng| q2.integrate(n, rng));
+        let report = measure_efficiency(mis.cost(), 200, 0, |rng| delayed.integrate(n, rng));
+
+        assert!((report.mean - EXPECTED).abs() / EXPECTED < 0.05);
+        assert!(report.cost < report0.cost);
+        assert!(report.efficiency() > report0.efficiency());
+        assert!(report.efficiency() > report2.efficiency());
     }
 }
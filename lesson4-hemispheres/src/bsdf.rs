@@ -0,0 +1,707 @@
+//! Reflection models (BSDFs) that can be evaluated and sampled.
+//!
+//! All directions are in the local shading frame, where the normal
+//! is +Z (see [`crate::OrthonormalBasis`]), and both `wo` and `wi`
+//! point away from the surface.
+
+use crate::{sample_cosine_weighted_horizontal_hemisphere, OrthonormalBasis, Vector3D};
+use std::f64::consts::PI;
+
+/// A direction sampled from a [`Bsdf`]
+#[derive(Debug, Clone, Copy)]
+pub struct BsdfSample {
+    /// The sampled incoming direction
+    pub wi: Vector3D,
+    /// The value of the BSDF for `wo` and `wi`
+    pub f: f64,
+    /// The PDF (per unit solid angle) of `wi`. For specular
+    /// samples, this is the discrete probability of choosing it.
+    pub pdf: f64,
+    /// Whether `wi` was sampled from a Dirac delta (e.g., a mirror)
+    pub specular: bool,
+}
+
+pub trait Bsdf {
+    /// Evaluates the BSDF for the outgoing direction `wo` and
+    /// the incoming direction `wi`
+    fn eval(&self, wo: Vector3D, wi: Vector3D) -> f64;
+
+    /// Samples an incoming direction for the outgoing direction `wo`.
+    /// Returns `None` if the sample carries no energy (e.g., it points
+    /// below the surface)
+    fn sample(&self, wo: Vector3D, u: (f64, f64)) -> Option<BsdfSample>;
+
+    /// The PDF (per unit solid angle) with which [`Bsdf::sample`]
+    /// produces `wi` when given `wo`
+    fn pdf(&self, wo: Vector3D, wi: Vector3D) -> f64;
+}
+
+/// Reflects `w` around `n`
+pub fn reflect(w: Vector3D, n: Vector3D) -> Vector3D {
+    -w + n * (2. * w.dot(n))
+}
+
+fn same_hemisphere(wo: Vector3D, wi: Vector3D) -> bool {
+    wo.z > 0. && wi.z > 0.
+}
+
+/// A perfectly diffuse surface
+pub struct Lambertian {
+    pub reflectance: f64,
+}
+
+impl Bsdf for Lambertian {
+    fn eval(&self, wo: Vector3D, wi: Vector3D) -> f64 {
+        if same_hemisphere(wo, wi) {
+            self.reflectance / PI
+        } else {
+            0.
+        }
+    }
+
+    fn sample(&self, wo: Vector3D, u: (f64, f64)) -> Option<BsdfSample> {
+        let wi = sample_cosine_weighted_horizontal_hemisphere(u);
+        if !same_hemisphere(wo, wi) {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, wi),
+            pdf: self.pdf(wo, wi),
+            specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: Vector3D, wi: Vector3D) -> f64 {
+        if same_hemisphere(wo, wi) {
+            wi.z / PI
+        } else {
+            0.
+        }
+    }
+}
+
+/// The energy conserving modified Phong model, from Lafortune and
+/// Willems (1994) "Using the modified Phong reflectance model for
+/// physically based rendering".
+///
+/// `diffuse + specular` must not exceed 1.
+pub struct Phong {
+    pub diffuse: f64,
+    pub specular: f64,
+    pub exponent: f64,
+}
+
+impl Phong {
+    pub fn new(diffuse: f64, specular: f64, exponent: f64) -> Self {
+        assert!(
+            diffuse >= 0. && specular >= 0. && diffuse + specular <= 1.,
+            "expecting diffuse + specular to be between 0 and 1... found {:.6}",
+            diffuse + specular
+        );
+        Self {
+            diffuse,
+            specular,
+            exponent,
+        }
+    }
+
+    /// The probability of sampling the diffuse lobe
+    fn diffuse_probability(&self) -> f64 {
+        self.diffuse / (self.diffuse + self.specular)
+    }
+
+    /// The cosine between `wi` and the mirror reflection of `wo`
+    fn cos_alpha(wo: Vector3D, wi: Vector3D) -> f64 {
+        let r = Vector3D::new(-wo.x, -wo.y, wo.z);
+        r.dot(wi).max(0.)
+    }
+}
+
+impl Bsdf for Phong {
+    fn eval(&self, wo: Vector3D, wi: Vector3D) -> f64 {
+        if !same_hemisphere(wo, wi) {
+            return 0.;
+        }
+        let n = self.exponent;
+        self.diffuse / PI + self.specular * (n + 2.) / (2. * PI) * Self::cos_alpha(wo, wi).powf(n)
+    }
+
+    fn sample(&self, wo: Vector3D, u: (f64, f64)) -> Option<BsdfSample> {
+        if wo.z <= 0. || self.diffuse + self.specular <= 0. {
+            return None;
+        }
+        let pd = self.diffuse_probability();
+        let wi = if u.0 < pd {
+            sample_cosine_weighted_horizontal_hemisphere((u.0 / pd, u.1))
+        } else {
+            let u0 = (u.0 - pd) / (1. - pd);
+            let cos_alpha = u0.powf(1. / (self.exponent + 1.));
+            let sin_alpha = (1. - cos_alpha * cos_alpha).max(0.).sqrt();
+            let (phi_sin, phi_cos) = (2. * PI * u.1).sin_cos();
+            let local = Vector3D::new(sin_alpha * phi_cos, sin_alpha * phi_sin, cos_alpha);
+            let r = Vector3D::new(-wo.x, -wo.y, wo.z);
+            OrthonormalBasis::new(r).to_world(local)
+        };
+        if !same_hemisphere(wo, wi) {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, wi),
+            pdf: self.pdf(wo, wi),
+            specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: Vector3D, wi: Vector3D) -> f64 {
+        if !same_hemisphere(wo, wi) || self.diffuse + self.specular <= 0. {
+            return 0.;
+        }
+        let pd = self.diffuse_probability();
+        let n = self.exponent;
+        pd * wi.z / PI + (1. - pd) * (n + 1.) / (2. * PI) * Self::cos_alpha(wo, wi).powf(n)
+    }
+}
+
+/// The distribution of the normals of the microfacets
+#[derive(Debug, Clone, Copy)]
+pub enum MicrofacetDistribution {
+    /// Trowbridge-Reitz, also known as GGX
+    Ggx,
+    Beckmann,
+}
+
+impl MicrofacetDistribution {
+    /// The density of microfacets whose normal is `m`, for
+    /// a roughness `alpha`
+    pub fn d(&self, alpha: f64, m: Vector3D) -> f64 {
+        if m.z <= 0. {
+            return 0.;
+        }
+        let a2 = alpha * alpha;
+        let cos2 = m.z * m.z;
+        let tan2 = (1. - cos2) / cos2;
+        match self {
+            Self::Ggx => a2 / (PI * cos2 * cos2 * (a2 + tan2).powi(2)),
+            Self::Beckmann => (-tan2 / a2).exp() / (PI * a2 * cos2 * cos2),
+        }
+    }
+
+    /// Smith's auxiliary function, such that the masking term is `1/(1+lambda)`
+    pub fn lambda(&self, alpha: f64, w: Vector3D) -> f64 {
+        let cos2 = w.z * w.z;
+        let tan2 = (1. - cos2) / cos2;
+        if tan2 == 0. {
+            return 0.;
+        }
+        match self {
+            Self::Ggx => ((1. + alpha * alpha * tan2).sqrt() - 1.) / 2.,
+            Self::Beckmann => {
+                let a = 1. / (alpha * tan2.sqrt());
+                (erf(a) - 1.) / 2. + (-a * a).exp() / (2. * a * PI.sqrt())
+            }
+        }
+    }
+
+    /// The fraction of microfacets with normals `m` that are
+    /// visible from `w` (i.e., Smith's masking term)
+    pub fn g1(&self, alpha: f64, w: Vector3D) -> f64 {
+        1. / (1. + self.lambda(alpha, w))
+    }
+
+    /// Samples a microfacet normal, proportionally to how much of it is visible
+    /// from `wo`, which must be above the surface.
+    pub fn sample_visible(&self, alpha: f64, wo: Vector3D, u: (f64, f64)) -> Vector3D {
+        match self {
+            Self::Ggx => sample_ggx_visible(alpha, wo, u),
+            Self::Beckmann => sample_beckmann_visible(alpha, wo, u),
+        }
+    }
+}
+
+/// Heitz (2018) "Sampling the GGX Distribution of Visible Normals"
+fn sample_ggx_visible(alpha: f64, wo: Vector3D, u: (f64, f64)) -> Vector3D {
+    // Go to the hemisphere configuration
+    let vh = Vector3D::new(alpha * wo.x, alpha * wo.y, wo.z).normalize();
+    let lensq = vh.x * vh.x + vh.y * vh.y;
+    let t1 = if lensq > 0. {
+        Vector3D::new(-vh.y, vh.x, 0.) / lensq.sqrt()
+    } else {
+        Vector3D::new(1., 0., 0.)
+    };
+    let t2 = vh.cross(t1);
+
+    // Sample the projected area
+    let r = u.0.sqrt();
+    let (phi_sin, phi_cos) = (2. * PI * u.1).sin_cos();
+    let p1 = r * phi_cos;
+    let p2 = r * phi_sin;
+    let s = 0.5 * (1. + vh.z);
+    let p2 = (1. - s) * (1. - p1 * p1).sqrt() + s * p2;
+
+    // Reproject onto the hemisphere, and back to the ellipsoid
+    let nh = t1 * p1 + t2 * p2 + vh * (1. - p1 * p1 - p2 * p2).max(0.).sqrt();
+    Vector3D::new(alpha * nh.x, alpha * nh.y, nh.z.max(0.)).normalize()
+}
+
+/// Heitz and d'Eon (2014) "Importance Sampling Microfacet-Based BSDFs
+/// using the Distribution of Visible Normals", with the numerical
+/// inversion used by PBRT.
+fn sample_beckmann_visible(alpha: f64, wo: Vector3D, u: (f64, f64)) -> Vector3D {
+    let ws = Vector3D::new(alpha * wo.x, alpha * wo.y, wo.z).normalize();
+    let (mut slope_x, mut slope_y) = sample_beckmann_slopes(ws.z, u);
+
+    // Rotate
+    let sin_theta = (1. - ws.z * ws.z).max(0.).sqrt();
+    let (cos_phi, sin_phi) = if sin_theta > 0. {
+        (ws.x / sin_theta, ws.y / sin_theta)
+    } else {
+        (1., 0.)
+    };
+    let tmp = cos_phi * slope_x - sin_phi * slope_y;
+    slope_y = sin_phi * slope_x + cos_phi * slope_y;
+    slope_x = tmp;
+
+    // Unstretch
+    Vector3D::new(-alpha * slope_x, -alpha * slope_y, 1.).normalize()
+}
+
+/// Samples the slopes of the visible normals of a Beckmann
+/// distribution with unit roughness
+fn sample_beckmann_slopes(cos_theta: f64, u: (f64, f64)) -> (f64, f64) {
+    if cos_theta > 0.9999 {
+        let r = (-(1. - u.0).ln()).sqrt();
+        let (phi_sin, phi_cos) = (2. * PI * u.1).sin_cos();
+        return (r * phi_cos, r * phi_sin);
+    }
+    let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+    let tan_theta = sin_theta / cos_theta;
+    let cot_theta = 1. / tan_theta;
+
+    // Search interval, in the erf() domain
+    let mut a = -1.;
+    let mut c = erf(cot_theta);
+    let sample_x = u.0.max(1e-6);
+
+    let theta = cos_theta.acos();
+    let fit = 1. + theta * (-0.876 + theta * (0.4265 - 0.0594 * theta));
+    let mut b = c - (1. + c) * (1. - sample_x).powf(fit);
+
+    let sqrt_pi_inv = 1. / PI.sqrt();
+    let normalization = 1. / (1. + c + sqrt_pi_inv * tan_theta * (-cot_theta * cot_theta).exp());
+
+    for _ in 0..20 {
+        if !(b >= a && b <= c) {
+            b = 0.5 * (a + c);
+        }
+        let inv_erf = erf_inv(b);
+        let value = normalization * (1. + b + sqrt_pi_inv * tan_theta * (-inv_erf * inv_erf).exp())
+            - sample_x;
+        let derivative = normalization * (1. - inv_erf * tan_theta);
+        if value.abs() < 1e-9 {
+            break;
+        }
+        if value > 0. {
+            c = b;
+        } else {
+            a = b;
+        }
+        b -= value / derivative;
+    }
+    (erf_inv(b), erf_inv(2. * u.1.max(1e-6) - 1.))
+}
+
+/// The error function, from its Taylor series for small arguments
+/// and from the continued fraction of `erfc` for large ones
+fn erf(x: f64) -> f64 {
+    let sign = x.signum();
+    let x = x.abs();
+    let y = if x < 2. {
+        // Taylor series
+        let mut sum = x;
+        let mut term = x;
+        let x2 = x * x;
+        let mut n = 0.;
+        while term.abs() > 1e-17 * sum.abs() {
+            n += 1.;
+            term *= -x2 / n;
+            sum += term / (2. * n + 1.);
+        }
+        2. / PI.sqrt() * sum
+    } else {
+        // Continued fraction for erfc (Lentz's method)
+        let x2 = x * x;
+        let mut f = x;
+        let mut c = x;
+        let mut d = 0.;
+        for n in 1..200 {
+            let an = n as f64 / 2.;
+            d = 1. / (x + an * d);
+            c = x + an / c;
+            let delta = c * d;
+            f *= delta;
+            if (delta - 1.).abs() < 1e-16 {
+                break;
+            }
+        }
+        1. - (-x2).exp() / (f * PI.sqrt())
+    };
+    sign * y
+}
+
+/// The inverse of [`erf`], polished with Newton's method
+fn erf_inv(y: f64) -> f64 {
+    if y <= -1. {
+        return f64::NEG_INFINITY;
+    }
+    if y >= 1. {
+        return f64::INFINITY;
+    }
+    // Initial guess from Giles (2010)
+    let w = -((1. - y) * (1. + y)).ln();
+    let mut x = if w < 5. {
+        let w = w - 2.5;
+        let mut p = 2.810_226_36e-08;
+        p = 3.432_739_39e-07 + p * w;
+        p = -3.523_387_7e-06 + p * w;
+        p = -4.391_506_54e-06 + p * w;
+        p = 0.000_218_580_87 + p * w;
+        p = -0.001_253_725_03 + p * w;
+        p = -0.004_177_681_64 + p * w;
+        p = 0.246_640_727 + p * w;
+        p = 1.501_409_41 + p * w;
+        p * y
+    } else {
+        let w = w.sqrt() - 3.;
+        let mut p = -0.000_200_214_257;
+        p = 0.000_100_950_558 + p * w;
+        p = 0.001_349_343_22 + p * w;
+        p = -0.003_673_428_44 + p * w;
+        p = 0.005_739_507_73 + p * w;
+        p = -0.007_622_461_3 + p * w;
+        p = 0.009_438_870_47 + p * w;
+        p = 1.001_674_06 + p * w;
+        p = 2.832_976_82 + p * w;
+        p * y
+    };
+    for _ in 0..2 {
+        let derivative = 2. / PI.sqrt() * (-x * x).exp();
+        if derivative == 0. {
+            break;
+        }
+        x -= (erf(x) - y) / derivative;
+    }
+    x
+}
+
+/// Schlick's approximation to the Fresnel reflectance
+fn schlick(f0: f64, cos_theta: f64) -> f64 {
+    f0 + (1. - f0) * (1. - cos_theta).clamp(0., 1.).powi(5)
+}
+
+/// A rough reflector made of perfectly specular microfacets, with
+/// Smith's height-correlated masking-shadowing and Schlick's Fresnel.
+///
+/// Directions are sampled from the distribution of visible normals.
+pub struct Microfacet {
+    pub distribution: MicrofacetDistribution,
+    /// The roughness
+    pub alpha: f64,
+    /// The reflectance at normal incidence
+    pub f0: f64,
+}
+
+impl Bsdf for Microfacet {
+    fn eval(&self, wo: Vector3D, wi: Vector3D) -> f64 {
+        if !same_hemisphere(wo, wi) {
+            return 0.;
+        }
+        let m = (wo + wi).normalize();
+        let d = self.distribution.d(self.alpha, m);
+        let g = 1.
+            / (1.
+                + self.distribution.lambda(self.alpha, wo)
+                + self.distribution.lambda(self.alpha, wi));
+        schlick(self.f0, wo.dot(m)) * d * g / (4. * wo.z * wi.z)
+    }
+
+    fn sample(&self, wo: Vector3D, u: (f64, f64)) -> Option<BsdfSample> {
+        if wo.z <= 0. {
+            return None;
+        }
+        let m = self.distribution.sample_visible(self.alpha, wo, u);
+        let wi = reflect(wo, m);
+        if !same_hemisphere(wo, wi) {
+            return None;
+        }
+        let pdf = self.pdf(wo, wi);
+        if pdf <= 0. {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, wi),
+            pdf,
+            specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: Vector3D, wi: Vector3D) -> f64 {
+        if !same_hemisphere(wo, wi) {
+            return 0.;
+        }
+        let m = (wo + wi).normalize();
+        // The density of visible normals, divided by the
+        // jacobian of the reflection (i.e., 4 wo.m)
+        self.distribution.d(self.alpha, m) * self.distribution.g1(self.alpha, wo) / (4. * wo.z)
+    }
+}
+
+/// A perfect mirror
+pub struct Mirror {
+    pub reflectance: f64,
+}
+
+impl Bsdf for Mirror {
+    fn eval(&self, _wo: Vector3D, _wi: Vector3D) -> f64 {
+        0.
+    }
+
+    fn sample(&self, wo: Vector3D, _u: (f64, f64)) -> Option<BsdfSample> {
+        if wo.z <= 0. {
+            return None;
+        }
+        let wi = Vector3D::new(-wo.x, -wo.y, wo.z);
+        Some(BsdfSample {
+            wi,
+            f: self.reflectance / wi.z,
+            pdf: 1.,
+            specular: true,
+        })
+    }
+
+    fn pdf(&self, _wo: Vector3D, _wi: Vector3D) -> f64 {
+        0.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn direction(theta_degrees: f64) -> Vector3D {
+        let (sin, cos) = theta_degrees.to_radians().sin_cos();
+        Vector3D::new(sin, 0., cos)
+    }
+
+    /// Estimates the albedo by sampling the BSDF
+    fn albedo(bsdf: &dyn Bsdf, wo: Vector3D) -> f64 {
+        let mut rng = Rng::with_seed(0);
+        let n = 100_000;
+        let mut s = 0.;
+        for _ in 0..n {
+            let u = (rng.next_float(), rng.next_float());
+            if let Some(sample) = bsdf.sample(wo, u) {
+                s += sample.f * sample.wi.z / sample.pdf;
+            }
+        }
+        s / n as f64
+    }
+
+    /// Integrates a function over the whole sphere using the midpoint rule
+    fn integrate_sphere<F: Fn(Vector3D) -> f64>(f: F) -> f64 {
+        let n_theta = 1000;
+        let n_phi = 400;
+        let d_theta = PI / n_theta as f64;
+        let d_phi = 2. * PI / n_phi as f64;
+        let mut s = 0.0;
+        for i in 0..n_theta {
+            let theta = (i as f64 + 0.5) * d_theta;
+            let (sin_theta, cos_theta) = theta.sin_cos();
+            for j in 0..n_phi {
+                let (sin_phi, cos_phi) = ((j as f64 + 0.5) * d_phi).sin_cos();
+                let v = Vector3D::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta);
+                s += f(v) * sin_theta * d_theta * d_phi;
+            }
+        }
+        s
+    }
+
+    /// Compares the histogram of the sampled directions with the
+    /// expected one, obtained by integrating the PDF, using a
+    /// chi-square test
+    fn chi_square(bsdf: &dyn Bsdf, wo: Vector3D) {
+        const THETA_RES: usize = 10;
+        const PHI_RES: usize = 20;
+        const SUBDIVISIONS: usize = 16;
+        const N: usize = 100_000;
+
+        let bin = |v: Vector3D| {
+            let theta = v.z.clamp(-1., 1.).acos();
+            let mut phi = v.y.atan2(v.x);
+            if phi < 0. {
+                phi += 2. * PI;
+            }
+            let i = ((theta / PI * THETA_RES as f64) as usize).min(THETA_RES - 1);
+            let j = ((phi / (2. * PI) * PHI_RES as f64) as usize).min(PHI_RES - 1);
+            i * PHI_RES + j
+        };
+
+        let mut rng = Rng::with_seed(1);
+        let mut observed = vec![0.; THETA_RES * PHI_RES];
+        for _ in 0..N {
+            let u = (rng.next_float(), rng.next_float());
+            if let Some(sample) = bsdf.sample(wo, u) {
+                let pdf = bsdf.pdf(wo, sample.wi);
+                assert!(
+                    (pdf - sample.pdf).abs() <= 1e-6 * pdf,
+                    "{} vs {}",
+                    pdf,
+                    sample.pdf
+                );
+                observed[bin(sample.wi)] += 1.;
+            }
+        }
+
+        let d_theta = PI / (THETA_RES * SUBDIVISIONS) as f64;
+        let d_phi = 2. * PI / (PHI_RES * SUBDIVISIONS) as f64;
+        let mut expected = vec![0.; THETA_RES * PHI_RES];
+        for i in 0..THETA_RES * SUBDIVISIONS {
+            let theta = (i as f64 + 0.5) * d_theta;
+            let (sin_theta, cos_theta) = theta.sin_cos();
+            for j in 0..PHI_RES * SUBDIVISIONS {
+                let (sin_phi, cos_phi) = ((j as f64 + 0.5) * d_phi).sin_cos();
+                let v = Vector3D::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta);
+                expected[bin(v)] += bsdf.pdf(wo, v) * sin_theta * d_theta * d_phi * N as f64;
+            }
+        }
+
+        // Pool the bins with few expected samples
+        let mut chi2 = 0.;
+        let mut dof = 0;
+        let mut pooled_observed = 0.;
+        let mut pooled_expected = 0.;
+        for (o, e) in observed.iter().zip(expected.iter()) {
+            if *e < 5. {
+                pooled_observed += o;
+                pooled_expected += e;
+            } else {
+                chi2 += (o - e).powi(2) / e;
+                dof += 1;
+            }
+        }
+        if pooled_expected >= 5. {
+            chi2 += (pooled_observed - pooled_expected).powi(2) / pooled_expected;
+            dof += 1;
+        } else {
+            assert!(pooled_observed < 5. + 5. * 5f64.sqrt());
+        }
+        let dof = (dof - 1) as f64;
+        assert!(
+            chi2 < dof + 5. * (2. * dof).sqrt(),
+            "chi2 = {} with {} degrees of freedom",
+            chi2,
+            dof
+        );
+    }
+
+    fn glossy() -> Vec<Box<dyn Bsdf>> {
+        vec![
+            Box::new(Lambertian { reflectance: 0.7 }),
+            Box::new(Phong::new(0.2, 0.7, 20.)),
+            Box::new(Phong::new(0., 1., 3.)),
+            Box::new(Microfacet {
+                distribution: MicrofacetDistribution::Ggx,
+                alpha: 0.3,
+                f0: 0.9,
+            }),
+            Box::new(Microfacet {
+                distribution: MicrofacetDistribution::Ggx,
+                alpha: 0.8,
+                f0: 1.,
+            }),
+            Box::new(Microfacet {
+                distribution: MicrofacetDistribution::Beckmann,
+                alpha: 0.3,
+                f0: 0.9,
+            }),
+            Box::new(Microfacet {
+                distribution: MicrofacetDistribution::Beckmann,
+                alpha: 0.8,
+                f0: 1.,
+            }),
+        ]
+    }
+
+    #[test]
+    fn special_functions() {
+        assert!(erf(0.).abs() < 1e-15);
+        assert!((erf(0.5) - 0.520_499_877_813_046_5).abs() < 1e-14);
+        assert!((erf(1.) - 0.842_700_792_949_714_9).abs() < 1e-14);
+        assert!((erf(2.5) - 0.999_593_047_982_555).abs() < 1e-14);
+        assert!((erf(-1.) + 0.842_700_792_949_714_9).abs() < 1e-14);
+        for y in [-0.999, -0.5, 0., 0.3, 0.9, 0.99999] {
+            assert!((erf(erf_inv(y)) - y).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn white_furnace() {
+        // Energy is conserved exactly
+        let lambertian = Lambertian { reflectance: 1. };
+        assert!((albedo(&lambertian, direction(30.)) - 1.).abs() < 1e-9);
+        let mirror = Mirror { reflectance: 1. };
+        assert!((albedo(&mirror, direction(60.)) - 1.).abs() < 1e-9);
+
+        // Some energy is lost under the horizon, or by masking, but never created.
+        for bsdf in glossy() {
+            for theta in [0., 30., 60., 85.] {
+                let a = albedo(bsdf.as_ref(), direction(theta));
+                assert!(a <= 1.0 + 1e-2, "albedo = {}", a);
+            }
+        }
+        let smooth = Microfacet {
+            distribution: MicrofacetDistribution::Ggx,
+            alpha: 0.05,
+            f0: 1.,
+        };
+        assert!(albedo(&smooth, direction(10.)) > 0.99);
+        let phong = Phong::new(0., 1., 200.);
+        assert!(albedo(&phong, direction(0.)) > 0.99);
+    }
+
+    #[test]
+    fn microfacet_normalization() {
+        for distribution in [
+            MicrofacetDistribution::Ggx,
+            MicrofacetDistribution::Beckmann,
+        ] {
+            for alpha in [0.2, 0.5, 1.0] {
+                // The projected area of the microfacets is that of the surface
+                let projected = integrate_sphere(|m| distribution.d(alpha, m) * m.z);
+                assert!((projected - 1.).abs() < 1e-3, "{}", projected);
+
+                // The weak white furnace test
+                for theta in [0., 45., 80.] {
+                    let wo = direction(theta);
+                    let visible = integrate_sphere(|m| {
+                        distribution.d(alpha, m) * distribution.g1(alpha, wo) * wo.dot(m).max(0.)
+                            / wo.z
+                    });
+                    assert!((visible - 1.).abs() < 1e-3, "{}", visible);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sampling_matches_pdf() {
+        for bsdf in glossy() {
+            for theta in [10., 50., 80.] {
+                chi_square(bsdf.as_ref(), direction(theta));
+            }
+        }
+    }
+}
@@ -1,25 +1,36 @@
+pub mod bsdf;
+pub mod projection;
+pub mod sh;
+pub mod shapes;
+pub mod sky;
+pub mod sky_patches;
+pub mod solar;
+pub mod vector;
+
+pub use crate::vector::{OrthonormalBasis, Vector3D};
 use std::f64::consts::PI;
 
-pub struct Vector3D {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
-}
-
+/// Samples a point uniformly over a disc, using the polar mapping.
+///
+/// This mapping distorts stratified samples; see
+/// [`shapes::concentric_sample_disc`] for one that does not.
 pub fn uniform_sample_horizontal_disc(u: (f64, f64), radius: f64) -> (f64, f64) {
     let (r, theta) = u;
 
     let r = radius * r.sqrt();
-    let theta = 2. * crate::PI * theta;
+    let theta = 2. * PI * theta;
     let (theta_sin, theta_cos) = theta.sin_cos();
 
-    let local_x = r * theta_sin;
-    let local_y = r * theta_cos;
+    let local_x = r * theta_cos;
+    let local_y = r * theta_sin;
     (local_x, local_y)
 }
 
+/// Samples a direction around +Z with a density proportional to
+/// its `z` component, by projecting a concentric disc sample up
+/// onto the hemisphere (i.e., Malley's method)
 pub fn sample_cosine_weighted_horizontal_hemisphere(u: (f64, f64)) -> Vector3D {
-    let (local_x, local_y) = uniform_sample_horizontal_disc(u, 1.);
+    let (local_x, local_y) = shapes::concentric_sample_disc(u);
     let aux = (local_x * local_x + local_y * local_y).clamp(0., 1.);
     let local_z = (1. - aux).sqrt();
     Vector3D {
@@ -42,11 +53,107 @@ pub fn sample_uniform_hemisphere(u: (f64, f64)) -> Vector3D {
     Vector3D { x, y, z }
 }
 
+/// Samples a direction uniformly over the hemisphere around `normal`,
+/// which must be normalized
+pub fn sample_uniform_hemisphere_around(u: (f64, f64), normal: Vector3D) -> Vector3D {
+    let local = sample_uniform_hemisphere(u);
+    OrthonormalBasis::new(normal).to_world(local)
+}
+
+/// The PDF (per unit solid angle) of the directions produced by
+/// [`sample_uniform_hemisphere_around`]
+pub fn uniform_hemisphere_pdf(direction: Vector3D, normal: Vector3D) -> f64 {
+    if direction.dot(normal) < 0.0 {
+        0.0
+    } else {
+        0.5 / PI
+    }
+}
+
+/// Samples a direction over the hemisphere around `normal`
+/// (which must be normalized) with a density proportional to
+/// the cosine of the angle between them
+pub fn sample_cosine_weighted_hemisphere_around(u: (f64, f64), normal: Vector3D) -> Vector3D {
+    let local = sample_cosine_weighted_horizontal_hemisphere(u);
+    OrthonormalBasis::new(normal).to_world(local)
+}
+
+/// The PDF (per unit solid angle) of the directions produced by
+/// [`sample_cosine_weighted_hemisphere_around`]
+pub fn cosine_weighted_hemisphere_pdf(direction: Vector3D, normal: Vector3D) -> f64 {
+    direction.dot(normal).max(0.0) / PI
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use rand::Rng;
 
+    /// Integrates a function over the whole sphere using the midpoint rule
+    fn integrate_sphere<F: Fn(Vector3D) -> f64>(f: F) -> f64 {
+        let n_theta = 400;
+        let n_phi = 800;
+        let d_theta = PI / n_theta as f64;
+        let d_phi = 2. * PI / n_phi as f64;
+        let mut s = 0.0;
+        for i in 0..n_theta {
+            let theta = (i as f64 + 0.5) * d_theta;
+            let (sin_theta, cos_theta) = theta.sin_cos();
+            for j in 0..n_phi {
+                let phi = (j as f64 + 0.5) * d_phi;
+                let (sin_phi, cos_phi) = phi.sin_cos();
+                let v = Vector3D::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta);
+                s += f(v) * sin_theta * d_theta * d_phi;
+            }
+        }
+        s
+    }
+
+    fn normals() -> Vec<Vector3D> {
+        vec![
+            Vector3D::new(0., 0., 1.),
+            Vector3D::new(0., 0., -1.),
+            Vector3D::new(1., 0., 0.),
+            Vector3D::new(1., -2., 0.5).normalize(),
+        ]
+    }
+
+    #[test]
+    fn hemisphere_pdfs_integrate_to_one() {
+        for n in normals() {
+            let uniform = integrate_sphere(|v| uniform_hemisphere_pdf(v, n));
+            let cosine = integrate_sphere(|v| cosine_weighted_hemisphere_pdf(v, n));
+            assert!((uniform - 1.0).abs() < 1e-2, "uniform = {}", uniform);
+            assert!((cosine - 1.0).abs() < 1e-2, "cosine = {}", cosine);
+        }
+    }
+
+    #[test]
+    fn samples_around_normal() {
+        let mut rng = Rng::with_seed(0);
+        for n in normals() {
+            let mut uniform_cos = 0.0;
+            let mut cosine_cos = 0.0;
+            let samples = 20000;
+            for _ in 0..samples {
+                let u = (rng.next_float(), rng.next_float());
+                let v = sample_uniform_hemisphere_around(u, n);
+                assert!(v.is_normalized());
+                assert!(v.dot(n) >= -1e-9);
+                assert!(uniform_hemisphere_pdf(v, n) > 0.);
+                uniform_cos += v.dot(n);
+
+                let v = sample_cosine_weighted_hemisphere_around(u, n);
+                assert!(v.is_normalized());
+                assert!(v.dot(n) >= -1e-9);
+                cosine_cos += v.dot(n);
+            }
+            // E[cos] is 1/2 for uniform and 2/3 for cosine-weighted
+            assert!((uniform_cos / samples as f64 - 0.5).abs() < 1e-2);
+            assert!((cosine_cos / samples as f64 - 2. / 3.).abs() < 1e-2);
+        }
+    }
+
     const EXPECTED_OVERCAST: f64 = PI * 7. / 9.;
     fn overcast_sky(v: Vector3D) -> f64 {
         (1. + 2. * v.z.abs()) / 3.
@@ -81,25 +188,61 @@ mod tests {
         println!("{:.7}", err * 100.);
     }
 
-    #[test]
-    fn cosine() {
-        let mut rng = Rng::new();
-
+    /// Estimates the irradiance on a horizontal surface from `sky`,
+    /// sampling directions with a cosine-weighted distribution
+    fn cosine_irradiance<F: Fn(Vector3D) -> f64>(sky: F, n: usize, rng: &mut Rng) -> f64 {
         let mut s = 0.0;
-        let n = 1000;
         for _ in 0..n {
             let u = (rng.next_float(), rng.next_float());
             let v = sample_cosine_weighted_horizontal_hemisphere(u); // create a new upward-looking direction
             let cos_theta = v.z; // cosine of the angle between UP and new_dir
             let pdf = cos_theta / PI; // uniformly distributed, so 1/2*PI
                                       // let spectrum = uniform_sky(v);
-            let spectrum = overcast_sky(v);
+            let spectrum = sky(v);
 
             s += spectrum * cos_theta / pdf;
         }
-        s /= n as f64;
+        s / n as f64
+    }
+
+    #[test]
+    fn cosine() {
+        let mut rng = Rng::new();
+
+        let s = cosine_irradiance(overcast_sky, 1000, &mut rng);
         let exp = EXPECTED_OVERCAST;
         let err = (s - exp).abs() / exp;
         println!("{:.7}", err * 100.)
     }
+
+    #[test]
+    fn spherical_harmonics_irradiance() {
+        let mut rng = Rng::with_seed(0);
+        let up = Vector3D::new(0., 0., 1.);
+        let expected = cosine_irradiance(overcast_sky, 100_000, &mut rng);
+        assert!((expected - EXPECTED_OVERCAST).abs() / EXPECTED_OVERCAST < 1e-2);
+
+        // Three bands are enough to get within about 1% of the irradiance
+        // (Ramamoorthi and Hanrahan, 2001)
+        for max_band in [2, 4] {
+            let sky = sh::SphericalHarmonics::project(max_band, overcast_sky, 100_000, &mut rng);
+            let found = sky.irradiance(up);
+            let err = (found - expected).abs() / expected;
+            assert!(
+                err < 2e-2,
+                "expecting {:.6}... found {:.6} with {} bands",
+                expected,
+                found,
+                max_band + 1
+            );
+        }
+
+        // The sky only lights the upper hemisphere
+        let sky = |v: Vector3D| if v.z > 0. { overcast_sky(v) } else { 0. };
+        let projection = sh::SphericalHarmonics::project_hemisphere(4, up, sky, 100_000, &mut rng);
+        let found = projection.irradiance(up);
+        assert!((found - expected).abs() / expected < 2e-2, "{:.6}", found);
+        let found = projection.irradiance(-up);
+        assert!(found.abs() < 5e-2, "{:.6}", found);
+    }
 }
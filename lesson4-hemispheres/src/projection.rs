@@ -0,0 +1,115 @@
+//! Images of distributions of directions over the upper (+Z) hemisphere.
+//!
+//! Directions are flattened onto a disc inscribed in the unit square
+//! with Lambert's equal-area projection, so a uniform distribution over
+//! the hemisphere looks uniform in the image too.
+
+use crate::Vector3D;
+use image::Image;
+
+/// Solid angle covered by a unit of area in the projection
+/// (i.e., the hemisphere, `2π`, over the disc, `π/4`)
+const SOLID_ANGLE_PER_AREA: f64 = 8.;
+
+/// Projects a direction with `z >= 0` into `[0, 1]^2`. The zenith
+/// lands on the centre, and the horizon on the inscribed circle.
+pub fn equal_area_projection(direction: Vector3D) -> (f64, f64) {
+    debug_assert!(
+        direction.z >= 0.,
+        "expecting a direction in the upper hemisphere... found z = {:.6}",
+        direction.z
+    );
+    // The radius is sqrt(1 - z), and |(x, y)| = sqrt((1 - z) (1 + z))
+    let s = 1. / (1. + direction.z).sqrt();
+    ((direction.x * s + 1.) / 2., (direction.y * s + 1.) / 2.)
+}
+
+/// The inverse of [`equal_area_projection`], which is `None`
+/// outside of the disc
+pub fn equal_area_projection_inverse(p: (f64, f64)) -> Option<Vector3D> {
+    let (x, y) = (2. * p.0 - 1., 2. * p.1 - 1.);
+    let r2 = x * x + y * y;
+    if r2 > 1. {
+        return None;
+    }
+    let z = 1. - r2;
+    let s = (1. + z).sqrt();
+    Some(Vector3D::new(x * s, y * s, z))
+}
+
+/// The density (per steradian) of `directions`, as an image with
+/// `resolution` pixels per side
+pub fn direction_histogram<I: IntoIterator<Item = Vector3D>>(
+    resolution: usize,
+    directions: I,
+) -> Image {
+    let points = directions.into_iter().map(equal_area_projection);
+    Image::histogram(resolution, resolution, points).map(|d| d / SOLID_ANGLE_PER_AREA)
+}
+
+/// An image of `pdf` (or any other function of the direction), to
+/// compare with [`direction_histogram`]. It is 0 outside of the disc.
+pub fn direction_image<F: Fn(Vector3D) -> f64>(resolution: usize, pdf: F) -> Image {
+    Image::from_fn(resolution, resolution, |u, v| {
+        equal_area_projection_inverse((u, v)).map_or(0., &pdf)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cosine_weighted_hemisphere_pdf, sample_cosine_weighted_hemisphere_around};
+    use rand::Rng;
+
+    #[test]
+    fn round_trip() {
+        let mut rng = Rng::with_seed(0);
+        for _ in 0..1000 {
+            let d = crate::sample_uniform_hemisphere((rng.next_float(), rng.next_float()));
+            let p = equal_area_projection(d);
+            assert!(equal_area_projection_inverse(p).unwrap().approx_eq(d, 1e-9));
+        }
+        assert_eq!(equal_area_projection(Vector3D::new(0., 0., 1.)), (0.5, 0.5));
+        assert_eq!(equal_area_projection(Vector3D::new(1., 0., 0.)), (1., 0.5));
+        assert!(equal_area_projection_inverse((0., 0.)).is_none());
+    }
+
+    #[test]
+    fn cosine_weighted_histogram() {
+        let up = Vector3D::new(0., 0., 1.);
+        let mut rng = Rng::with_seed(1);
+        let n = 500_000;
+        let resolution = 16;
+        let samples = (0..n).map(|_| {
+            sample_cosine_weighted_hemisphere_around((rng.next_float(), rng.next_float()), up)
+        });
+        let histogram = direction_histogram(resolution, samples);
+        let exact = direction_image(resolution, |d| cosine_weighted_hemisphere_pdf(d, up));
+
+        // Compare the pixels that are fully inside the disc
+        let inside = |i: usize| {
+            let (x, y) = (i % resolution, i / resolution);
+            [(0, 0), (1, 0), (0, 1), (1, 1)].iter().all(|(dx, dy)| {
+                let u = (x + dx) as f64 / resolution as f64;
+                let v = (y + dy) as f64 / resolution as f64;
+                (2. * u - 1.).powi(2) + (2. * v - 1.).powi(2) <= 1.
+            })
+        };
+        let mut compared = 0;
+        for (i, (h, e)) in histogram.pixels().iter().zip(exact.pixels()).enumerate() {
+            if inside(i) {
+                compared += 1;
+                assert!(
+                    (h - e).abs() < 0.02 + 0.05 * e,
+                    "pixel {}: {} vs {}",
+                    i,
+                    h,
+                    e
+                );
+            }
+        }
+        assert!(compared > 100);
+        // Every sample is in the disc, and the density integrates to 1
+        assert!((histogram.mean() * SOLID_ANGLE_PER_AREA - 1.).abs() < 1e-9);
+    }
+}
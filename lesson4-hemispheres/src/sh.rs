@@ -0,0 +1,302 @@
+//! Real spherical harmonics, for projecting functions on the sphere
+//! (e.g., the luminance of a sky) and convolving them with the clamped
+//! cosine to get irradiance.
+//!
+//! The basis follows Ramamoorthi and Hanrahan (2001) "An Efficient
+//! Representation for Irradiance Environment Maps" (i.e., without the
+//! Condon-Shortley phase), so `Y(1, 1)` is proportional to `x`. Coefficients
+//! are stored band by band, with `Y(l, m)` at index `l*(l+1) + m`.
+
+use crate::shapes::{sample_uniform_sphere, uniform_sphere_pdf};
+use crate::{sample_uniform_hemisphere_around, uniform_hemisphere_pdf, Vector3D};
+use rand::Rng;
+use std::f64::consts::PI;
+
+/// The number of coefficients needed for bands `0..=max_band`
+pub fn n_coefficients(max_band: usize) -> usize {
+    (max_band + 1) * (max_band + 1)
+}
+
+/// The index of the coefficient of `Y(l, m)`, where `-l <= m <= l`
+pub fn index(l: usize, m: i64) -> usize {
+    assert!(
+        m.unsigned_abs() as usize <= l,
+        "expecting |m| <= l... found l = {} and m = {}",
+        l,
+        m
+    );
+    (l as i64 * (l as i64 + 1) + m) as usize
+}
+
+/// The normalization factor of `Y(l, m)`, for `m >= 0`
+fn normalization(l: usize, m: usize) -> f64 {
+    // (l-m)!/(l+m)!, without computing the factorials
+    let ratio: f64 = ((l - m + 1)..=(l + m)).map(|k| 1. / k as f64).product();
+    ((2 * l + 1) as f64 * ratio / (4. * PI)).sqrt()
+}
+
+/// Evaluates all the basis functions of bands `0..=max_band` at
+/// `direction`, which must be normalized
+pub fn evaluate_basis(max_band: usize, direction: Vector3D) -> Vec<f64> {
+    let mut ret = vec![0.0; n_coefficients(max_band)];
+    let cos_theta = direction.z.clamp(-1., 1.);
+    let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+    let phi = direction.y.atan2(direction.x);
+
+    // Associated Legendre polynomials P(l, m), going up in l for each m
+    let mut p_mm = 1.0; // P(m, m) = (2m-1)!! sin^m
+    for m in 0..=max_band {
+        if m > 0 {
+            p_mm *= (2 * m - 1) as f64 * sin_theta;
+        }
+        let (sin_m_phi, cos_m_phi) = (m as f64 * phi).sin_cos();
+        let mut p_prev = 0.0;
+        let mut p_l = p_mm;
+        for l in m..=max_band {
+            if l == m + 1 {
+                p_prev = p_l;
+                p_l = cos_theta * (2 * m + 1) as f64 * p_mm;
+            } else if l > m + 1 {
+                let next = (cos_theta * (2 * l - 1) as f64 * p_l - (l + m - 1) as f64 * p_prev)
+                    / (l - m) as f64;
+                p_prev = p_l;
+                p_l = next;
+            }
+            let k = normalization(l, m);
+            if m == 0 {
+                ret[index(l, 0)] = k * p_l;
+            } else {
+                ret[index(l, m as i64)] = 2f64.sqrt() * k * cos_m_phi * p_l;
+                ret[index(l, -(m as i64))] = 2f64.sqrt() * k * sin_m_phi * p_l;
+            }
+        }
+    }
+    ret
+}
+
+/// The coefficient of band `l` of the clamped cosine kernel, `max(cos, 0)`,
+/// scaled so that convolving with it gives irradiance. It is zero for
+/// odd bands above 1.
+pub fn cosine_lobe_coefficient(l: usize) -> f64 {
+    match l {
+        0 => PI,
+        1 => 2. * PI / 3.,
+        _ if l % 2 == 1 => 0.,
+        _ => {
+            let half = l / 2;
+            let sign = if half % 2 == 1 { 1. } else { -1. };
+            // l!/(2^l (l/2)!^2), without computing the factorials
+            let ratio: f64 = (1..=half)
+                .map(|k| (half + k) as f64 / (4. * k as f64))
+                .product();
+            2. * PI * sign * ratio / ((l + 2) * (l - 1)) as f64
+        }
+    }
+}
+
+/// A function on the sphere, represented by its projection
+/// into the spherical harmonics
+#[derive(Debug, Clone, PartialEq)]
+pub struct SphericalHarmonics {
+    max_band: usize,
+    coefficients: Vec<f64>,
+}
+
+impl SphericalHarmonics {
+    /// Builds a projection from its coefficients, which must be
+    /// `n_coefficients(max_band)`
+    pub fn new(max_band: usize, coefficients: Vec<f64>) -> Self {
+        assert_eq!(
+            coefficients.len(),
+            n_coefficients(max_band),
+            "expecting {} coefficients for band {}",
+            n_coefficients(max_band),
+            max_band
+        );
+        Self {
+            max_band,
+            coefficients,
+        }
+    }
+
+    /// Projects `f` into bands `0..=max_band`, using `n` samples
+    /// distributed uniformly over the sphere
+    pub fn project<F: Fn(Vector3D) -> f64>(max_band: usize, f: F, n: usize, rng: &mut Rng) -> Self {
+        let pdf = uniform_sphere_pdf();
+        Self::project_with(max_band, f, n, |u| (sample_uniform_sphere(u), pdf), rng)
+    }
+
+    /// Projects `f`, which is zero below the hemisphere around `normal`,
+    /// into bands `0..=max_band`, using `n` samples distributed uniformly
+    /// over that hemisphere
+    pub fn project_hemisphere<F: Fn(Vector3D) -> f64>(
+        max_band: usize,
+        normal: Vector3D,
+        f: F,
+        n: usize,
+        rng: &mut Rng,
+    ) -> Self {
+        Self::project_with(
+            max_band,
+            f,
+            n,
+            |u| {
+                let v = sample_uniform_hemisphere_around(u, normal);
+                (v, uniform_hemisphere_pdf(v, normal))
+            },
+            rng,
+        )
+    }
+
+    /// Monte Carlo projection, where `sample` returns a direction and its PDF
+    fn project_with<F, S>(max_band: usize, f: F, n: usize, sample: S, rng: &mut Rng) -> Self
+    where
+        F: Fn(Vector3D) -> f64,
+        S: Fn((f64, f64)) -> (Vector3D, f64),
+    {
+        let mut coefficients = vec![0.0; n_coefficients(max_band)];
+        for _ in 0..n {
+            let (v, pdf) = sample((rng.next_float(), rng.next_float()));
+            let value = f(v) / pdf;
+            for (c, y) in coefficients.iter_mut().zip(evaluate_basis(max_band, v)) {
+                *c += value * y;
+            }
+        }
+        for c in coefficients.iter_mut() {
+            *c /= n as f64;
+        }
+        Self {
+            max_band,
+            coefficients,
+        }
+    }
+
+    /// The highest band in the projection
+    pub fn max_band(&self) -> usize {
+        self.max_band
+    }
+
+    /// The coefficients, band by band
+    pub fn coefficients(&self) -> &[f64] {
+        &self.coefficients
+    }
+
+    /// Reconstructs the projected function at `direction`
+    pub fn eval(&self, direction: Vector3D) -> f64 {
+        evaluate_basis(self.max_band, direction)
+            .iter()
+            .zip(self.coefficients.iter())
+            .map(|(y, c)| y * c)
+            .sum()
+    }
+
+    /// The projection of the convolution of this function with
+    /// the clamped cosine
+    pub fn convolve_cosine(&self) -> Self {
+        let mut coefficients = self.coefficients.clone();
+        for l in 0..=self.max_band {
+            let a = cosine_lobe_coefficient(l);
+            for m in -(l as i64)..=l as i64 {
+                coefficients[index(l, m)] *= a;
+            }
+        }
+        Self {
+            max_band: self.max_band,
+            coefficients,
+        }
+    }
+
+    /// The irradiance received by a surface facing `normal`, when this
+    /// is the luminance of its surroundings
+    pub fn irradiance(&self, normal: Vector3D) -> f64 {
+        self.convolve_cosine().eval(normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basis() {
+        let mut rng = Rng::with_seed(0);
+        for _ in 0..100 {
+            let v = sample_uniform_sphere((rng.next_float(), rng.next_float()));
+            let (x, y, z) = (v.x, v.y, v.z);
+            let expected = [
+                0.282095,
+                0.488603 * y,
+                0.488603 * z,
+                0.488603 * x,
+                1.092548 * x * y,
+                1.092548 * y * z,
+                0.315392 * (3. * z * z - 1.),
+                1.092548 * x * z,
+                0.546274 * (x * x - y * y),
+            ];
+            let found = evaluate_basis(2, v);
+            for (f, e) in found.iter().zip(expected.iter()) {
+                assert!((f - e).abs() < 1e-5, "expecting {:.6}... found {:.6}", e, f);
+            }
+        }
+    }
+
+    #[test]
+    fn projection() {
+        let mut rng = Rng::with_seed(1);
+        let max_band = 4;
+
+        // A basis function projects onto itself
+        for i in [0, 3, 6, 13, 20] {
+            let sh = SphericalHarmonics::project(
+                max_band,
+                |v| evaluate_basis(max_band, v)[i],
+                100_000,
+                &mut rng,
+            );
+            for (j, c) in sh.coefficients().iter().enumerate() {
+                let expected = if i == j { 1. } else { 0. };
+                assert!((c - expected).abs() < 3e-2, "{} {}: {:.6}", i, j, c);
+            }
+        }
+
+        // ... and band-limited functions are reconstructed
+        let f = |v: Vector3D| 1. + v.x - 2. * v.y * v.z + 0.5 * v.z * v.z;
+        let sh = SphericalHarmonics::project(2, f, 200_000, &mut rng);
+        for _ in 0..100 {
+            let v = sample_uniform_sphere((rng.next_float(), rng.next_float()));
+            assert!((sh.eval(v) - f(v)).abs() < 5e-2);
+        }
+
+        // A function on the upper hemisphere
+        let up = Vector3D::new(0., 0., 1.);
+        let f = |v: Vector3D| v.z.max(0.);
+        let a = SphericalHarmonics::project(max_band, f, 200_000, &mut rng);
+        let b = SphericalHarmonics::project_hemisphere(max_band, up, f, 200_000, &mut rng);
+        for (a, b) in a.coefficients().iter().zip(b.coefficients().iter()) {
+            assert!((a - b).abs() < 2e-2);
+        }
+    }
+
+    #[test]
+    fn cosine_lobe() {
+        // The projection of the clamped cosine around +Z only has m = 0
+        // terms, with A(l) = sqrt(4PI/(2l+1)) * c(l, 0)
+        let mut rng = Rng::with_seed(2);
+        let max_band = 6;
+        let sh =
+            SphericalHarmonics::project(max_band, |v: Vector3D| v.z.max(0.), 400_000, &mut rng);
+        for l in 0..=max_band {
+            let expected = cosine_lobe_coefficient(l);
+            let found = (4. * PI / (2 * l + 1) as f64).sqrt() * sh.coefficients()[index(l, 0)];
+            assert!(
+                (found - expected).abs() < 1e-2,
+                "band {}: expecting {:.6}... found {:.6}",
+                l,
+                expected,
+                found
+            );
+        }
+        assert!((cosine_lobe_coefficient(4) + PI / 24.).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,486 @@
+//! Uniform samplers for discs, spheres, cones and triangles.
+//!
+//! Every sampler has a matching PDF and an inverse mapping, which takes
+//! a sampled point back to the `u` in `[0,1)x[0,1)` that produced it.
+
+use crate::{OrthonormalBasis, Vector3D};
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+/// Maps a point in the unit square into a point in the disc of unit
+/// radius, using the concentric mapping from Shirley and Chiu (1997)
+/// "A Low Distortion Map Between Disk and Square".
+///
+/// Unlike the polar mapping (i.e., `r = sqrt(u)`), this one keeps
+/// stratified samples stratified.
+pub fn concentric_sample_disc(u: (f64, f64)) -> (f64, f64) {
+    let a = 2. * u.0 - 1.;
+    let b = 2. * u.1 - 1.;
+    if a == 0. && b == 0. {
+        return (0., 0.);
+    }
+    let (r, phi) = if a.abs() > b.abs() {
+        (a, FRAC_PI_4 * b / a)
+    } else {
+        (b, FRAC_PI_2 - FRAC_PI_4 * a / b)
+    };
+    let (phi_sin, phi_cos) = phi.sin_cos();
+    (r * phi_cos, r * phi_sin)
+}
+
+/// The inverse of [`concentric_sample_disc`]
+pub fn concentric_disc_inverse(p: (f64, f64)) -> (f64, f64) {
+    let (x, y) = p;
+    let r = (x * x + y * y).sqrt();
+    let mut phi = y.atan2(x);
+    if phi < -FRAC_PI_4 {
+        phi += 2. * PI;
+    }
+    let (a, b) = if phi < FRAC_PI_4 {
+        (r, r * phi / FRAC_PI_4)
+    } else if phi < 3. * FRAC_PI_4 {
+        (r * (FRAC_PI_2 - phi) / FRAC_PI_4, r)
+    } else if phi < 5. * FRAC_PI_4 {
+        (-r, -r * (phi - PI) / FRAC_PI_4)
+    } else {
+        (-r * (3. * FRAC_PI_2 - phi) / FRAC_PI_4, -r)
+    };
+    ((a + 1.) / 2., (b + 1.) / 2.)
+}
+
+/// The PDF (per unit area) of the points produced by [`concentric_sample_disc`]
+pub fn disc_pdf(p: (f64, f64)) -> f64 {
+    if p.0 * p.0 + p.1 * p.1 > 1. {
+        0.
+    } else {
+        1. / PI
+    }
+}
+
+/// Samples a direction uniformly within the spherical cap around +Z
+/// made of the directions whose `z` is larger than `cos_theta_max`.
+///
+/// It maps the concentric disc onto the cap preserving areas, so
+/// `cos_theta_max = 0` is the hemisphere and `cos_theta_max = -1`
+/// is the whole sphere.
+pub fn sample_spherical_cap(u: (f64, f64), cos_theta_max: f64) -> Vector3D {
+    let (x, y) = concentric_sample_disc(u);
+    let r2 = x * x + y * y;
+    let z = 1. - r2 * (1. - cos_theta_max);
+    if r2 == 0. {
+        return Vector3D::new(0., 0., 1.);
+    }
+    let sin_theta = (1. - z * z).max(0.).sqrt();
+    let r = r2.sqrt();
+    Vector3D::new(sin_theta * x / r, sin_theta * y / r, z)
+}
+
+/// The inverse of [`sample_spherical_cap`]
+pub fn spherical_cap_inverse(direction: Vector3D, cos_theta_max: f64) -> (f64, f64) {
+    let r = ((1. - direction.z) / (1. - cos_theta_max))
+        .clamp(0., 1.)
+        .sqrt();
+    let horizontal = (direction.x * direction.x + direction.y * direction.y).sqrt();
+    if horizontal == 0. {
+        return concentric_disc_inverse((0., 0.));
+    }
+    concentric_disc_inverse((r * direction.x / horizontal, r * direction.y / horizontal))
+}
+
+/// The PDF (per unit solid angle) of the directions produced by [`sample_spherical_cap`]
+pub fn spherical_cap_pdf(direction: Vector3D, cos_theta_max: f64) -> f64 {
+    if direction.z < cos_theta_max {
+        0.
+    } else {
+        1. / (2. * PI * (1. - cos_theta_max))
+    }
+}
+
+/// Samples a direction uniformly over the whole sphere
+pub fn sample_uniform_sphere(u: (f64, f64)) -> Vector3D {
+    sample_spherical_cap(u, -1.)
+}
+
+/// The inverse of [`sample_uniform_sphere`]
+pub fn uniform_sphere_inverse(direction: Vector3D) -> (f64, f64) {
+    spherical_cap_inverse(direction, -1.)
+}
+
+/// The PDF (per unit solid angle) of the directions produced by [`sample_uniform_sphere`]
+pub fn uniform_sphere_pdf() -> f64 {
+    0.25 / PI
+}
+
+/// Samples a direction uniformly within the cone around `axis`
+/// (which must be normalized) whose half angle has a cosine of
+/// `cos_theta_max`. E.g., the sun disc.
+pub fn sample_uniform_cone(u: (f64, f64), axis: Vector3D, cos_theta_max: f64) -> Vector3D {
+    let local = sample_spherical_cap(u, cos_theta_max);
+    OrthonormalBasis::new(axis).to_world(local)
+}
+
+/// The inverse of [`sample_uniform_cone`]
+pub fn uniform_cone_inverse(direction: Vector3D, axis: Vector3D, cos_theta_max: f64) -> (f64, f64) {
+    let local = OrthonormalBasis::new(axis).to_local(direction);
+    spherical_cap_inverse(local, cos_theta_max)
+}
+
+/// The PDF (per unit solid angle) of the directions produced by [`sample_uniform_cone`]
+pub fn uniform_cone_pdf(direction: Vector3D, axis: Vector3D, cos_theta_max: f64) -> f64 {
+    if direction.dot(axis) < cos_theta_max {
+        0.
+    } else {
+        1. / (2. * PI * (1. - cos_theta_max))
+    }
+}
+
+/// Samples the barycentric coordinates `(b0, b1)` of a point uniformly
+/// distributed over a triangle. The third one is `1 - b0 - b1`.
+pub fn sample_uniform_barycentric(u: (f64, f64)) -> (f64, f64) {
+    let su0 = u.0.sqrt();
+    (1. - su0, u.1 * su0)
+}
+
+/// The inverse of [`sample_uniform_barycentric`]
+pub fn uniform_barycentric_inverse(b: (f64, f64)) -> (f64, f64) {
+    let su0 = 1. - b.0;
+    if su0 <= 0. {
+        return (0., 0.);
+    }
+    (su0 * su0, (b.1 / su0).clamp(0., 1.))
+}
+
+/// Samples a point uniformly over the triangle `p0`, `p1`, `p2`
+pub fn sample_uniform_triangle(
+    u: (f64, f64),
+    p0: Vector3D,
+    p1: Vector3D,
+    p2: Vector3D,
+) -> Vector3D {
+    let (b0, b1) = sample_uniform_barycentric(u);
+    p0 * b0 + p1 * b1 + p2 * (1. - b0 - b1)
+}
+
+/// The inverse of [`sample_uniform_triangle`]. The `point` is
+/// assumed to be within the triangle.
+pub fn uniform_triangle_inverse(
+    point: Vector3D,
+    p0: Vector3D,
+    p1: Vector3D,
+    p2: Vector3D,
+) -> (f64, f64) {
+    let e0 = p0 - p2;
+    let e1 = p1 - p2;
+    let d = point - p2;
+    let n = e0.cross(e1);
+    let n2 = n.length_squared();
+    let b0 = d.cross(e1).dot(n) / n2;
+    let b1 = e0.cross(d).dot(n) / n2;
+    uniform_barycentric_inverse((b0, b1))
+}
+
+/// The PDF (per unit area) of the points produced by [`sample_uniform_triangle`]
+pub fn uniform_triangle_pdf(p0: Vector3D, p1: Vector3D, p2: Vector3D) -> f64 {
+    2. / (p1 - p0).cross(p2 - p0).length()
+}
+
+/// The angle between two normalized vectors, computed without the
+/// loss of precision of `acos` near 0 and PI
+fn angle_between(v1: Vector3D, v2: Vector3D) -> f64 {
+    if v1.dot(v2) < 0. {
+        PI - 2. * ((v1 + v2).length() / 2.).clamp(-1., 1.).asin()
+    } else {
+        2. * ((v2 - v1).length() / 2.).clamp(-1., 1.).asin()
+    }
+}
+
+/// Removes from `v` its component along `w` (which must be normalized)
+fn gram_schmidt(v: Vector3D, w: Vector3D) -> Vector3D {
+    v - w * v.dot(w)
+}
+
+/// The solid angle subtended by the spherical triangle whose vertices
+/// are the normalized directions `a`, `b` and `c`. (Van Oosterom and
+/// Strackee, 1983)
+pub fn spherical_triangle_area(a: Vector3D, b: Vector3D, c: Vector3D) -> f64 {
+    let num = a.dot(b.cross(c)).abs();
+    let den = 1. + a.dot(b) + b.dot(c) + c.dot(a);
+    2. * num.atan2(den)
+}
+
+/// The angles at the vertices `a`, `b` and `c` of a spherical triangle
+fn spherical_triangle_angles(a: Vector3D, b: Vector3D, c: Vector3D) -> (f64, f64, f64) {
+    let n_ab = a.cross(b).normalize();
+    let n_bc = b.cross(c).normalize();
+    let n_ca = c.cross(a).normalize();
+    let alpha = angle_between(n_ab, -n_ca);
+    let beta = angle_between(n_bc, -n_ab);
+    let gamma = angle_between(n_ca, -n_bc);
+    (alpha, beta, gamma)
+}
+
+/// Samples a direction uniformly (in solid angle) within the spherical
+/// triangle whose vertices are the normalized directions `a`, `b` and `c`.
+///
+/// Uses the method from Arvo (1995) "Stratified Sampling of Spherical
+/// Triangles".
+pub fn sample_spherical_triangle(u: (f64, f64), a: Vector3D, b: Vector3D, c: Vector3D) -> Vector3D {
+    let (alpha, beta, gamma) = spherical_triangle_angles(a, b, c);
+
+    // Pick the area of the sub-triangle a-b-c'
+    let area_pi = alpha + beta + gamma;
+    let sub_area_pi = PI + u.0 * (area_pi - PI);
+
+    // Find the cosine of the arc between a and c'
+    let (sin_alpha, cos_alpha) = alpha.sin_cos();
+    let (sin_sub, cos_sub) = sub_area_pi.sin_cos();
+    let sin_phi = sin_sub * cos_alpha - cos_sub * sin_alpha;
+    let cos_phi = cos_sub * cos_alpha + sin_sub * sin_alpha;
+    let k1 = cos_phi + cos_alpha;
+    let k2 = sin_phi - sin_alpha * a.dot(b);
+    let cos_bp = (k2 + (k2 * cos_phi - k1 * sin_phi) * cos_alpha)
+        / ((k2 * sin_phi + k1 * cos_phi) * sin_alpha);
+    let cos_bp = cos_bp.clamp(-1., 1.);
+    let sin_bp = (1. - cos_bp * cos_bp).max(0.).sqrt();
+    let cp = a * cos_bp + gram_schmidt(c, a).normalize() * sin_bp;
+
+    // Sample along the arc between b and c'
+    let cos_theta = 1. - u.1 * (1. - cp.dot(b));
+    let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+    b * cos_theta + gram_schmidt(cp, b).normalize() * sin_theta
+}
+
+/// The inverse of [`sample_spherical_triangle`]. The `direction` is
+/// assumed to be within the triangle.
+pub fn spherical_triangle_inverse(
+    direction: Vector3D,
+    a: Vector3D,
+    b: Vector3D,
+    c: Vector3D,
+) -> (f64, f64) {
+    let (alpha, beta, gamma) = spherical_triangle_angles(a, b, c);
+
+    // Find c', where the arc from b through the direction meets a-c
+    let mut cp = b.cross(direction).cross(c.cross(a)).normalize();
+    if cp.dot(a + c) < 0. {
+        cp = -cp;
+    }
+
+    let u0 = if cp.dot(a) > 1. - 1e-12 {
+        0.
+    } else {
+        let n_ab = a.cross(b).normalize();
+        let n_cpb = cp.cross(b).normalize();
+        let n_acp = a.cross(cp).normalize();
+        let sub_area = alpha + angle_between(n_ab, n_cpb) + angle_between(n_acp, -n_cpb) - PI;
+        let area = alpha + beta + gamma - PI;
+        sub_area / area
+    };
+    let u1 = (1. - direction.dot(b)) / (1. - cp.dot(b));
+    (u0.clamp(0., 1.), u1.clamp(0., 1.))
+}
+
+/// The PDF (per unit solid angle) of the directions produced by
+/// [`sample_spherical_triangle`]
+pub fn spherical_triangle_pdf(direction: Vector3D, a: Vector3D, b: Vector3D, c: Vector3D) -> f64 {
+    let s_ab = direction.dot(a.cross(b));
+    let s_bc = direction.dot(b.cross(c));
+    let s_ca = direction.dot(c.cross(a));
+    let inside =
+        (s_ab >= 0. && s_bc >= 0. && s_ca >= 0.) || (s_ab <= 0. && s_bc <= 0. && s_ca <= 0.);
+    if inside && direction.dot(a + b + c) > 0. {
+        1. / spherical_triangle_area(a, b, c)
+    } else {
+        0.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    const BINS: usize = 8;
+
+    /// Asserts that the values are uniformly distributed over
+    /// `[0,1)x[0,1)`, using a chi-square test
+    fn assert_uniform(values: &[(f64, f64)]) {
+        let mut histogram = vec![0.0; BINS * BINS];
+        for (u0, u1) in values {
+            let i = ((u0 * BINS as f64) as usize).min(BINS - 1);
+            let j = ((u1 * BINS as f64) as usize).min(BINS - 1);
+            histogram[i * BINS + j] += 1.;
+        }
+        let expected = values.len() as f64 / (BINS * BINS) as f64;
+        let chi2: f64 = histogram
+            .iter()
+            .map(|o| (o - expected).powi(2) / expected)
+            .sum();
+        // 63 degrees of freedom: mean 63, standard deviation ~11
+        assert!(chi2 < 63. + 5. * 11.3, "chi2 = {}", chi2);
+    }
+
+    /// A uniformly distributed direction, by rejection sampling
+    fn random_direction(rng: &mut Rng) -> Vector3D {
+        loop {
+            let v = Vector3D::new(
+                2.0 * rng.next_float() - 1.0,
+                2.0 * rng.next_float() - 1.0,
+                2.0 * rng.next_float() - 1.0,
+            );
+            let l = v.length();
+            if l > 1e-3 && l <= 1.0 {
+                return v.normalize();
+            }
+        }
+    }
+
+    fn roundtrip<F, G>(forward: F, inverse: G)
+    where
+        F: Fn((f64, f64)) -> Vector3D,
+        G: Fn(Vector3D) -> (f64, f64),
+    {
+        let mut rng = Rng::with_seed(0);
+        for _ in 0..1000 {
+            let u = (rng.next_float(), rng.next_float());
+            let found = inverse(forward(u));
+            assert!(
+                (found.0 - u.0).abs() < 1e-6 && (found.1 - u.1).abs() < 1e-6,
+                "{:?} vs {:?}",
+                u,
+                found
+            );
+        }
+    }
+
+    #[test]
+    fn concentric_disc() {
+        let mut rng = Rng::with_seed(0);
+        for _ in 0..1000 {
+            let u = (rng.next_float(), rng.next_float());
+            let p = concentric_sample_disc(u);
+            assert!(disc_pdf(p) > 0.);
+            let found = concentric_disc_inverse(p);
+            assert!((found.0 - u.0).abs() < 1e-9 && (found.1 - u.1).abs() < 1e-9);
+        }
+
+        let mut inverted = Vec::new();
+        while inverted.len() < 20000 {
+            let p = (2. * rng.next_float() - 1., 2. * rng.next_float() - 1.);
+            if disc_pdf(p) > 0. {
+                inverted.push(concentric_disc_inverse(p));
+            }
+        }
+        assert_uniform(&inverted);
+    }
+
+    #[test]
+    fn sphere_and_cones() {
+        roundtrip(sample_uniform_sphere, uniform_sphere_inverse);
+        let mut rng = Rng::with_seed(1);
+        let inverted: Vec<(f64, f64)> = (0..20000)
+            .map(|_| uniform_sphere_inverse(random_direction(&mut rng)))
+            .collect();
+        assert_uniform(&inverted);
+
+        // The sun disc
+        let axis = Vector3D::new(1., 2., 3.).normalize();
+        let cos_theta_max = 0.265f64.to_radians().cos();
+        roundtrip(
+            |u| sample_uniform_cone(u, axis, cos_theta_max),
+            |v| uniform_cone_inverse(v, axis, cos_theta_max),
+        );
+
+        // A wider cap, sampled by rejection
+        let cos_theta_max = 0.3;
+        roundtrip(
+            |u| sample_uniform_cone(u, axis, cos_theta_max),
+            |v| uniform_cone_inverse(v, axis, cos_theta_max),
+        );
+        let mut inverted = Vec::new();
+        while inverted.len() < 20000 {
+            let v = random_direction(&mut rng);
+            if uniform_cone_pdf(v, axis, cos_theta_max) > 0. {
+                inverted.push(uniform_cone_inverse(v, axis, cos_theta_max));
+            }
+        }
+        assert_uniform(&inverted);
+
+        for _ in 0..1000 {
+            let u = (rng.next_float(), rng.next_float());
+            let v = sample_uniform_cone(u, axis, cos_theta_max);
+            assert!(v.is_normalized());
+            assert!(uniform_cone_pdf(v, axis, cos_theta_max) > 0.);
+        }
+    }
+
+    #[test]
+    fn triangle() {
+        let p0 = Vector3D::new(0., 0., 0.);
+        let p1 = Vector3D::new(2., 0., 1.);
+        let p2 = Vector3D::new(0.5, 3., 0.);
+        roundtrip(
+            |u| sample_uniform_triangle(u, p0, p1, p2),
+            |p| uniform_triangle_inverse(p, p0, p1, p2),
+        );
+
+        // Uniform points, by rejection from the parallelogram
+        let mut rng = Rng::with_seed(2);
+        let mut inverted = Vec::new();
+        while inverted.len() < 20000 {
+            let (s, t) = (rng.next_float(), rng.next_float());
+            if s + t <= 1. {
+                let p = p0 + (p1 - p0) * s + (p2 - p0) * t;
+                inverted.push(uniform_triangle_inverse(p, p0, p1, p2));
+            }
+        }
+        assert_uniform(&inverted);
+
+        let area = (p1 - p0).cross(p2 - p0).length() / 2.;
+        assert!((uniform_triangle_pdf(p0, p1, p2) - 1. / area).abs() < 1e-12);
+    }
+
+    #[test]
+    fn spherical_triangle() {
+        let a = Vector3D::new(1., 0.2, 0.3).normalize();
+        let b = Vector3D::new(0.1, 1., 0.4).normalize();
+        let c = Vector3D::new(0.2, 0.1, 1.).normalize();
+
+        // The octant has an area of 4PI/8
+        let area = spherical_triangle_area(
+            Vector3D::new(1., 0., 0.),
+            Vector3D::new(0., 1., 0.),
+            Vector3D::new(0., 0., 1.),
+        );
+        assert!((area - PI / 2.).abs() < 1e-12);
+
+        let mut rng = Rng::with_seed(3);
+        for _ in 0..1000 {
+            let u = (rng.next_float(), rng.next_float());
+            let v = sample_spherical_triangle(u, a, b, c);
+            assert!(v.is_normalized());
+            assert!(spherical_triangle_pdf(v, a, b, c) > 0.);
+        }
+        roundtrip(
+            |u| sample_spherical_triangle(u, a, b, c),
+            |v| spherical_triangle_inverse(v, a, b, c),
+        );
+
+        let mut inverted = Vec::new();
+        let mut inside = 0;
+        let mut total = 0;
+        while inverted.len() < 20000 {
+            let v = random_direction(&mut rng);
+            total += 1;
+            if spherical_triangle_pdf(v, a, b, c) > 0. {
+                inside += 1;
+                inverted.push(spherical_triangle_inverse(v, a, b, c));
+            }
+        }
+        assert_uniform(&inverted);
+
+        // The fraction of the sphere covered by the triangle
+        let expected = spherical_triangle_area(a, b, c) / (4. * PI);
+        let found = inside as f64 / total as f64;
+        assert!((found - expected).abs() / expected < 0.05);
+    }
+}
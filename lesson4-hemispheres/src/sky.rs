@@ -0,0 +1,503 @@
+//! Sky luminance distributions.
+//!
+//! Directions are in world coordinates, with +Z pointing up (i.e.,
+//! towards the zenith). The sun is given as a normalized direction
+//! pointing towards it. Directions below the horizon have no luminance.
+
+use crate::{sample_cosine_weighted_hemisphere_around, Vector3D};
+use rand::Rng;
+use std::f64::consts::PI;
+
+pub trait Sky {
+    /// The luminance of the sky seen in `direction`, which must be normalized
+    fn luminance(&self, direction: Vector3D) -> f64;
+}
+
+/// The angle between two normalized directions
+fn angle(a: Vector3D, b: Vector3D) -> f64 {
+    a.dot(b).clamp(-1., 1.).acos()
+}
+
+/// Estimates the irradiance (or illuminance) received by a surface
+/// facing `normal`, from `n` cosine-weighted samples of its hemisphere
+pub fn irradiance(sky: &dyn Sky, normal: Vector3D, n: usize, rng: &mut Rng) -> f64 {
+    let mut s = 0.0;
+    for _ in 0..n {
+        let u = (rng.next_float(), rng.next_float());
+        let v = sample_cosine_weighted_hemisphere_around(u, normal);
+        // L*cos/pdf, with pdf = cos/PI
+        s += sky.luminance(v) * PI;
+    }
+    s / n as f64
+}
+
+/// A sky with the same luminance in every direction
+pub struct UniformSky {
+    pub luminance: f64,
+}
+
+impl Sky for UniformSky {
+    fn luminance(&self, direction: Vector3D) -> f64 {
+        if direction.z < 0. {
+            0.
+        } else {
+            self.luminance
+        }
+    }
+}
+
+/// The traditional CIE overcast sky, from Moon and Spencer (1942),
+/// whose luminance goes from `zenith_luminance/3` at the horizon
+/// to `zenith_luminance` at the zenith
+pub struct OvercastSky {
+    pub zenith_luminance: f64,
+}
+
+impl Sky for OvercastSky {
+    fn luminance(&self, direction: Vector3D) -> f64 {
+        if direction.z < 0. {
+            0.
+        } else {
+            self.zenith_luminance * (1. + 2. * direction.z) / 3.
+        }
+    }
+}
+
+/// The gradation (`a`, `b`) and indicatrix (`c`, `d`, `e`) parameters
+/// of the 15 CIE general standard skies (ISO 15469:2004/CIE S 011/E:2003)
+const CIE_PARAMETERS: [[f64; 5]; 15] = [
+    [4.0, -0.70, 0., -1.0, 0.00],
+    [4.0, -0.70, 2., -1.5, 0.15],
+    [1.1, -0.80, 0., -1.0, 0.00],
+    [1.1, -0.80, 2., -1.5, 0.15],
+    [0.0, -1.00, 0., -1.0, 0.00],
+    [0.0, -1.00, 2., -1.5, 0.15],
+    [0.0, -1.00, 5., -2.5, 0.30],
+    [0.0, -1.00, 10., -3.0, 0.45],
+    [-1.0, -0.55, 2., -1.5, 0.15],
+    [-1.0, -0.55, 5., -2.5, 0.30],
+    [-1.0, -0.55, 10., -3.0, 0.45],
+    [-1.0, -0.32, 10., -3.0, 0.45],
+    [-1.0, -0.32, 16., -3.0, 0.30],
+    [-1.0, -0.15, 16., -3.0, 0.30],
+    [-1.0, -0.15, 24., -2.8, 0.15],
+];
+
+/// One of the 15 CIE general standard skies, from type 1 (overcast,
+/// with steep gradation towards the zenith) to type 15 (cloudless,
+/// turbid, with a broad solar corona).
+pub struct CieSky {
+    /// The CIE type of sky, from 1 to 15
+    kind: usize,
+    sun: Vector3D,
+    pub zenith_luminance: f64,
+}
+
+impl CieSky {
+    pub fn new(kind: usize, sun: Vector3D, zenith_luminance: f64) -> Self {
+        assert!(
+            (1..=15).contains(&kind),
+            "expecting CIE sky type to be between 1 and 15... found {}",
+            kind
+        );
+        Self {
+            kind,
+            sun,
+            zenith_luminance,
+        }
+    }
+
+    pub fn kind(&self) -> usize {
+        self.kind
+    }
+
+    fn parameters(&self) -> [f64; 5] {
+        CIE_PARAMETERS[self.kind - 1]
+    }
+
+    /// The luminance gradation, as a function of the cosine of the zenith angle
+    fn gradation(&self, cos_z: f64) -> f64 {
+        let [a, b, ..] = self.parameters();
+        if cos_z <= 0. {
+            1.
+        } else {
+            1. + a * (b / cos_z).exp()
+        }
+    }
+
+    /// The scattering indicatrix, as a function of the angle to the sun
+    fn indicatrix(&self, chi: f64) -> f64 {
+        let [_, _, c, d, e] = self.parameters();
+        1. + c * ((d * chi).exp() - (d * PI / 2.).exp()) + e * chi.cos().powi(2)
+    }
+}
+
+impl Sky for CieSky {
+    fn luminance(&self, direction: Vector3D) -> f64 {
+        if direction.z < 0. {
+            return 0.;
+        }
+        let sun_zenith = self.sun.z.clamp(-1., 1.).acos();
+        let chi = angle(direction, self.sun);
+        self.zenith_luminance * self.indicatrix(chi) * self.gradation(direction.z)
+            / (self.indicatrix(sun_zenith) * self.gradation(1.))
+    }
+}
+
+/// Coefficients of the Perez all-weather model, one row per sky clearness
+/// bin. Each row contains `[x1, x2, x3, x4]` for `a`, `b`, `c`, `d` and `e`,
+/// where `x = x1 + x2 * Z + brightness * (x3 + x4 * Z)`
+const PEREZ_COEFFICIENTS: [[f64; 20]; 8] = [
+    [
+        1.3525, -0.2576, -0.2690, -1.4366, -0.7670, 0.0007, 1.2734, -0.1233, 2.8000, 0.6004,
+        1.2375, 1.0000, 1.8734, 0.6297, 0.9738, 0.2809, 0.0356, -0.1246, -0.5718, 0.9938,
+    ],
+    [
+        -1.2219, -0.7730, 1.4148, 1.1016, -0.2054, 0.0367, -3.9128, 0.9156, 6.9750, 0.1774, 6.4477,
+        -0.1239, -1.5798, -0.5081, -1.7812, 0.1080, 0.2624, 0.0672, -0.2190, -0.4285,
+    ],
+    [
+        -1.1000, -0.2515, 0.8952, 0.0156, 0.2782, -0.1812, -4.5000, 1.1766, 24.7219, -13.0812,
+        -37.7000, 34.8438, -5.0000, 1.5218, 3.9229, -2.6204, -0.0156, 0.1597, 0.4199, -0.5562,
+    ],
+    [
+        -0.5484, -0.6654, -0.2672, 0.7117, 0.7234, -0.6219, -5.6812, 2.6297, 33.3389, -18.3000,
+        -62.2500, 52.0781, -3.5000, 0.0016, 1.1477, 0.1062, 0.4659, -0.3296, -0.0876, -0.0329,
+    ],
+    [
+        -0.6000, -0.3566, -2.5000, 2.3250, 0.2937, 0.0496, -5.6812, 1.8415, 21.0000, -4.7656,
+        -21.5906, 7.2492, -3.5000, -0.1554, 1.4062, 0.3988, 0.0032, 0.0766, -0.0656, -0.1294,
+    ],
+    [
+        -1.0156, -0.3670, 1.0078, 1.4051, 0.2875, -0.5328, -3.8500, 3.3750, 14.0000, -0.9999,
+        -7.1406, 7.5469, -3.4000, -0.1078, -1.0750, 1.5702, -0.0672, 0.4016, 0.3017, -0.4844,
+    ],
+    [
+        -1.0000, 0.0211, 0.5025, -0.5119, -0.3000, 0.1922, 0.7023, -1.6317, 19.0000, -5.0000,
+        1.2438, -1.9094, -4.0000, 0.0250, 0.3844, 0.2656, 1.0468, -0.3788, -2.4517, 1.4656,
+    ],
+    [
+        -1.0500, 0.0289, 0.4260, 0.3590, -0.3250, 0.1156, 0.7781, 0.0025, 31.0625, -14.5000,
+        -46.1148, 55.3750, -7.2312, 0.4050, 13.3500, 0.6234, 1.5000, -0.6426, 1.8564, 0.5636,
+    ],
+];
+
+/// Upper limits of the sky clearness bins of the Perez model
+const PEREZ_CLEARNESS_BINS: [f64; 7] = [1.065, 1.230, 1.500, 1.950, 2.800, 4.500, 6.200];
+
+/// The extraterrestrial normal irradiance, in W/m2
+const SOLAR_CONSTANT: f64 = 1367.;
+
+/// The `F(theta, gamma)` function shared by the Perez and Preetham models
+fn perez_function(coefficients: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+    let [a, b, c, d, e] = *coefficients;
+    let cos_theta = cos_theta.max(0.01);
+    (1. + a * (b / cos_theta).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+/// The all-weather sky model from Perez, Seals and Michalsky (1993)
+/// "All-weather model for sky luminance distribution—Preliminary
+/// configuration and validation"
+pub struct PerezSky {
+    sun: Vector3D,
+    /// `a`, `b`, `c`, `d` and `e`
+    coefficients: [f64; 5],
+    pub zenith_luminance: f64,
+}
+
+impl PerezSky {
+    /// Builds the sky from its clearness (`epsilon`, from 1 for overcast to
+    /// about 6 and above for clear skies) and brightness (`delta`)
+    pub fn new(sun: Vector3D, epsilon: f64, delta: f64, zenith_luminance: f64) -> Self {
+        let bin = PEREZ_CLEARNESS_BINS
+            .iter()
+            .position(|limit| epsilon < *limit)
+            .unwrap_or(PEREZ_CLEARNESS_BINS.len());
+        let row = &PEREZ_COEFFICIENTS[bin];
+        let z = sun.z.clamp(-1., 1.).acos();
+        let x = |i: usize| row[i] + row[i + 1] * z + delta * (row[i + 2] + row[i + 3] * z);
+        let (c, d) = if bin == 0 {
+            (
+                (delta * (row[8] + row[9] * z)).powf(row[10]).exp() - row[11],
+                -(delta * (row[12] + row[13] * z)).exp() + row[14] + delta * row[15],
+            )
+        } else {
+            (x(8), x(12))
+        };
+        Self {
+            sun,
+            coefficients: [x(0), x(4), c, d, x(16)],
+            zenith_luminance,
+        }
+    }
+
+    /// Builds the sky from the diffuse horizontal and direct normal
+    /// irradiance (in W/m2)
+    pub fn from_irradiance(
+        sun: Vector3D,
+        diffuse_horizontal: f64,
+        direct_normal: f64,
+        zenith_luminance: f64,
+    ) -> Self {
+        let z = sun.z.clamp(-1., 1.).acos();
+        let k = 1.041 * z.powi(3);
+        let epsilon = ((diffuse_horizontal + direct_normal) / diffuse_horizontal + k) / (1. + k);
+        // Kasten and Young (1989)
+        let altitude = 90. - z.to_degrees();
+        let air_mass = 1. / (sun.z.max(0.) + 0.50572 * (altitude + 6.07995).powf(-1.6364));
+        let delta = diffuse_horizontal * air_mass / SOLAR_CONSTANT;
+        Self::new(sun, epsilon, delta, zenith_luminance)
+    }
+}
+
+impl Sky for PerezSky {
+    fn luminance(&self, direction: Vector3D) -> f64 {
+        if direction.z < 0. {
+            return 0.;
+        }
+        let sun_zenith = self.sun.z.clamp(-1., 1.).acos();
+        let gamma = angle(direction, self.sun);
+        self.zenith_luminance * perez_function(&self.coefficients, direction.z, gamma)
+            / perez_function(&self.coefficients, 1., sun_zenith)
+    }
+}
+
+/// The clear sky model from Preetham, Shirley and Smits (1999)
+/// "A Practical Analytic Model for Daylight". Luminances are in cd/m2.
+pub struct PreethamSky {
+    sun: Vector3D,
+    /// `A`, `B`, `C`, `D` and `E`
+    coefficients: [f64; 5],
+    zenith_luminance: f64,
+}
+
+impl PreethamSky {
+    /// Builds a sky with a `turbidity` between 2 (very clear)
+    /// and 10 (hazy)
+    pub fn new(sun: Vector3D, turbidity: f64) -> Self {
+        let t = turbidity;
+        let sun_zenith = sun.z.clamp(-1., 1.).acos();
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * sun_zenith);
+        // In kcd/m2
+        let zenith = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        Self {
+            sun,
+            coefficients: [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            zenith_luminance: zenith * 1000.,
+        }
+    }
+
+    pub fn zenith_luminance(&self) -> f64 {
+        self.zenith_luminance
+    }
+}
+
+impl Sky for PreethamSky {
+    fn luminance(&self, direction: Vector3D) -> f64 {
+        if direction.z < 0. {
+            return 0.;
+        }
+        let sun_zenith = self.sun.z.clamp(-1., 1.).acos();
+        let gamma = angle(direction, self.sun);
+        self.zenith_luminance * perez_function(&self.coefficients, direction.z, gamma)
+            / perez_function(&self.coefficients, 1., sun_zenith)
+    }
+}
+
+/// The `F(theta, gamma)` function of the Hosek-Wilkie model, an extension
+/// of [`perez_function`] with a Mie scattering term (`G`, `H`) around the
+/// sun and a zenith term (`I`)
+fn hosek_wilkie_function(coefficients: &[f64; 9], cos_theta: f64, gamma: f64) -> f64 {
+    let [a, b, c, d, e, f, g, h, i] = *coefficients;
+    let cos_theta = cos_theta.max(0.);
+    let cos_gamma = gamma.cos();
+    let mie = (1. + cos_gamma * cos_gamma) / (1. + h * h - 2. * h * cos_gamma).powf(1.5);
+    (1. + a * (b / (cos_theta + 0.01)).exp())
+        * (c + d * (e * gamma).exp() + f * cos_gamma * cos_gamma + g * mie + i * cos_theta.sqrt())
+}
+
+/// The clear sky model from Hosek and Wilkie (2012) "An Analytic Model
+/// for Full Spectral Sky-Dome Radiance", for luminance.
+///
+/// The model is defined by tables of fitted coefficients (e.g., the `Y`
+/// channel of its CIE XYZ dataset), which are not bundled here: they are
+/// interpolated for the turbidity, ground albedo and elevation of the sun
+/// into the nine `A` to `I` that this sky takes.
+pub struct HosekWilkieSky {
+    sun: Vector3D,
+    /// `A` to `I`, in the order of the paper (the reference implementation
+    /// stores `I` before `H`)
+    coefficients: [f64; 9],
+    pub zenith_luminance: f64,
+}
+
+impl HosekWilkieSky {
+    pub fn new(sun: Vector3D, coefficients: [f64; 9], zenith_luminance: f64) -> Self {
+        Self {
+            sun,
+            coefficients,
+            zenith_luminance,
+        }
+    }
+}
+
+impl Sky for HosekWilkieSky {
+    fn luminance(&self, direction: Vector3D) -> f64 {
+        if direction.z < 0. {
+            return 0.;
+        }
+        let sun_zenith = self.sun.z.clamp(-1., 1.).acos();
+        let gamma = angle(direction, self.sun);
+        self.zenith_luminance * hosek_wilkie_function(&self.coefficients, direction.z, gamma)
+            / hosek_wilkie_function(&self.coefficients, 1., sun_zenith)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_angles(altitude: f64, azimuth: f64) -> Vector3D {
+        let (alt_sin, alt_cos) = altitude.to_radians().sin_cos();
+        let (az_sin, az_cos) = azimuth.to_radians().sin_cos();
+        Vector3D::new(alt_cos * az_sin, alt_cos * az_cos, alt_sin)
+    }
+
+    fn up() -> Vector3D {
+        Vector3D::new(0., 0., 1.)
+    }
+
+    #[test]
+    fn cie_overcast() {
+        let sun = from_angles(40., 180.);
+        let overcast = OvercastSky {
+            zenith_luminance: 1.,
+        };
+        // Type 1 approximates the traditional CIE overcast sky (although
+        // it is darker near the horizon)
+        let sky = CieSky::new(1, sun, 1.);
+        for altitude in [30., 60., 90.] {
+            for azimuth in [0., 90., 180.] {
+                let v = from_angles(altitude, azimuth);
+                let found = sky.luminance(v);
+                let expected = overcast.luminance(v);
+                assert!((found - expected).abs() / expected < 0.05);
+            }
+        }
+
+        let mut rng = Rng::with_seed(0);
+        let expected = PI * 7. / 9.;
+        let found = irradiance(&sky, up(), 100_000, &mut rng);
+        assert!((found - expected).abs() / expected < 0.02);
+        let found = irradiance(&overcast, up(), 100_000, &mut rng);
+        assert!((found - expected).abs() / expected < 0.01);
+    }
+
+    #[test]
+    fn cie_skies() {
+        let sun = from_angles(35., 180.);
+        let towards_sun = from_angles(45., 180.);
+        let away_from_sun = from_angles(45., 0.);
+        for kind in 1..=15 {
+            let sky = CieSky::new(kind, sun, 1.);
+            assert!((sky.luminance(up()) - 1.).abs() < 1e-12);
+            assert_eq!(sky.luminance(from_angles(-10., 0.)), 0.);
+            for altitude in [0., 15., 45., 75.] {
+                for azimuth in [0., 120., 240.] {
+                    assert!(sky.luminance(from_angles(altitude, azimuth)) > 0.);
+                }
+            }
+            // The skies with an indicatrix are brighter around the sun
+            if sky.parameters()[2] > 0. {
+                assert!(sky.luminance(towards_sun) > sky.luminance(away_from_sun));
+            }
+        }
+        // Clear skies are brighter at the horizon than at the zenith
+        let clear = CieSky::new(12, sun, 1.);
+        assert!(clear.luminance(from_angles(2., 90.)) > 1.);
+    }
+
+    #[test]
+    fn perez() {
+        let sun = from_angles(30., 180.);
+        let overcast = PerezSky::new(sun, 1.0, 0.2, 1.);
+        let clear = PerezSky::new(sun, 6.5, 0.1, 1.);
+        for sky in [&overcast, &clear] {
+            assert!((sky.luminance(up()) - 1.).abs() < 1e-12);
+            for altitude in [1., 15., 45., 75.] {
+                for azimuth in [0., 120., 240.] {
+                    let l = sky.luminance(from_angles(altitude, azimuth));
+                    assert!(l.is_finite() && l > 0.);
+                }
+            }
+        }
+        // The clear sky has a bright circumsolar region, the overcast
+        // one is darker towards the horizon
+        assert!(clear.luminance(from_angles(35., 180.)) > 3.);
+        assert!(overcast.luminance(from_angles(5., 0.)) < 1.);
+
+        let sky = PerezSky::from_irradiance(sun, 100., 800., 1.);
+        assert!(sky.luminance(from_angles(35., 180.)) > sky.luminance(from_angles(35., 0.)));
+    }
+
+    #[test]
+    fn preetham() {
+        let sun = from_angles(60., 180.);
+        let sky = PreethamSky::new(sun, 2.);
+        let t: f64 = 2.;
+        let chi = (4. / 9. - t / 120.) * (PI - PI / 3.);
+        let expected = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192) * 1000.;
+        assert!((sky.zenith_luminance() - expected).abs() < 1e-9);
+        assert!((sky.luminance(up()) - expected).abs() < 1e-9);
+
+        // Brighter near the sun, and clear skies are brighter
+        // at the horizon than at the zenith
+        assert!(sky.luminance(from_angles(50., 180.)) > sky.luminance(from_angles(50., 0.)));
+        assert!(sky.luminance(from_angles(3., 0.)) > sky.zenith_luminance());
+    }
+
+    #[test]
+    fn hosek_wilkie() {
+        let sun = from_angles(30., 180.);
+        // With no Mie or zenith terms (and `C` = 1) it is the Perez
+        // function, but for the 0.01 that keeps it finite at the horizon
+        let preetham = PreethamSky::new(sun, 3.);
+        let [a, b, c, d, e] = preetham.coefficients;
+        let sky = HosekWilkieSky::new(
+            sun,
+            [a, b, 1., c, d, e, 0., 0.8, 0.],
+            preetham.zenith_luminance(),
+        );
+        for altitude in [10., 45., 80.] {
+            for azimuth in [0., 90., 180.] {
+                let v = from_angles(altitude, azimuth);
+                let (found, expected) = (sky.luminance(v), preetham.luminance(v));
+                assert!(
+                    (found - expected).abs() / expected < 0.02,
+                    "expecting {}... found {}",
+                    expected,
+                    found
+                );
+            }
+        }
+        assert!((sky.luminance(up()) - preetham.zenith_luminance()).abs() < 1e-9);
+        assert_eq!(sky.luminance(from_angles(-10., 0.)), 0.);
+
+        // The Mie term makes a narrow bright region around the sun
+        let mie = HosekWilkieSky::new(sun, [a, b, 1., c, d, e, 1., 0.9, 0.], 1.);
+        let near = mie.luminance(from_angles(32., 180.));
+        assert!(near > 2. * sky.luminance(from_angles(32., 180.)) / sky.zenith_luminance);
+        assert!(near > 5. * mie.luminance(from_angles(32., 0.)));
+        // ... and the zenith term brightens the sky towards the zenith
+        let zenith = HosekWilkieSky::new(sun, [a, b, 1., c, d, e, 0., 0.8, 2.], 1.);
+        let relative =
+            |sky: &HosekWilkieSky| sky.luminance(from_angles(5., 0.)) / sky.luminance(up());
+        assert!(relative(&zenith) < relative(&sky));
+    }
+}
@@ -0,0 +1,285 @@
+//! Discretizations of the sky hemisphere into patches, and daylight
+//! coefficients.
+//!
+//! The daylight coefficients of a surface are the irradiance it receives
+//! from each patch when that patch has a unit luminance. The irradiance
+//! from any sky is then the dot product of the daylight coefficients and
+//! the luminance of the patches (see [`SkyPatches::sky_vector`]).
+//!
+//! Directions use +Z as the zenith, +Y as North and +X as East, and patches
+//! are numbered from the horizon up, starting at North and going East.
+
+use crate::sky::Sky;
+use crate::{sample_cosine_weighted_hemisphere_around, Vector3D};
+use rand::Rng;
+use std::f64::consts::PI;
+
+/// Number of patches in each of the Tregenza rows, from the horizon up
+/// (without the one at the zenith)
+const TREGENZA_ROWS: [usize; 7] = [30, 30, 24, 24, 18, 12, 6];
+
+/// A band of patches with the same altitude
+#[derive(Debug, Clone, Copy)]
+struct Row {
+    /// In radians
+    min_altitude: f64,
+    /// In radians
+    max_altitude: f64,
+    n_patches: usize,
+    /// The index of the first patch of this row
+    first: usize,
+}
+
+/// The patches in which the sky is subdivided
+#[derive(Debug, Clone)]
+pub struct SkyPatches {
+    rows: Vec<Row>,
+}
+
+impl SkyPatches {
+    /// The 145 patches from Tregenza (1987)
+    pub fn tregenza() -> Self {
+        Self::reinhart(1)
+    }
+
+    /// Reinhart's MF:n subdivision of the Tregenza patches, where each of
+    /// them is split into `mf*mf` (the one at the zenith is kept as is).
+    /// MF:1 is Tregenza's.
+    pub fn reinhart(mf: usize) -> Self {
+        assert!(mf > 0, "expecting a subdivision of at least 1");
+        // The zenith patch is half a row high
+        let row_height = (PI / 2.) / ((TREGENZA_ROWS.len() * mf) as f64 + 0.5);
+        let mut rows = Vec::new();
+        let mut first = 0;
+        for (i, n) in TREGENZA_ROWS.iter().enumerate() {
+            for j in 0..mf {
+                let r = (i * mf + j) as f64;
+                rows.push(Row {
+                    min_altitude: r * row_height,
+                    max_altitude: (r + 1.) * row_height,
+                    n_patches: n * mf,
+                    first,
+                });
+                first += n * mf;
+            }
+        }
+        rows.push(Row {
+            min_altitude: (TREGENZA_ROWS.len() * mf) as f64 * row_height,
+            max_altitude: PI / 2.,
+            n_patches: 1,
+            first,
+        });
+        Self { rows }
+    }
+
+    /// The number of patches
+    pub fn len(&self) -> usize {
+        let last = self.rows.last().unwrap();
+        last.first + last.n_patches
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the row of patch `i`, and the position of the patch in it
+    fn locate(&self, i: usize) -> (&Row, usize) {
+        assert!(
+            i < self.len(),
+            "patch index out of bounds... found {} for {} patches",
+            i,
+            self.len()
+        );
+        let row = self
+            .rows
+            .iter()
+            .rev()
+            .find(|r| r.first <= i)
+            .expect("there is always a row starting at 0");
+        (row, i - row.first)
+    }
+
+    /// The azimuth range (in radians) of the `j`-th patch in a row with `n`
+    fn azimuth_range(j: usize, n: usize) -> (f64, f64) {
+        if n == 1 {
+            return (0., 2. * PI);
+        }
+        let width = 2. * PI / n as f64;
+        ((j as f64 - 0.5) * width, (j as f64 + 0.5) * width)
+    }
+
+    /// The solid angle subtended by patch `i`
+    pub fn solid_angle(&self, i: usize) -> f64 {
+        let (row, _) = self.locate(i);
+        2. * PI * (row.max_altitude.sin() - row.min_altitude.sin()) / row.n_patches as f64
+    }
+
+    /// The direction to the centre of patch `i`
+    pub fn centre(&self, i: usize) -> Vector3D {
+        let (row, j) = self.locate(i);
+        if row.n_patches == 1 {
+            return Vector3D::new(0., 0., 1.);
+        }
+        let (min_az, max_az) = Self::azimuth_range(j, row.n_patches);
+        let altitude = (row.min_altitude + row.max_altitude) / 2.;
+        from_angles(altitude, (min_az + max_az) / 2.)
+    }
+
+    /// Samples a direction uniformly (in solid angle) within patch `i`
+    pub fn sample(&self, i: usize, u: (f64, f64)) -> Vector3D {
+        let (row, j) = self.locate(i);
+        let (min_az, max_az) = Self::azimuth_range(j, row.n_patches);
+        let min_sin = row.min_altitude.sin();
+        let max_sin = row.max_altitude.sin();
+        let altitude = (min_sin + u.0 * (max_sin - min_sin)).asin();
+        let azimuth = min_az + u.1 * (max_az - min_az);
+        from_angles(altitude, azimuth)
+    }
+
+    /// The PDF (per unit solid angle) of the directions produced by
+    /// [`SkyPatches::sample`]
+    pub fn pdf(&self, i: usize, direction: Vector3D) -> f64 {
+        if self.patch_index(direction) == Some(i) {
+            1. / self.solid_angle(i)
+        } else {
+            0.
+        }
+    }
+
+    /// The patch that contains `direction`, which must be normalized.
+    /// Returns `None` for directions below the horizon.
+    pub fn patch_index(&self, direction: Vector3D) -> Option<usize> {
+        if direction.z < 0. {
+            return None;
+        }
+        let altitude = direction.z.clamp(-1., 1.).asin();
+        let row = self
+            .rows
+            .iter()
+            .find(|r| altitude < r.max_altitude)
+            .unwrap_or_else(|| self.rows.last().unwrap());
+        if row.n_patches == 1 {
+            return Some(row.first);
+        }
+        let width = 2. * PI / row.n_patches as f64;
+        let azimuth = direction.x.atan2(direction.y);
+        let j = ((azimuth / width + 0.5).floor() as i64).rem_euclid(row.n_patches as i64);
+        Some(row.first + j as usize)
+    }
+
+    /// Calculates the daylight coefficients of a surface facing `normal`,
+    /// from `n` cosine-weighted samples of its hemisphere.
+    pub fn daylight_coefficients(&self, normal: Vector3D, n: usize, rng: &mut Rng) -> Vec<f64> {
+        let mut coefficients = vec![0.0; self.len()];
+        for _ in 0..n {
+            let u = (rng.next_float(), rng.next_float());
+            let v = sample_cosine_weighted_hemisphere_around(u, normal);
+            if let Some(i) = self.patch_index(v) {
+                // L*cos/pdf, with pdf = cos/PI and L = 1
+                coefficients[i] += PI;
+            }
+        }
+        for c in coefficients.iter_mut() {
+            *c /= n as f64;
+        }
+        coefficients
+    }
+
+    /// The luminance of `sky` at the centre of each patch
+    pub fn sky_vector(&self, sky: &dyn Sky) -> Vec<f64> {
+        (0..self.len())
+            .map(|i| sky.luminance(self.centre(i)))
+            .collect()
+    }
+}
+
+/// Direction from an altitude and azimuth, in radians
+fn from_angles(altitude: f64, azimuth: f64) -> Vector3D {
+    let (alt_sin, alt_cos) = altitude.sin_cos();
+    let (az_sin, az_cos) = azimuth.sin_cos();
+    Vector3D::new(alt_cos * az_sin, alt_cos * az_cos, alt_sin)
+}
+
+/// The irradiance produced by a sky, given the daylight coefficients
+/// of a surface and the luminance of each patch
+pub fn contribution(daylight_coefficients: &[f64], sky_vector: &[f64]) -> f64 {
+    assert_eq!(
+        daylight_coefficients.len(),
+        sky_vector.len(),
+        "expecting one luminance per daylight coefficient"
+    );
+    daylight_coefficients
+        .iter()
+        .zip(sky_vector.iter())
+        .map(|(dc, l)| dc * l)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sky::{irradiance, CieSky, OvercastSky};
+
+    #[test]
+    fn patches() {
+        for (mf, expected) in [(1, 145), (2, 577), (4, 2305)] {
+            let patches = SkyPatches::reinhart(mf);
+            assert_eq!(patches.len(), expected);
+
+            let total: f64 = (0..patches.len()).map(|i| patches.solid_angle(i)).sum();
+            assert!((total - 2. * PI).abs() < 1e-9);
+
+            let mut rng = Rng::with_seed(0);
+            for i in 0..patches.len() {
+                assert_eq!(patches.patch_index(patches.centre(i)), Some(i));
+                for _ in 0..10 {
+                    let v = patches.sample(i, (rng.next_float(), rng.next_float()));
+                    assert!(v.is_normalized());
+                    assert_eq!(patches.patch_index(v), Some(i));
+                    assert!(patches.pdf(i, v) > 0.);
+                }
+            }
+        }
+
+        // Tregenza's rows are 12 degrees high, and the zenith patch is 6
+        let tregenza = SkyPatches::tregenza();
+        assert!((tregenza.rows[0].max_altitude.to_degrees() - 12.).abs() < 1e-9);
+        assert!((tregenza.rows[7].min_altitude.to_degrees() - 84.).abs() < 1e-9);
+        assert_eq!(tregenza.patch_index(Vector3D::new(0., 0., -1.)), None);
+    }
+
+    #[test]
+    fn daylight_coefficients() {
+        let mut rng = Rng::with_seed(0);
+        let patches = SkyPatches::reinhart(2);
+        let up = Vector3D::new(0., 0., 1.);
+        let south = Vector3D::new(0., -1., 0.);
+        let sun = Vector3D::new(0.3, -0.6, 0.5).normalize();
+        let skies: Vec<Box<dyn Sky>> = vec![
+            Box::new(OvercastSky {
+                zenith_luminance: 1.,
+            }),
+            Box::new(CieSky::new(5, sun, 1.)),
+        ];
+
+        for normal in [up, south] {
+            let dc = patches.daylight_coefficients(normal, 200_000, &mut rng);
+            // With a uniform sky, the coefficients add up to the
+            // irradiance from a unit luminance
+            let total: f64 = dc.iter().sum();
+            let expected = if normal.z > 0.5 { PI } else { PI / 2. };
+            assert!((total - expected).abs() / expected < 1e-2);
+
+            for sky in skies.iter() {
+                let found = contribution(&dc, &patches.sky_vector(sky.as_ref()));
+                let expected = irradiance(sky.as_ref(), normal, 200_000, &mut rng);
+                assert!(
+                    (found - expected).abs() / expected < 2e-2,
+                    "{} vs {}",
+                    found,
+                    expected
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! The position of the sun, using the algorithm from Michalsky (1988)
+//! "The Astronomical Almanac's algorithm for approximate solar position
+//! (1950–2050)", which is accurate to about 0.01 degrees.
+//!
+//! Directions use +Z as the zenith, +Y as North and +X as East.
+
+use crate::shapes::{sample_uniform_cone, uniform_cone_pdf};
+use crate::Vector3D;
+use std::f64::consts::PI;
+
+/// The apparent angular radius of the sun, in degrees
+pub const SUN_HALF_ANGLE: f64 = 0.265;
+
+/// A place on Earth
+#[derive(Debug, Clone, Copy)]
+pub struct Location {
+    /// In degrees, positive towards the North
+    pub latitude: f64,
+    /// In degrees, positive towards the East
+    pub longitude: f64,
+    /// The difference between local time and UTC, in hours (e.g., -7 for MST)
+    pub timezone: f64,
+}
+
+/// The position of the sun in the sky
+#[derive(Debug, Clone, Copy)]
+pub struct SolarPosition {
+    /// In degrees above the horizon, corrected by refraction
+    pub altitude: f64,
+    /// In degrees, clockwise from North
+    pub azimuth: f64,
+    /// In degrees
+    pub declination: f64,
+}
+
+/// The Julian date of a date (in the Gregorian calendar) and
+/// hour (in UTC)
+fn julian_date(year: i32, month: u32, day: u32, hour: f64) -> f64 {
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 12)
+    } else {
+        (year, month)
+    };
+    let a = y.div_euclid(100);
+    let b = 2 - a + a.div_euclid(4);
+    (365.25 * (y + 4716) as f64).floor()
+        + (30.6001 * (m + 1) as f64).floor()
+        + day as f64
+        + b as f64
+        - 1524.5
+        + hour / 24.
+}
+
+/// The atmospheric refraction (in degrees) of the sun seen
+/// at `elevation` (in degrees)
+fn refraction(elevation: f64, pressure: f64, temperature: f64) -> f64 {
+    if elevation <= -0.56 {
+        0.
+    } else if elevation >= 19.225 {
+        0.00452 * pressure / ((273. + temperature) * elevation.to_radians().tan())
+    } else {
+        let e = elevation;
+        pressure * (0.1594 + 0.0196 * e + 0.00002 * e * e)
+            / ((273. + temperature) * (1. + 0.505 * e + 0.0845 * e * e))
+    }
+}
+
+impl SolarPosition {
+    /// Calculates the position of the sun at `location`, at a local `hour`
+    /// (e.g., 13.5 for 1:30 PM) of a day, with a standard atmosphere
+    pub fn new(location: &Location, year: i32, month: u32, day: u32, hour: f64) -> Self {
+        Self::with_atmosphere(location, year, month, day, hour, 1013.25, 10.)
+    }
+
+    /// Same as [`SolarPosition::new`], with the pressure (in millibars) and the
+    /// temperature (in Celsius) used to calculate the refraction
+    pub fn with_atmosphere(
+        location: &Location,
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: f64,
+        pressure: f64,
+        temperature: f64,
+    ) -> Self {
+        let ut = hour - location.timezone;
+        let n = julian_date(year, month, day, ut) - 2451545.0;
+
+        // Ecliptic coordinates
+        let mean_longitude = (280.460 + 0.9856474 * n).rem_euclid(360.);
+        let mean_anomaly = (357.528 + 0.9856003 * n).rem_euclid(360.).to_radians();
+        let ecliptic_longitude =
+            (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2. * mean_anomaly).sin())
+                .to_radians();
+        let obliquity = (23.439 - 0.0000004 * n).to_radians();
+
+        // Celestial coordinates
+        let right_ascension =
+            (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+        let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+
+        // Local coordinates
+        let gmst = (6.697375 + 0.0657098242 * n + ut).rem_euclid(24.);
+        let lmst = (gmst + location.longitude / 15.).rem_euclid(24.);
+        let hour_angle =
+            ((lmst * 15.).to_radians() - right_ascension + PI).rem_euclid(2. * PI) - PI;
+
+        let lat = location.latitude.to_radians();
+        let elevation = (declination.sin() * lat.sin()
+            + declination.cos() * lat.cos() * hour_angle.cos())
+        .asin();
+        let azimuth = hour_angle
+            .sin()
+            .atan2(hour_angle.cos() * lat.sin() - declination.tan() * lat.cos())
+            + PI;
+
+        let elevation = elevation.to_degrees();
+        Self {
+            altitude: elevation + refraction(elevation, pressure, temperature),
+            azimuth: azimuth.to_degrees().rem_euclid(360.),
+            declination: declination.to_degrees(),
+        }
+    }
+
+    /// In degrees from the zenith
+    pub fn zenith(&self) -> f64 {
+        90. - self.altitude
+    }
+
+    /// Whether the sun is above the horizon
+    pub fn is_up(&self) -> bool {
+        self.altitude > 0.
+    }
+
+    /// The normalized direction pointing towards the sun
+    pub fn direction(&self) -> Vector3D {
+        let (alt_sin, alt_cos) = self.altitude.to_radians().sin_cos();
+        let (az_sin, az_cos) = self.azimuth.to_radians().sin_cos();
+        Vector3D::new(alt_cos * az_sin, alt_cos * az_cos, alt_sin)
+    }
+
+    /// The solar disc, as seen from this position
+    pub fn sun_disc(&self) -> SunDisc {
+        SunDisc::new(self.direction())
+    }
+}
+
+/// The disc of the sun, used for sampling directions towards it
+#[derive(Debug, Clone, Copy)]
+pub struct SunDisc {
+    /// The direction towards the centre of the sun
+    pub direction: Vector3D,
+    /// The cosine of [`SUN_HALF_ANGLE`]
+    pub cos_half_angle: f64,
+}
+
+impl SunDisc {
+    pub fn new(direction: Vector3D) -> Self {
+        Self {
+            direction,
+            cos_half_angle: SUN_HALF_ANGLE.to_radians().cos(),
+        }
+    }
+
+    /// The solid angle subtended by the sun, in steradians
+    pub fn solid_angle(&self) -> f64 {
+        2. * PI * (1. - self.cos_half_angle)
+    }
+
+    /// Samples a direction uniformly within the sun disc
+    pub fn sample(&self, u: (f64, f64)) -> Vector3D {
+        sample_uniform_cone(u, self.direction, self.cos_half_angle)
+    }
+
+    /// The PDF (per unit solid angle) of the directions produced by [`SunDisc::sample`]
+    pub fn pdf(&self, direction: Vector3D) -> f64 {
+        uniform_cone_pdf(direction, self.direction, self.cos_half_angle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn spa_reference() {
+        // The example in Reda and Andreas (2008) "Solar Position Algorithm
+        // for Solar Radiation Applications", NREL/TP-560-34302
+        let golden = Location {
+            latitude: 39.742476,
+            longitude: -105.1786,
+            timezone: -7.,
+        };
+        let hour = 12. + 30. / 60. + 30. / 3600.;
+        let sun = SolarPosition::with_atmosphere(&golden, 2003, 10, 17, hour, 820., 11.);
+        assert!((sun.zenith() - 50.11162).abs() < 0.01, "{}", sun.zenith());
+        assert!((sun.azimuth - 194.34024).abs() < 0.01, "{}", sun.azimuth);
+        assert!(sun.is_up());
+        assert!(sun.direction().is_normalized());
+    }
+
+    #[test]
+    fn almanac_declinations() {
+        let greenwich = Location {
+            latitude: 51.48,
+            longitude: 0.,
+            timezone: 0.,
+        };
+        // Meeus (1998) "Astronomical Algorithms", example 25.a: -7°47'01"
+        let sun = SolarPosition::new(&greenwich, 1992, 10, 13, 0.);
+        let expected = -(7. + 47. / 60. + 1. / 3600.);
+        assert!(
+            (sun.declination - expected).abs() < 0.01,
+            "{}",
+            sun.declination
+        );
+
+        // The equinoxes and solstices of 2024 (in UTC), as published by the
+        // U.S. Naval Observatory
+        let seasons = [
+            (3, 20, 3. + 6. / 60., 0.),
+            (6, 20, 20. + 51. / 60., 23.436),
+            (9, 22, 12. + 44. / 60., 0.),
+            (12, 21, 9. + 20. / 60., -23.436),
+        ];
+        for (month, day, hour, expected) in seasons {
+            let sun = SolarPosition::new(&greenwich, 2024, month, day, hour);
+            assert!(
+                (sun.declination - expected).abs() < 0.01,
+                "expecting a declination of {} on {}/{}... found {}",
+                expected,
+                month,
+                day,
+                sun.declination
+            );
+        }
+    }
+
+    #[test]
+    fn london_sunrise() {
+        let london = Location {
+            latitude: 51.5074,
+            longitude: -0.1278,
+            timezone: 0.,
+        };
+        // Published sunrise and sunset times (in UTC, to the minute) and
+        // directions at the solstices of 2024. These put the centre of the
+        // sun 50' below the horizon (its radius plus the refraction).
+        let almanac = [
+            (6, 3. + 43. / 60., 49.),
+            (6, 20. + 21. / 60., 311.),
+            (12, 8. + 4. / 60., 128.),
+            (12, 15. + 53. / 60., 232.),
+        ];
+        for (month, hour, azimuth) in almanac {
+            let sun = SolarPosition::new(&london, 2024, month, 21, hour);
+            assert!(
+                (sun.altitude + 50. / 60.).abs() < 0.15,
+                "expecting an altitude of -0.833 at {}... found {}",
+                hour,
+                sun.altitude
+            );
+            assert!(!sun.is_up());
+            assert!(
+                (sun.azimuth - azimuth).abs() < 1.,
+                "expecting an azimuth of {} at {}... found {}",
+                azimuth,
+                hour,
+                sun.azimuth
+            );
+        }
+    }
+
+    #[test]
+    fn solstices() {
+        let greenwich = Location {
+            latitude: 23.44,
+            longitude: 0.,
+            timezone: 0.,
+        };
+        // Near the solar noon, the sun is at the zenith of the Tropic of Cancer
+        let sun = SolarPosition::new(&greenwich, 2024, 6, 20, 12.03);
+        assert!(
+            (sun.declination - 23.44).abs() < 0.02,
+            "{}",
+            sun.declination
+        );
+        assert!(sun.altitude > 89.5, "{}", sun.altitude);
+
+        // ... and to the North in the southern hemisphere in June
+        let wellington = Location {
+            latitude: -41.29,
+            longitude: 174.78,
+            timezone: 12.,
+        };
+        let sun = SolarPosition::new(&wellington, 2024, 6, 21, 12.5);
+        assert!(sun.azimuth < 10. || sun.azimuth > 350., "{}", sun.azimuth);
+        let expected = 90. - 41.29 - 23.44;
+        assert!((sun.altitude - expected).abs() < 0.5, "{}", sun.altitude);
+
+        // ... and it is night at midnight
+        let sun = SolarPosition::new(&wellington, 2024, 6, 21, 0.);
+        assert!(!sun.is_up());
+
+        let sun = SolarPosition::new(&greenwich, 2024, 12, 21, 12.);
+        assert!(
+            (sun.declination + 23.44).abs() < 0.02,
+            "{}",
+            sun.declination
+        );
+    }
+
+    #[test]
+    fn sun_disc() {
+        let sun = SolarPosition::new(
+            &Location {
+                latitude: -33.45,
+                longitude: -70.66,
+                timezone: -4.,
+            },
+            2024,
+            3,
+            1,
+            15.,
+        );
+        let disc = sun.sun_disc();
+        // The sun subtends about 6.8e-5 steradians
+        assert!((disc.solid_angle() - 6.8e-5).abs() < 0.1e-5);
+
+        let mut rng = Rng::with_seed(0);
+        let max_angle = SUN_HALF_ANGLE.to_radians();
+        for _ in 0..1000 {
+            let v = disc.sample((rng.next_float(), rng.next_float()));
+            assert!(v.is_normalized());
+            assert!(v.dot(disc.direction).clamp(-1., 1.).acos() <= max_angle + 1e-9);
+            assert!((disc.pdf(v) - 1. / disc.solid_angle()).abs() < 1e-6 / disc.solid_angle());
+        }
+    }
+}
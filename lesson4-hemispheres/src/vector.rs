@@ -0,0 +1,279 @@
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Sub, SubAssign};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vector3D {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Vector3D {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn dot(&self, other: Self) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: Self) -> Self {
+        Self {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+
+    pub fn length_squared(&self) -> f64 {
+        self.dot(*self)
+    }
+
+    pub fn length(&self) -> f64 {
+        self.length_squared().sqrt()
+    }
+
+    /// Returns a vector with the same direction and a length of 1.
+    ///
+    /// The result is meaningless (i.e., NaN) for the zero vector
+    pub fn normalize(&self) -> Self {
+        *self / self.length()
+    }
+
+    pub fn is_normalized(&self) -> bool {
+        (self.length_squared() - 1.0).abs() < 1e-9
+    }
+
+    /// Checks whether all the components of `self` and `other` are
+    /// within `tolerance` of each other
+    pub fn approx_eq(&self, other: Self, tolerance: f64) -> bool {
+        (self.x - other.x).abs() <= tolerance
+            && (self.y - other.y).abs() <= tolerance
+            && (self.z - other.z).abs() <= tolerance
+    }
+
+    /// The component-wise minimum of `self` and `other`
+    pub fn min(&self, other: Self) -> Self {
+        Self::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    /// The component-wise maximum of `self` and `other`
+    pub fn max(&self, other: Self) -> Self {
+        Self::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
+}
+
+/// Access to the components by axis (i.e., 0 is `x`, 1 is `y` and 2 is `z`)
+impl Index<usize> for Vector3D {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("expecting an axis between 0 and 2... found {}", axis),
+        }
+    }
+}
+
+impl Add for Vector3D {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl AddAssign for Vector3D {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Vector3D {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl SubAssign for Vector3D {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Vector3D {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl Mul<f64> for Vector3D {
+    type Output = Self;
+    fn mul(self, s: f64) -> Self {
+        Self::new(self.x * s, self.y * s, self.z * s)
+    }
+}
+
+impl Mul<Vector3D> for f64 {
+    type Output = Vector3D;
+    fn mul(self, v: Vector3D) -> Vector3D {
+        v * self
+    }
+}
+
+impl MulAssign<f64> for Vector3D {
+    fn mul_assign(&mut self, s: f64) {
+        *self = *self * s;
+    }
+}
+
+impl Div<f64> for Vector3D {
+    type Output = Self;
+    fn div(self, s: f64) -> Self {
+        Self::new(self.x / s, self.y / s, self.z / s)
+    }
+}
+
+impl DivAssign<f64> for Vector3D {
+    fn div_assign(&mut self, s: f64) {
+        *self = *self / s;
+    }
+}
+
+/// An orthonormal basis `{u, v, w}`, where `w` is a given normal.
+///
+/// It is used to rotate directions sampled around the local +Z axis
+/// so that they are around `w` instead.
+#[derive(Debug, Clone, Copy)]
+pub struct OrthonormalBasis {
+    pub u: Vector3D,
+    pub v: Vector3D,
+    pub w: Vector3D,
+}
+
+impl OrthonormalBasis {
+    /// Builds a basis around `normal`, which must be normalized.
+    ///
+    /// Uses the branchless construction from Duff et al. (2017)
+    /// "Building an Orthonormal Basis, Revisited".
+    pub fn new(normal: Vector3D) -> Self {
+        debug_assert!(
+            normal.is_normalized(),
+            "expecting normal to be normalized... found length {:.6}",
+            normal.length()
+        );
+        let sign = 1f64.copysign(normal.z);
+        let a = -1.0 / (sign + normal.z);
+        let b = normal.x * normal.y * a;
+        let u = Vector3D::new(
+            1.0 + sign * normal.x * normal.x * a,
+            sign * b,
+            -sign * normal.x,
+        );
+        let v = Vector3D::new(b, sign + normal.y * normal.y * a, -normal.y);
+        Self { u, v, w: normal }
+    }
+
+    /// Transforms a direction from the local coordinates of the
+    /// basis (where `w` is +Z) into world coordinates
+    pub fn to_world(&self, local: Vector3D) -> Vector3D {
+        self.u * local.x + self.v * local.y + self.w * local.z
+    }
+
+    /// Transforms a direction from world coordinates into the
+    /// local coordinates of the basis (where `w` is +Z)
+    pub fn to_local(&self, world: Vector3D) -> Vector3D {
+        Vector3D::new(world.dot(self.u), world.dot(self.v), world.dot(self.w))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn random_direction(rng: &mut Rng) -> Vector3D {
+        loop {
+            let v = Vector3D::new(
+                2.0 * rng.next_float() - 1.0,
+                2.0 * rng.next_float() - 1.0,
+                2.0 * rng.next_float() - 1.0,
+            );
+            let l = v.length();
+            if l > 1e-3 && l <= 1.0 {
+                return v.normalize();
+            }
+        }
+    }
+
+    #[test]
+    fn operators() {
+        let a = Vector3D::new(1., 2., 3.);
+        let b = Vector3D::new(-2., 0.5, 4.);
+        assert_eq!(a + b, Vector3D::new(-1., 2.5, 7.));
+        assert_eq!(a - b, Vector3D::new(3., 1.5, -1.));
+        assert_eq!(-a, Vector3D::new(-1., -2., -3.));
+        assert_eq!(a * 2., 2. * a);
+        assert_eq!(a * 2. / 2., a);
+        assert_eq!(a.dot(b), 11.);
+
+        let mut c = a;
+        c += b;
+        c -= b;
+        c *= 3.;
+        c /= 3.;
+        assert!(c.approx_eq(a, 1e-12));
+
+        let x = Vector3D::new(1., 0., 0.);
+        let y = Vector3D::new(0., 1., 0.);
+        assert_eq!(x.cross(y), Vector3D::new(0., 0., 1.));
+        assert_eq!(a.cross(b).dot(a), 0.);
+        assert_eq!(a.cross(b).dot(b), 0.);
+
+        assert_eq!(Vector3D::new(3., 0., 4.).length(), 5.);
+        assert!(a.normalize().is_normalized());
+
+        assert_eq!(a.min(b), Vector3D::new(-2., 0.5, 3.));
+        assert_eq!(a.max(b), Vector3D::new(1., 2., 4.));
+        assert_eq!((a[0], a[1], a[2]), (1., 2., 3.));
+    }
+
+    #[test]
+    fn orthonormal_basis() {
+        let mut rng = Rng::with_seed(0);
+        let mut normals = vec![
+            Vector3D::new(0., 0., 1.),
+            Vector3D::new(0., 0., -1.),
+            Vector3D::new(1., 0., 0.),
+        ];
+        for _ in 0..1000 {
+            normals.push(random_direction(&mut rng));
+        }
+
+        for n in normals {
+            let onb = OrthonormalBasis::new(n);
+            assert!(onb.u.is_normalized());
+            assert!(onb.v.is_normalized());
+            assert!(onb.u.dot(onb.v).abs() < 1e-9);
+            assert!(onb.u.dot(n).abs() < 1e-9);
+            assert!(onb.v.dot(n).abs() < 1e-9);
+            // right handed
+            assert!(onb.u.cross(onb.v).approx_eq(n, 1e-9));
+
+            let up = Vector3D::new(0., 0., 1.);
+            assert!(onb.to_world(up).approx_eq(n, 1e-12));
+
+            let d = random_direction(&mut rng);
+            assert!(onb.to_local(onb.to_world(d)).approx_eq(d, 1e-9));
+        }
+    }
+}
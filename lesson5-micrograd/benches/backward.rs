@@ -0,0 +1,30 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lesson5_micrograd::Value;
+
+/// `tanh(w . x + b)` for a neuron with `n` inputs
+fn neuron(n: usize) -> (Vec<Value>, Value) {
+    let weights: Vec<Value> = (0..n).map(|i| Value::new(0.01 * i as f64)).collect();
+    let inputs: Vec<Value> = (0..n).map(|i| Value::new(1.0 - 0.02 * i as f64)).collect();
+    let products: Vec<Value> = weights
+        .iter()
+        .zip(inputs.iter())
+        .map(|(w, x)| w * x)
+        .collect();
+    let out = (Value::sum(&products) + 0.1).tanh();
+    (weights, out)
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    c.bench_function("forward 1000", |b| b.iter(|| neuron(black_box(1000))));
+    let (weights, out) = neuron(1000);
+    c.bench_function("backward 1000", |b| {
+        b.iter(|| {
+            out.zero_grad_all();
+            out.backward();
+            black_box(weights[0].grad())
+        })
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
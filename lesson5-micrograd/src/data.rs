@@ -0,0 +1,51 @@
+//! Synthetic datasets to train models on
+
+use rand::Rng;
+use std::f64::consts::PI;
+
+/// A point and its label (`-1` or `1`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub x: [f64; 2],
+    pub y: f64,
+}
+
+/// Two interleaving half circles (as `make_moons` in scikit-learn), with
+/// `n / 2` points in each one: the upper one labelled `-1` and the lower
+/// one `1`. Both coordinates get normal noise with standard deviation
+/// `noise`.
+pub fn two_moons(n: usize, noise: f64, rng: &mut Rng) -> Vec<Sample> {
+    (0..n)
+        .map(|i| {
+            let upper = i % 2 == 0;
+            let t = PI * rng.next_float();
+            let (x, y) = if upper {
+                (t.cos(), t.sin())
+            } else {
+                (1.0 - t.cos(), 0.5 - t.sin())
+            };
+            Sample {
+                x: [x + noise * rng.next_normal(), y + noise * rng.next_normal()],
+                y: if upper { -1.0 } else { 1.0 },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moons() {
+        let data = two_moons(200, 0.0, &mut Rng::with_seed(0));
+        assert_eq!(data.len(), 200);
+        assert_eq!(data.iter().filter(|s| s.y > 0.0).count(), 100);
+        for s in data.iter() {
+            let (cx, cy) = if s.y < 0.0 { (0.0, 0.0) } else { (1.0, 0.5) };
+            let radius = ((s.x[0] - cx).powi(2) + (s.x[1] - cy).powi(2)).sqrt();
+            assert!((radius - 1.0).abs() < 1e-12, "{:?}", s);
+        }
+        assert_eq!(data, two_moons(200, 0.0, &mut Rng::with_seed(0)));
+    }
+}
@@ -0,0 +1,450 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::rc::Rc;
+
+/// How a [`Value`] was computed from its children
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// An input (or a parameter), with no children
+    Leaf,
+    Add,
+    Mul,
+    /// Raised to a constant exponent
+    Pow(f64),
+    Exp,
+    /// The natural logarithm
+    Log,
+    Tanh,
+    Relu,
+}
+
+struct Node {
+    data: f64,
+    grad: f64,
+    op: Op,
+    children: Vec<Value>,
+}
+
+impl Drop for Node {
+    /// Drops the nodes only reachable from this one without recursion,
+    /// which would overflow the stack for long chains
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(Value(node)) = stack.pop() {
+            if let Ok(node) = Rc::try_unwrap(node) {
+                stack.append(&mut node.into_inner().children);
+            }
+        }
+    }
+}
+
+/// A scalar in a computation graph, which remembers how it was computed,
+/// so [`Value::backward`] can find the derivative of it with respect to
+/// every value it depends on.
+///
+/// Cloning a `Value` does not copy it: both clones are the same node
+/// of the graph.
+#[derive(Clone)]
+pub struct Value(Rc<RefCell<Node>>);
+
+impl Value {
+    pub fn new(data: f64) -> Self {
+        Self::from_op(data, Op::Leaf, Vec::new())
+    }
+
+    fn from_op(data: f64, op: Op, children: Vec<Value>) -> Self {
+        Value(Rc::new(RefCell::new(Node {
+            data,
+            grad: 0.0,
+            op,
+            children,
+        })))
+    }
+
+    pub fn data(&self) -> f64 {
+        self.0.borrow().data
+    }
+
+    /// Changes the value of a leaf (e.g., a parameter after a step of
+    /// gradient descent). Values computed from it are not updated.
+    pub fn set_data(&self, data: f64) {
+        self.0.borrow_mut().data = data;
+    }
+
+    /// The derivative of the last values `backward()` was called on,
+    /// with respect to this one, added up since the last `zero_grad()`
+    pub fn grad(&self) -> f64 {
+        self.0.borrow().grad
+    }
+
+    pub fn zero_grad(&self) {
+        self.0.borrow_mut().grad = 0.0;
+    }
+
+    pub fn op(&self) -> Op {
+        self.0.borrow().op
+    }
+
+    pub fn children(&self) -> Vec<Value> {
+        self.0.borrow().children.clone()
+    }
+
+    /// A new leaf with the same data, so gradients do not flow through it
+    /// (i.e., it is a constant for `backward()`)
+    pub fn detach(&self) -> Value {
+        Value::new(self.data())
+    }
+
+    /// Whether `self` and `other` are the same node (not only equal data)
+    pub fn same(&self, other: &Value) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    pub fn pow(&self, exponent: f64) -> Value {
+        Self::from_op(
+            self.data().powf(exponent),
+            Op::Pow(exponent),
+            vec![self.clone()],
+        )
+    }
+
+    pub fn exp(&self) -> Value {
+        Self::from_op(self.data().exp(), Op::Exp, vec![self.clone()])
+    }
+
+    /// The natural logarithm
+    pub fn ln(&self) -> Value {
+        Self::from_op(self.data().ln(), Op::Log, vec![self.clone()])
+    }
+
+    pub fn tanh(&self) -> Value {
+        Self::from_op(self.data().tanh(), Op::Tanh, vec![self.clone()])
+    }
+
+    pub fn relu(&self) -> Value {
+        Self::from_op(self.data().max(0.0), Op::Relu, vec![self.clone()])
+    }
+
+    /// The sum of `values` (zero if there are none)
+    pub fn sum<'a, I: IntoIterator<Item = &'a Value>>(values: I) -> Value {
+        values
+            .into_iter()
+            .fold(Value::new(0.0), |total, v| &total + v)
+    }
+
+    /// The nodes this one depends on (including itself), each one after
+    /// all of its children
+    pub fn topological_order(&self) -> Vec<Value> {
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        // Iterative, so long chains (e.g., sums of many terms) do not
+        // overflow the stack. The flag says whether the children of
+        // the node have already been pushed.
+        let mut stack = vec![(self.clone(), false)];
+        while let Some((node, expanded)) = stack.pop() {
+            if expanded {
+                order.push(node);
+                continue;
+            }
+            if !visited.insert(Rc::as_ptr(&node.0)) {
+                continue;
+            }
+            let children = node.children();
+            stack.push((node, true));
+            for child in children.into_iter().rev() {
+                if !visited.contains(&Rc::as_ptr(&child.0)) {
+                    stack.push((child, false));
+                }
+            }
+        }
+        order
+    }
+
+    /// Adds the derivative of this value, with respect to every leaf it
+    /// depends on, to their gradients (i.e., reverse-mode differentiation).
+    /// The gradients of the values in between only hold this pass.
+    pub fn backward(&self) {
+        let order = self.topological_order();
+        for node in order.iter().filter(|n| n.op() != Op::Leaf) {
+            node.zero_grad();
+        }
+        self.0.borrow_mut().grad += 1.0;
+        for node in order.iter().rev() {
+            let node = node.0.borrow();
+            let (data, grad) = (node.data, node.grad);
+            let children = &node.children;
+            // The local derivative of the node with respect to each child
+            match node.op {
+                Op::Leaf => {}
+                Op::Add => {
+                    children[0].0.borrow_mut().grad += grad;
+                    children[1].0.borrow_mut().grad += grad;
+                }
+                Op::Mul => {
+                    let (a, b) = (children[0].data(), children[1].data());
+                    children[0].0.borrow_mut().grad += b * grad;
+                    children[1].0.borrow_mut().grad += a * grad;
+                }
+                Op::Pow(exponent) => {
+                    let x = children[0].data();
+                    children[0].0.borrow_mut().grad += exponent * x.powf(exponent - 1.0) * grad;
+                }
+                Op::Exp => children[0].0.borrow_mut().grad += data * grad,
+                Op::Log => {
+                    let x = children[0].data();
+                    children[0].0.borrow_mut().grad += grad / x;
+                }
+                Op::Tanh => children[0].0.borrow_mut().grad += (1.0 - data * data) * grad,
+                Op::Relu => {
+                    if data > 0.0 {
+                        children[0].0.borrow_mut().grad += grad;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sets the gradients of this value, and of all it depends on, to zero
+    pub fn zero_grad_all(&self) {
+        for node in self.topological_order() {
+            node.zero_grad();
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Value(data={}, grad={}, op={:?})",
+            self.data(),
+            self.grad(),
+            self.op()
+        )
+    }
+}
+
+impl From<f64> for Value {
+    fn from(data: f64) -> Self {
+        Value::new(data)
+    }
+}
+
+/// Implements a binary operator for every combination of `Value`,
+/// `&Value` and `f64`, given how to combine two `&Value`
+macro_rules! binary_op {
+    ($trait:ident, $method:ident, |$a:ident, $b:ident| $body:expr) => {
+        impl $trait<&Value> for &Value {
+            type Output = Value;
+            fn $method(self, other: &Value) -> Value {
+                let ($a, $b) = (self, other);
+                $body
+            }
+        }
+        impl $trait<Value> for Value {
+            type Output = Value;
+            fn $method(self, other: Value) -> Value {
+                (&self).$method(&other)
+            }
+        }
+        impl $trait<&Value> for Value {
+            type Output = Value;
+            fn $method(self, other: &Value) -> Value {
+                (&self).$method(other)
+            }
+        }
+        impl $trait<Value> for &Value {
+            type Output = Value;
+            fn $method(self, other: Value) -> Value {
+                self.$method(&other)
+            }
+        }
+        impl $trait<f64> for &Value {
+            type Output = Value;
+            fn $method(self, other: f64) -> Value {
+                self.$method(&Value::new(other))
+            }
+        }
+        impl $trait<f64> for Value {
+            type Output = Value;
+            fn $method(self, other: f64) -> Value {
+                (&self).$method(&Value::new(other))
+            }
+        }
+        impl $trait<&Value> for f64 {
+            type Output = Value;
+            fn $method(self, other: &Value) -> Value {
+                (&Value::new(self)).$method(other)
+            }
+        }
+        impl $trait<Value> for f64 {
+            type Output = Value;
+            fn $method(self, other: Value) -> Value {
+                (&Value::new(self)).$method(&other)
+            }
+        }
+    };
+}
+
+binary_op!(Add, add, |a, b| Value::from_op(
+    a.data() + b.data(),
+    Op::Add,
+    vec![a.clone(), b.clone()]
+));
+binary_op!(Mul, mul, |a, b| Value::from_op(
+    a.data() * b.data(),
+    Op::Mul,
+    vec![a.clone(), b.clone()]
+));
+binary_op!(Sub, sub, |a, b| a + &(-b));
+binary_op!(Div, div, |a, b| a * &b.pow(-1.0));
+
+impl Neg for &Value {
+    type Output = Value;
+    fn neg(self) -> Value {
+        self * -1.0
+    }
+}
+
+impl Neg for Value {
+    type Output = Value;
+    fn neg(self) -> Value {
+        -&self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks the gradients of `f` at `inputs` against central finite
+    /// differences
+    fn check_gradients<F: Fn(&[Value]) -> Value>(f: F, inputs: &[f64]) {
+        let values: Vec<Value> = inputs.iter().map(|x| Value::new(*x)).collect();
+        let out = f(&values);
+        out.backward();
+
+        let h = 1e-6;
+        for (i, value) in values.iter().enumerate() {
+            let at = |delta: f64| {
+                let shifted: Vec<Value> = inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(j, x)| Value::new(if i == j { x + delta } else { *x }))
+                    .collect();
+                f(&shifted).data()
+            };
+            let numerical = (at(h) - at(-h)) / (2.0 * h);
+            let analytical = value.grad();
+            assert!(
+                (numerical - analytical).abs() < 1e-5 * (1.0 + numerical.abs()),
+                "expecting d/dx{} = {:.6}... found {:.6}",
+                i,
+                numerical,
+                analytical
+            );
+        }
+    }
+
+    #[test]
+    fn operations() {
+        let a = Value::new(-4.0);
+        let b = Value::new(2.0);
+        assert_eq!((&a + &b).data(), -2.0);
+        assert_eq!((&a * &b).data(), -8.0);
+        assert_eq!((&a - &b).data(), -6.0);
+        assert_eq!((&a / &b).data(), -2.0);
+        assert_eq!((-&a).data(), 4.0);
+        assert_eq!((2.0 * &b + 1.0).data(), 5.0);
+        assert_eq!(b.pow(3.0).data(), 8.0);
+        assert_eq!(a.relu().data(), 0.0);
+        assert_eq!(b.relu().data(), 2.0);
+        assert_eq!(Value::sum(&[a.clone(), b.clone(), b.clone()]).data(), 0.0);
+    }
+
+    #[test]
+    fn micrograd_example() {
+        // The example in the README of Karpathy's micrograd
+        let a = Value::new(-4.0);
+        let b = Value::new(2.0);
+        let mut c = &a + &b;
+        let mut d = &a * &b + b.pow(3.0);
+        c = &c + &(&c + 1.0);
+        c = &c + &(1.0 + &c + (-&a));
+        d = &d + &(&d * 2.0 + (&b + &a).relu());
+        d = &d + &(3.0 * &d + (&b - &a).relu());
+        let e = &c - &d;
+        let f = e.pow(2.0);
+        let mut g = &f / 2.0;
+        g = &g + &(10.0 / &f);
+        g.backward();
+
+        assert!((g.data() - 24.7041).abs() < 1e-4, "{:?}", g);
+        assert!((a.grad() - 138.8338).abs() < 1e-4, "{:?}", a);
+        assert!((b.grad() - 645.5773).abs() < 1e-4, "{:?}", b);
+    }
+
+    #[test]
+    fn finite_differences() {
+        check_gradients(|v| &v[0] * &v[1] + v[0].exp(), &[0.3, -1.2]);
+        check_gradients(|v| (&v[0] / &v[1]).tanh() - v[1].pow(3.0), &[0.7, 1.5]);
+        check_gradients(|v| (&v[0] * 2.0 - &v[1]).relu() * &v[2], &[1.0, 0.5, -2.0]);
+        check_gradients(|v| v[0].pow(0.5) * v[0].pow(-1.5), &[2.0]);
+        check_gradients(|v| (&v[0] * &v[1]).ln() + v[1].exp().ln(), &[0.4, 2.5]);
+        // A neuron: tanh(w . x + b)
+        check_gradients(
+            |v| (&v[0] * &v[1] + &v[2] * &v[3] + &v[4]).tanh(),
+            &[0.5, -1.0, 2.0, 0.25, 0.1],
+        );
+    }
+
+    #[test]
+    fn shared_nodes() {
+        // `a` is used twice, so both of its uses add to its gradient
+        let a = Value::new(3.0);
+        let b = &a * &a;
+        b.backward();
+        assert_eq!(a.grad(), 6.0);
+
+        let c = &a + &a;
+        let d = &b * &c;
+        assert_eq!(d.topological_order().len(), 4);
+        assert!(d.topological_order().last().unwrap().same(&d));
+    }
+
+    #[test]
+    fn accumulation() {
+        let x = Value::new(2.0);
+        let y = x.pow(2.0);
+        y.backward();
+        y.backward();
+        // Gradients add up until they are set to zero
+        assert_eq!(x.grad(), 8.0);
+        y.zero_grad_all();
+        assert_eq!(x.grad(), 0.0);
+        y.backward();
+        assert_eq!(x.grad(), 4.0);
+
+        x.set_data(3.0);
+        assert_eq!(y.data(), 4.0);
+
+        // Nothing flows through a detached value
+        let z = &x * &x.detach();
+        x.zero_grad();
+        z.backward();
+        assert_eq!(x.grad(), 3.0);
+    }
+
+    #[test]
+    fn long_chains() {
+        // Deep graphs must not overflow the stack
+        let x = Value::new(1.0);
+        let terms: Vec<Value> = (0..100_000).map(|_| &x * 0.5).collect();
+        let total = Value::sum(&terms);
+        total.backward();
+        assert_eq!(total.data(), 50_000.0);
+        assert_eq!(x.grad(), 50_000.0);
+    }
+}
@@ -0,0 +1,8 @@
+pub mod data;
+pub mod engine;
+pub mod montecarlo;
+pub mod nn;
+pub mod optim;
+pub mod train;
+
+pub use crate::engine::Value;
@@ -0,0 +1,369 @@
+//! Gradients of Monte Carlo estimates, with respect to the parameters of
+//! the integrand and of the distribution of the samples
+
+use crate::engine::Value;
+use crate::nn::Module;
+use crate::optim::Sgd;
+use lesson1_sampling::step_pdf::DiscretePdf;
+use rand::Rng;
+
+/// A step PDF over `[0, max]`, with the probability of each step given
+/// by the softmax of a learnable logit
+pub struct LearnablePdf {
+    /// The upper limit of each step (the first one starts at 0)
+    steps: Vec<f64>,
+    pub logits: Vec<Value>,
+}
+
+impl LearnablePdf {
+    /// With the same density in every step
+    pub fn uniform(steps: Vec<f64>) -> Self {
+        let mut lower = 0.0;
+        for upper in steps.iter() {
+            assert!(
+                *upper > lower,
+                "expecting increasing steps... found {:.6} after {:.6}",
+                upper,
+                lower
+            );
+            lower = *upper;
+        }
+        let mut pdf = Self {
+            steps,
+            logits: Vec::new(),
+        };
+        pdf.logits = (0..pdf.steps.len())
+            .map(|k| Value::new(pdf.width(k).ln()))
+            .collect();
+        pdf
+    }
+
+    pub fn steps(&self) -> &[f64] {
+        &self.steps
+    }
+
+    fn lower(&self, k: usize) -> f64 {
+        if k == 0 {
+            0.0
+        } else {
+            self.steps[k - 1]
+        }
+    }
+
+    fn width(&self, k: usize) -> f64 {
+        self.steps[k] - self.lower(k)
+    }
+
+    /// The step `x` falls in
+    pub fn step(&self, x: f64) -> usize {
+        assert!(
+            (0.0..*self.steps.last().unwrap()).contains(&x),
+            "expecting x within the steps... found {:.6}",
+            x
+        );
+        self.steps.iter().position(|upper| x < *upper).unwrap()
+    }
+
+    /// Of each step (i.e., the softmax of the logits)
+    pub fn probabilities(&self) -> Vec<Value> {
+        // Shifting all the logits by a constant changes nothing but
+        // the overflows
+        let max = self
+            .logits
+            .iter()
+            .map(|l| l.data())
+            .fold(f64::NEG_INFINITY, f64::max);
+        let exps: Vec<Value> = self.logits.iter().map(|l| (l - max).exp()).collect();
+        let total = Value::sum(&exps);
+        exps.iter().map(|e| e / &total).collect()
+    }
+
+    /// Of each step
+    pub fn densities(&self) -> Vec<Value> {
+        self.probabilities()
+            .into_iter()
+            .enumerate()
+            .map(|(k, p)| p / self.width(k))
+            .collect()
+    }
+
+    /// A snapshot, to sample from with the other lessons
+    pub fn to_pdf(&self) -> DiscretePdf {
+        let densities = self.densities().iter().map(|d| d.data()).collect();
+        DiscretePdf::new(0.0, self.steps.clone(), densities)
+    }
+
+    /// The point with a CDF of `u`, as a function of the logits (i.e., a
+    /// sample reparameterized from the uniform sample `u`), given the
+    /// `probabilities` of the steps
+    pub fn inverse_cdf(&self, probabilities: &[Value], u: f64) -> Value {
+        let mut k = 0;
+        let mut cumulative = 0.0;
+        while k + 1 < self.steps.len() && cumulative + probabilities[k].data() <= u {
+            cumulative += probabilities[k].data();
+            k += 1;
+        }
+        let below = Value::sum(&probabilities[..k]);
+        (u - below) * self.width(k) / &probabilities[k] + self.lower(k)
+    }
+
+    pub fn samples(&self, n: usize, rng: &mut Rng) -> Vec<f64> {
+        let probabilities = self.probabilities();
+        (0..n)
+            .map(|_| {
+                self.inverse_cdf(&probabilities, rng.next_float())
+                    .data()
+                    .min(self.steps.last().unwrap().next_down())
+            })
+            .collect()
+    }
+}
+
+impl Module for LearnablePdf {
+    fn parameters(&self) -> Vec<Value> {
+        self.logits.clone()
+    }
+}
+
+/// Estimates `E_p[f(x)]` from `samples` of `pdf`, as a value whose
+/// gradient is the score-function (REINFORCE) estimate, `mean(grad f(x)
+/// + f(x) grad log p(x))`. `f` does not need to be continuous.
+pub fn score_function<F: Fn(f64) -> Value>(pdf: &LearnablePdf, f: F, samples: &[f64]) -> Value {
+    let densities = pdf.densities();
+    let terms: Vec<Value> = samples
+        .iter()
+        .map(|x| {
+            let fx = f(*x);
+            let log_p = densities[pdf.step(*x)].ln();
+            // `log_p - log_p.detach()` is zero, but not its gradient
+            &fx + fx.detach() * (&log_p - log_p.detach())
+        })
+        .collect();
+    Value::sum(&terms) / samples.len() as f64
+}
+
+/// Estimates `E_p[f(x)]` with `x = inverse_cdf(u)` for the uniform
+/// samples `uniforms`, as a value whose gradient is the
+/// reparameterization (pathwise) estimate, `mean(grad f(x(u)))`. It is
+/// only unbiased for continuous `f`.
+pub fn reparameterized<F: Fn(&Value) -> Value>(
+    pdf: &LearnablePdf,
+    f: F,
+    uniforms: &[f64],
+) -> Value {
+    let probabilities = pdf.probabilities();
+    let terms: Vec<Value> = uniforms
+        .iter()
+        .map(|u| f(&pdf.inverse_cdf(&probabilities, *u)))
+        .collect();
+    Value::sum(&terms) / uniforms.len() as f64
+}
+
+/// Estimates the second moment of the importance sampling estimator
+/// `f(x) / p(x)` (i.e., `integral(f^2 / p)`) from `samples` of `pdf`.
+/// As the integral of `f` does not depend on `p`, its gradient is the
+/// one of the variance of the estimator.
+pub fn second_moment<F: Fn(f64) -> f64>(pdf: &LearnablePdf, f: F, samples: &[f64]) -> Value {
+    let densities = pdf.densities();
+    let terms: Vec<Value> = samples
+        .iter()
+        .map(|x| {
+            let p = &densities[pdf.step(*x)];
+            // Reweighted by the density of the samples, which does not
+            // change with the logits
+            f(*x).powi(2) / p.data() / p
+        })
+        .collect();
+    Value::sum(&terms) / samples.len() as f64
+}
+
+/// Fits `pdf` to `f`, to minimize the variance of importance sampling,
+/// by gradient descent on the logarithm of the second moment (so the
+/// steps do not depend on the scale of `f`). Returns the estimated
+/// second moment of each iteration.
+pub fn fit_proposal<F: Fn(f64) -> f64>(
+    pdf: &LearnablePdf,
+    f: F,
+    optimizer: &mut Sgd,
+    iterations: usize,
+    samples: usize,
+    rng: &mut Rng,
+) -> Vec<f64> {
+    let parameters = pdf.parameters();
+    (0..iterations)
+        .map(|_| {
+            let samples = pdf.samples(samples, rng);
+            let moment = second_moment(pdf, &f, &samples);
+            pdf.zero_grad();
+            moment.ln().backward();
+            optimizer.step(&parameters);
+            moment.data()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lesson2_montecarlo::importance_sampling::{exp, EXP_INTEGRAL};
+
+    fn example() -> LearnablePdf {
+        let pdf = LearnablePdf::uniform(vec![0.2, 0.5, 0.6, 1.0]);
+        for (logit, x) in pdf.logits.iter().zip([0.3, -0.5, 1.2, 0.1]) {
+            logit.set_data(x);
+        }
+        pdf
+    }
+
+    /// `E_p[x^2]`, exactly
+    fn exact_moment(pdf: &LearnablePdf) -> Value {
+        let terms: Vec<Value> = pdf
+            .probabilities()
+            .iter()
+            .enumerate()
+            .map(|(k, p)| {
+                let (a, b) = (pdf.lower(k), pdf.steps[k]);
+                p * ((b.powi(3) - a.powi(3)) / (3.0 * (b - a)))
+            })
+            .collect();
+        Value::sum(&terms)
+    }
+
+    /// The derivatives of `g` with respect to each logit, by central
+    /// finite differences
+    fn finite_differences<G: Fn(&LearnablePdf) -> f64>(pdf: &LearnablePdf, g: G) -> Vec<f64> {
+        let h = 1e-6;
+        pdf.logits
+            .iter()
+            .map(|logit| {
+                let x = logit.data();
+                logit.set_data(x + h);
+                let above = g(pdf);
+                logit.set_data(x - h);
+                let below = g(pdf);
+                logit.set_data(x);
+                (above - below) / (2.0 * h)
+            })
+            .collect()
+    }
+
+    fn gradients(pdf: &LearnablePdf, value: &Value) -> Vec<f64> {
+        pdf.zero_grad();
+        value.backward();
+        pdf.logits.iter().map(|l| l.grad()).collect()
+    }
+
+    #[test]
+    fn probabilities() {
+        let pdf = LearnablePdf::uniform(vec![0.2, 0.5, 0.6, 1.0]);
+        for d in pdf.densities() {
+            assert!((d.data() - 1.0).abs() < 1e-12);
+        }
+        assert_eq!(pdf.step(0.0), 0);
+        assert_eq!(pdf.step(0.55), 2);
+
+        let pdf = example();
+        let total: f64 = pdf.probabilities().iter().map(|p| p.data()).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+        let discrete = pdf.to_pdf();
+        let probabilities = pdf.probabilities();
+        for u in [0.0, 0.1, 0.35, 0.5, 0.8, 0.99] {
+            let x = pdf.inverse_cdf(&probabilities, u).data();
+            assert!((discrete.cdf(x).0 - u).abs() < 1e-12, "{:.6}", u);
+        }
+        for x in pdf.samples(1000, &mut Rng::with_seed(0)) {
+            assert!((0.0..1.0).contains(&x));
+        }
+    }
+
+    #[test]
+    fn exact_gradients() {
+        let pdf = example();
+        let expected = finite_differences(&pdf, |pdf| exact_moment(pdf).data());
+        let found = gradients(&pdf, &exact_moment(&pdf));
+        for (e, f) in expected.iter().zip(found.iter()) {
+            assert!((e - f).abs() < 1e-6, "expecting {:.6}... found {:.6}", e, f);
+        }
+
+        // With the same uniform samples, the reparameterized estimate is
+        // a smooth function of the logits too
+        let mut rng = Rng::with_seed(1);
+        let uniforms: Vec<f64> = (0..100).map(|_| rng.next_float()).collect();
+        let estimate = |pdf: &LearnablePdf| reparameterized(pdf, |x| x.pow(2.0), &uniforms);
+        let expected = finite_differences(&pdf, |pdf| estimate(pdf).data());
+        let found = gradients(&pdf, &estimate(&pdf));
+        for (e, f) in expected.iter().zip(found.iter()) {
+            assert!((e - f).abs() < 1e-6, "expecting {:.6}... found {:.6}", e, f);
+        }
+    }
+
+    #[test]
+    fn estimated_gradients() {
+        let pdf = example();
+        let expected = finite_differences(&pdf, |pdf| exact_moment(pdf).data());
+        let scale = expected.iter().fold(0.0, |m: f64, e| m.max(e.abs()));
+        let n = 100_000;
+        let mut rng = Rng::with_seed(2);
+
+        let samples = pdf.samples(n, &mut rng);
+        let score = score_function(&pdf, |x| Value::new(x * x), &samples);
+        let uniforms: Vec<f64> = (0..n).map(|_| rng.next_float()).collect();
+        let pathwise = reparameterized(&pdf, |x| x.pow(2.0), &uniforms);
+
+        for estimate in [score, pathwise] {
+            assert!((estimate.data() - exact_moment(&pdf).data()).abs() < 0.01);
+            let found = gradients(&pdf, &estimate);
+            for (e, f) in expected.iter().zip(found.iter()) {
+                assert!(
+                    (e - f).abs() < 0.05 * scale,
+                    "expecting {:.6}... found {:.6}",
+                    e,
+                    f
+                );
+            }
+        }
+
+        // With respect to the integrand: d/da E_p[a x^2] = E_p[x^2]
+        let a = Value::new(3.0);
+        let estimate = score_function(&pdf, |x| &a * (x * x), &samples);
+        estimate.backward();
+        let expected = exact_moment(&pdf).data();
+        assert!((a.grad() - expected).abs() < 0.01, "{:.6}", a.grad());
+    }
+
+    #[test]
+    fn learned_proposal() {
+        // The variance of importance sampling `exp` with `pdf`, by
+        // quadrature
+        let variance = |pdf: &DiscretePdf| {
+            let n = 100_000;
+            let moment: f64 = (0..n)
+                .map(|i| {
+                    let x = (i as f64 + 0.5) / n as f64;
+                    exp(x).powi(2) / pdf.pdf(x)
+                })
+                .sum::<f64>()
+                / n as f64;
+            moment - EXP_INTEGRAL.powi(2)
+        };
+
+        let steps: Vec<f64> = (1..=20).map(|i| i as f64 / 20.0).collect();
+        let pdf = LearnablePdf::uniform(steps);
+        let uniform = variance(&pdf.to_pdf());
+        let mut rng = Rng::with_seed(3);
+        let moments = fit_proposal(&pdf, exp, &mut Sgd::new(0.1, 0.9), 200, 256, &mut rng);
+        let learned = variance(&pdf.to_pdf());
+        assert!(
+            learned < uniform / 10.0,
+            "expecting a variance below {:.6}... found {:.6}",
+            uniform / 10.0,
+            learned
+        );
+        let last = moments[moments.len() - 10..].iter().sum::<f64>() / 10.0;
+        assert!(last < moments[0], "{:?}", moments);
+
+        // The spike gets most of the samples
+        let spike = pdf.to_pdf().cdf(0.55).0 - pdf.to_pdf().cdf(0.45).0;
+        assert!(spike > 0.5, "{:.6}", spike);
+    }
+}
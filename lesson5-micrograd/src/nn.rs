@@ -0,0 +1,185 @@
+//! Multi-layer perceptrons made of [`Value`]s, so their parameters can be
+//! trained by gradient descent
+
+use crate::engine::Value;
+use rand::Rng;
+
+/// Anything with trainable parameters
+pub trait Module {
+    fn parameters(&self) -> Vec<Value>;
+
+    /// Sets the gradients of all the parameters to zero, before computing
+    /// the ones of the next step
+    fn zero_grad(&self) {
+        for p in self.parameters() {
+            p.zero_grad();
+        }
+    }
+}
+
+/// The non-linearity applied to the output of a [`Neuron`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activation {
+    Linear,
+    Relu,
+    Tanh,
+}
+
+impl Activation {
+    pub fn apply(&self, x: Value) -> Value {
+        match self {
+            Activation::Linear => x,
+            Activation::Relu => x.relu(),
+            Activation::Tanh => x.tanh(),
+        }
+    }
+}
+
+/// `activation(w . x + b)`
+pub struct Neuron {
+    pub weights: Vec<Value>,
+    pub bias: Value,
+    pub activation: Activation,
+}
+
+impl Neuron {
+    /// With weights uniformly distributed in `[-1, 1)` and no bias
+    pub fn new(inputs: usize, activation: Activation, rng: &mut Rng) -> Self {
+        Self {
+            weights: (0..inputs)
+                .map(|_| Value::new(2.0 * rng.next_float() - 1.0))
+                .collect(),
+            bias: Value::new(0.0),
+            activation,
+        }
+    }
+
+    pub fn forward(&self, x: &[Value]) -> Value {
+        assert_eq!(
+            x.len(),
+            self.weights.len(),
+            "expecting as many inputs as weights"
+        );
+        let products: Vec<Value> = self.weights.iter().zip(x).map(|(w, x)| w * x).collect();
+        self.activation.apply(Value::sum(&products) + &self.bias)
+    }
+}
+
+impl Module for Neuron {
+    fn parameters(&self) -> Vec<Value> {
+        let mut parameters = self.weights.clone();
+        parameters.push(self.bias.clone());
+        parameters
+    }
+}
+
+/// Neurons with the same inputs
+pub struct Layer {
+    pub neurons: Vec<Neuron>,
+}
+
+impl Layer {
+    pub fn new(inputs: usize, outputs: usize, activation: Activation, rng: &mut Rng) -> Self {
+        Self {
+            neurons: (0..outputs)
+                .map(|_| Neuron::new(inputs, activation, rng))
+                .collect(),
+        }
+    }
+
+    pub fn forward(&self, x: &[Value]) -> Vec<Value> {
+        self.neurons.iter().map(|n| n.forward(x)).collect()
+    }
+}
+
+impl Module for Layer {
+    fn parameters(&self) -> Vec<Value> {
+        self.neurons.iter().flat_map(|n| n.parameters()).collect()
+    }
+}
+
+/// Layers, each one fed with the outputs of the previous one
+pub struct MLP {
+    pub layers: Vec<Layer>,
+}
+
+impl MLP {
+    /// With `inputs` inputs and a layer of each of the `sizes`: ReLU for
+    /// the hidden ones, and linear for the last one
+    pub fn new(inputs: usize, sizes: &[usize], rng: &mut Rng) -> Self {
+        let mut layers = Vec::new();
+        let mut fan_in = inputs;
+        for (i, size) in sizes.iter().enumerate() {
+            let activation = if i + 1 == sizes.len() {
+                Activation::Linear
+            } else {
+                Activation::Relu
+            };
+            layers.push(Layer::new(fan_in, *size, activation, rng));
+            fan_in = *size;
+        }
+        Self { layers }
+    }
+
+    pub fn forward(&self, x: &[Value]) -> Vec<Value> {
+        self.layers
+            .iter()
+            .fold(x.to_vec(), |x, layer| layer.forward(&x))
+    }
+
+    /// The first output for the input `x`, as a plain number
+    pub fn predict(&self, x: &[f64]) -> f64 {
+        let x: Vec<Value> = x.iter().map(|x| Value::new(*x)).collect();
+        self.forward(&x)[0].data()
+    }
+}
+
+impl Module for MLP {
+    fn parameters(&self) -> Vec<Value> {
+        self.layers.iter().flat_map(|l| l.parameters()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameters() {
+        let mut rng = Rng::with_seed(0);
+        let mlp = MLP::new(3, &[4, 4, 1], &mut rng);
+        // (3 + 1) * 4 + (4 + 1) * 4 + (4 + 1) * 1
+        assert_eq!(mlp.parameters().len(), 41);
+        assert_eq!(mlp.layers[0].neurons[0].activation, Activation::Relu);
+        assert_eq!(mlp.layers[2].neurons[0].activation, Activation::Linear);
+        assert!(mlp
+            .parameters()
+            .iter()
+            .all(|p| (-1.0..1.0).contains(&p.data())));
+
+        let x: Vec<Value> = vec![Value::new(1.0), Value::new(-2.0), Value::new(0.5)];
+        let out = mlp.forward(&x);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].data(), mlp.predict(&[1.0, -2.0, 0.5]));
+
+        out[0].backward();
+        assert!(mlp.parameters().iter().any(|p| p.grad() != 0.0));
+        mlp.zero_grad();
+        assert!(mlp.parameters().iter().all(|p| p.grad() == 0.0));
+    }
+
+    #[test]
+    fn neuron() {
+        let neuron = Neuron {
+            weights: vec![Value::new(2.0), Value::new(-1.0)],
+            bias: Value::new(0.5),
+            activation: Activation::Tanh,
+        };
+        let out = neuron.forward(&[Value::new(1.0), Value::new(3.0)]);
+        assert_eq!(out.data(), (-0.5f64).tanh());
+        out.backward();
+        let slope = 1.0 - (-0.5f64).tanh().powi(2);
+        assert!((neuron.weights[1].grad() - 3.0 * slope).abs() < 1e-12);
+        assert!((neuron.bias.grad() - slope).abs() < 1e-12);
+    }
+}
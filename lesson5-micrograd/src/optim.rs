@@ -0,0 +1,68 @@
+//! Updates the parameters of a model from their gradients
+
+use crate::engine::Value;
+
+/// Stochastic gradient descent with momentum: each step moves the
+/// parameters by `velocity = momentum * velocity - learning_rate * grad`
+pub struct Sgd {
+    pub learning_rate: f64,
+    pub momentum: f64,
+    velocities: Vec<f64>,
+}
+
+impl Sgd {
+    pub fn new(learning_rate: f64, momentum: f64) -> Self {
+        assert!(
+            (0.0..1.0).contains(&momentum),
+            "expecting a momentum within 0 to 1 range... found {:.6}",
+            momentum
+        );
+        Self {
+            learning_rate,
+            momentum,
+            velocities: Vec::new(),
+        }
+    }
+
+    /// Updates `parameters`, which must be the same (in the same order)
+    /// in every step
+    pub fn step(&mut self, parameters: &[Value]) {
+        if self.velocities.is_empty() {
+            self.velocities = vec![0.0; parameters.len()];
+        }
+        assert_eq!(
+            self.velocities.len(),
+            parameters.len(),
+            "expecting the same parameters in every step"
+        );
+        for (p, v) in parameters.iter().zip(self.velocities.iter_mut()) {
+            *v = self.momentum * *v - self.learning_rate * p.grad();
+            p.set_data(p.data() + *v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quadratic() {
+        // The minimum of (x - 3)^2
+        let minimize = |momentum| {
+            let x = Value::new(0.0);
+            let mut sgd = Sgd::new(0.01, momentum);
+            let mut steps = 0;
+            while (x.data() - 3.0).abs() > 1e-3 {
+                x.zero_grad();
+                (&x - 3.0).pow(2.0).backward();
+                sgd.step(std::slice::from_ref(&x));
+                steps += 1;
+            }
+            steps
+        };
+        let plain = minimize(0.0);
+        let momentum = minimize(0.9);
+        assert!(momentum < plain, "{} {}", momentum, plain);
+    }
+}
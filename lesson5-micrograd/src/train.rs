@@ -0,0 +1,163 @@
+//! Losses, and the loop that minimizes them
+
+use crate::data::Sample;
+use crate::engine::Value;
+use crate::nn::{Module, MLP};
+use crate::optim::Sgd;
+use rand::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Loss {
+    /// Mean squared error, `mean((score - y)^2)`
+    Mse,
+    /// The loss of support vector machines, `mean(max(0, 1 - y * score))`,
+    /// for labels `-1` and `1`
+    Hinge,
+}
+
+impl Loss {
+    pub fn eval(&self, scores: &[Value], targets: &[f64]) -> Value {
+        assert_eq!(
+            scores.len(),
+            targets.len(),
+            "expecting as many scores as targets"
+        );
+        let losses: Vec<Value> = scores
+            .iter()
+            .zip(targets)
+            .map(|(score, y)| match self {
+                Loss::Mse => (score - *y).pow(2.0),
+                Loss::Hinge => (1.0 - score * *y).relu(),
+            })
+            .collect();
+        Value::sum(&losses) / scores.len() as f64
+    }
+}
+
+/// Trains a model for a number of epochs, each one going through all the
+/// samples (shuffled) in batches
+pub struct Training {
+    pub loss: Loss,
+    pub optimizer: Sgd,
+    pub epochs: usize,
+    pub batch_size: usize,
+}
+
+impl Training {
+    /// Returns the mean loss of each epoch
+    pub fn run(&mut self, model: &MLP, data: &[Sample], rng: &mut Rng) -> Vec<f64> {
+        assert!(
+            self.batch_size > 0,
+            "expecting at least 1 sample per batch... found 0"
+        );
+        assert!(
+            !data.is_empty(),
+            "expecting at least 1 sample to train on... found 0"
+        );
+        let mut order: Vec<usize> = (0..data.len()).collect();
+        let parameters = model.parameters();
+        (0..self.epochs)
+            .map(|_| {
+                shuffle(&mut order, rng);
+                let mut total = 0.0;
+                for batch in order.chunks(self.batch_size) {
+                    let scores: Vec<Value> = batch
+                        .iter()
+                        .map(|i| {
+                            let x = data[*i].x.map(Value::new);
+                            model.forward(&x)[0].clone()
+                        })
+                        .collect();
+                    let targets: Vec<f64> = batch.iter().map(|i| data[*i].y).collect();
+                    let loss = self.loss.eval(&scores, &targets);
+
+                    model.zero_grad();
+                    loss.backward();
+                    self.optimizer.step(&parameters);
+                    total += loss.data() * batch.len() as f64;
+                }
+                total / data.len() as f64
+            })
+            .collect()
+    }
+}
+
+/// Fisher-Yates
+fn shuffle<T>(items: &mut [T], rng: &mut Rng) {
+    for i in (1..items.len()).rev() {
+        let j = ((rng.next_float() * (i + 1) as f64) as usize).min(i);
+        items.swap(i, j);
+    }
+}
+
+/// The fraction of `data` whose label has the sign of the score of `model`
+pub fn accuracy(model: &MLP, data: &[Sample]) -> f64 {
+    let correct = data
+        .iter()
+        .filter(|s| (model.predict(&s.x) > 0.0) == (s.y > 0.0))
+        .count();
+    correct as f64 / data.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::two_moons;
+
+    #[test]
+    fn losses() {
+        let scores = [Value::new(2.0), Value::new(-0.5)];
+        assert_eq!(Loss::Mse.eval(&scores, &[1.0, -1.0]).data(), 0.625);
+        // Only the second one is within the margin
+        assert_eq!(Loss::Hinge.eval(&scores, &[1.0, -1.0]).data(), 0.25);
+        assert_eq!(Loss::Hinge.eval(&scores, &[-1.0, 1.0]).data(), 2.25);
+    }
+
+    fn train(seed: u64, loss: Loss) -> (MLP, Vec<f64>, Vec<Sample>) {
+        let mut rng = Rng::with_seed(seed);
+        let data = two_moons(100, 0.1, &mut rng);
+        let model = MLP::new(2, &[8, 8, 1], &mut rng);
+        let mut training = Training {
+            loss,
+            optimizer: Sgd::new(0.05, 0.9),
+            epochs: 30,
+            batch_size: 10,
+        };
+        let losses = training.run(&model, &data, &mut rng);
+        (model, losses, data)
+    }
+
+    #[test]
+    fn two_moons_classification() {
+        for loss in [Loss::Hinge, Loss::Mse] {
+            let (model, losses, data) = train(7, loss);
+            assert!(losses.last().unwrap() < &(0.5 * losses[0]), "{:?}", losses);
+            let found = accuracy(&model, &data);
+            assert!(
+                found >= 0.95,
+                "expecting an accuracy of 95%... found {:.6}",
+                found
+            );
+
+            // On new points too
+            let test = two_moons(200, 0.1, &mut Rng::with_seed(100));
+            let found = accuracy(&model, &test);
+            assert!(
+                found >= 0.9,
+                "expecting an accuracy of 90%... found {:.6}",
+                found
+            );
+        }
+    }
+
+    #[test]
+    fn reproducible() {
+        let (a, losses_a, _) = train(3, Loss::Hinge);
+        let (b, losses_b, _) = train(3, Loss::Hinge);
+        assert_eq!(losses_a, losses_b);
+        let data = |m: &MLP| m.parameters().iter().map(|p| p.data()).collect::<Vec<_>>();
+        assert_eq!(data(&a), data(&b));
+        let (_, losses_c, _) = train(4, Loss::Hinge);
+        assert_ne!(losses_a, losses_c);
+    }
+}
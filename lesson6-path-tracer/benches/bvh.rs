@@ -0,0 +1,101 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lesson4_hemispheres::shapes::sample_uniform_sphere;
+use lesson4_hemispheres::Vector3D;
+use lesson6_path_tracer::bvh::Bvh;
+use lesson6_path_tracer::geometry::Ray;
+use lesson6_path_tracer::intersections::triangle;
+use rand::Rng;
+
+fn random_direction(rng: &mut Rng) -> Vector3D {
+    sample_uniform_sphere((rng.next_float(), rng.next_float()))
+}
+
+fn triangles(n: usize, rng: &mut Rng) -> Vec<[Vector3D; 3]> {
+    (0..n)
+        .map(|_| {
+            let a = Vector3D::new(rng.next_float(), rng.next_float(), rng.next_float()) * 10.;
+            [
+                a,
+                a + random_direction(rng) * 0.5,
+                a + random_direction(rng) * 0.5,
+            ]
+        })
+        .collect()
+}
+
+fn rays(n: usize, rng: &mut Rng) -> Vec<Ray> {
+    (0..n)
+        .map(|_| {
+            let origin = Vector3D::new(rng.next_float(), rng.next_float(), rng.next_float()) * 10.;
+            Ray::new(origin, random_direction(rng))
+        })
+        .collect()
+}
+
+fn closest_hits(bvh: &Bvh, triangles: &[[Vector3D; 3]], rays: &[Ray]) -> usize {
+    rays.iter()
+        .filter(|ray| {
+            bvh.closest_hit(ray, f64::INFINITY, |i, t_max| {
+                let [a, b, c] = triangles[i];
+                triangle(ray, a, b, c, t_max).map(|h| (h.t, h))
+            })
+            .is_some()
+        })
+        .count()
+}
+
+fn brute_force(triangles: &[[Vector3D; 3]], rays: &[Ray]) -> usize {
+    rays.iter()
+        .filter(|ray| {
+            let mut t_max = f64::INFINITY;
+            let mut hit = false;
+            for [a, b, c] in triangles {
+                if let Some(h) = triangle(ray, *a, *b, *c, t_max) {
+                    t_max = h.t;
+                    hit = true;
+                }
+            }
+            hit
+        })
+        .count()
+}
+
+fn bvh(c: &mut Criterion) {
+    let mut rng = Rng::with_seed(0);
+    let small = triangles(1_000, &mut rng);
+    let large = triangles(100_000, &mut rng);
+    let rays = rays(1_000, &mut rng);
+    let small_bvh = Bvh::from_triangles(&small);
+    let large_bvh = Bvh::from_triangles(&large);
+
+    let mut group = c.benchmark_group("bvh");
+    group.sample_size(20);
+    group.bench_function("build-100k", |b| {
+        b.iter(|| Bvh::from_triangles(black_box(&large)))
+    });
+    group.bench_function("brute-force-1k", |b| {
+        b.iter(|| brute_force(black_box(&small), &rays))
+    });
+    group.bench_function("closest-hit-1k", |b| {
+        b.iter(|| closest_hits(&small_bvh, black_box(&small), &rays))
+    });
+    group.bench_function("closest-hit-100k", |b| {
+        b.iter(|| closest_hits(&large_bvh, black_box(&large), &rays))
+    });
+    group.bench_function("any-hit-100k", |b| {
+        b.iter(|| {
+            rays.iter()
+                .filter(|ray| {
+                    large_bvh.any_hit(ray, f64::INFINITY, |i, t_max| {
+                        let [a, b, c] = large[i];
+                        triangle(ray, a, b, c, t_max).is_some()
+                    })
+                })
+                .count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bvh);
+criterion_main!(benches);
@@ -0,0 +1,148 @@
+use crate::geometry::Ray;
+use lesson4_hemispheres::Vector3D;
+
+/// An axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3D,
+    pub max: Vector3D,
+}
+
+impl Default for Aabb {
+    /// An empty box, which contains nothing
+    fn default() -> Self {
+        Self {
+            min: Vector3D::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vector3D::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+}
+
+impl Aabb {
+    /// The smallest box containing all `points`
+    pub fn from_points(points: &[Vector3D]) -> Self {
+        points.iter().fold(Self::default(), |b, p| b.grow(*p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// This box, extended to contain `point`
+    pub fn grow(&self, point: Vector3D) -> Self {
+        Self {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
+    }
+
+    /// The smallest box containing both `self` and `other`
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn centre(&self) -> Vector3D {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.;
+        }
+        let d = self.max - self.min;
+        2. * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// The axis along which the box is the longest
+    pub fn longest_axis(&self) -> usize {
+        let d = self.max - self.min;
+        if d.x >= d.y && d.x >= d.z {
+            0
+        } else if d.y >= d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// The range of distances `(t_enter, t_exit)` in which `ray` is within
+    /// the box, clipped to `[0, t_max]`
+    pub fn intersect(&self, ray: &Ray, t_max: f64) -> Option<(f64, f64)> {
+        let inv_direction = Vector3D::new(
+            1. / ray.direction.x,
+            1. / ray.direction.y,
+            1. / ray.direction.z,
+        );
+        self.intersect_inv(ray.origin, inv_direction, t_max)
+    }
+
+    /// Same as [`Aabb::intersect`], with the inverse of the direction
+    /// precomputed (e.g., when traversing a BVH)
+    pub(crate) fn intersect_inv(
+        &self,
+        origin: Vector3D,
+        inv_direction: Vector3D,
+        t_max: f64,
+    ) -> Option<(f64, f64)> {
+        let mut t0 = 0.0f64;
+        let mut t1 = t_max;
+        for axis in 0..3 {
+            if inv_direction[axis].is_infinite() {
+                // Parallel to the slab
+                if origin[axis] < self.min[axis] || origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let near = (self.min[axis] - origin[axis]) * inv_direction[axis];
+            let far = (self.max[axis] - origin[axis]) * inv_direction[axis];
+            let (near, far) = if near <= far {
+                (near, far)
+            } else {
+                (far, near)
+            };
+            // Make up for the rounding errors of the slabs (Ize 2013)
+            t0 = t0.max(near);
+            t1 = t1.min(far * (1. + 4. * f64::EPSILON));
+            if t0 > t1 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boxes() {
+        let v = Vector3D::new;
+        let b = Aabb::from_points(&[v(0., 0., 0.), v(1., 2., 3.), v(-1., 1., 1.)]);
+        assert_eq!(b.min, v(-1., 0., 0.));
+        assert_eq!(b.max, v(1., 2., 3.));
+        assert_eq!(b.longest_axis(), 2);
+        assert_eq!(b.surface_area(), 2. * (4. + 6. + 6.));
+        assert!(Aabb::default().is_empty());
+        assert_eq!(Aabb::default().surface_area(), 0.);
+        assert_eq!(Aabb::default().union(&b), b);
+
+        let ray = Ray::new(v(0., 1., -2.), v(0., 0., 1.));
+        let (t0, t1) = b.intersect(&ray, f64::INFINITY).unwrap();
+        assert!((t0 - 2.).abs() < 1e-12 && (t1 - 5.).abs() < 1e-9);
+        assert!(b.intersect(&ray, 1.).is_none());
+        // From the inside
+        let ray = Ray::new(v(0., 1., 1.), v(1., 0., 0.));
+        let (t0, t1) = b.intersect(&ray, f64::INFINITY).unwrap();
+        assert!(t0 == 0. && (t1 - 1.).abs() < 1e-9);
+        // Along a face
+        let ray = Ray::new(v(0., 0., -1.), v(0., 0., 1.));
+        assert!(b.intersect(&ray, f64::INFINITY).is_some());
+        let ray = Ray::new(v(0., 3., -1.), v(0., 0., 1.));
+        assert!(b.intersect(&ray, f64::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,435 @@
+//! A bounding volume hierarchy, built with the surface area heuristic
+//! (SAH), for finding which primitives a ray hits without testing
+//! all of them.
+//!
+//! The hierarchy only knows the bounds of the primitives, so queries
+//! take a closure that intersects primitive `i` given the current `t_max`.
+
+use crate::aabb::Aabb;
+use crate::geometry::Ray;
+use lesson4_hemispheres::Vector3D;
+
+/// The number of buckets in which the centroids are binned
+/// when looking for the best split
+const BINS: usize = 12;
+
+/// Nodes with fewer primitives than this become leaves when
+/// splitting them is not worth it
+const MAX_LEAF_SIZE: usize = 4;
+
+/// The cost of traversing a node, relative to intersecting a primitive
+const TRAVERSAL_COST: f64 = 1.;
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    bounds: Aabb,
+    /// For leaves, the position of their first primitive in `indices`.
+    /// For interior nodes, the index of the second child (the first
+    /// one is right after its parent).
+    offset: usize,
+    /// The number of primitives, which is 0 for interior nodes
+    count: usize,
+    /// The axis in which interior nodes were split
+    axis: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    /// The primitives, sorted so that each leaf is a contiguous range
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    /// Builds a hierarchy over primitives with the given `bounds`
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            indices: (0..bounds.len()).collect(),
+        };
+        if !bounds.is_empty() {
+            let centroids: Vec<Vector3D> = bounds.iter().map(|b| b.centre()).collect();
+            bvh.build(0, bounds.len(), bounds, &centroids);
+        }
+        bvh
+    }
+
+    /// Builds a hierarchy over triangles
+    pub fn from_triangles(triangles: &[[Vector3D; 3]]) -> Self {
+        let bounds: Vec<Aabb> = triangles.iter().map(|t| Aabb::from_points(t)).collect();
+        Self::new(&bounds)
+    }
+
+    /// The number of primitives
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// The number of nodes in the hierarchy
+    pub fn n_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The bounds of all the primitives
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map(|n| n.bounds).unwrap_or_default()
+    }
+
+    /// Builds the node for the primitives in `indices[start..end]`,
+    /// returning its index
+    fn build(
+        &mut self,
+        start: usize,
+        end: usize,
+        bounds: &[Aabb],
+        centroids: &[Vector3D],
+    ) -> usize {
+        let range = &self.indices[start..end];
+        let node_bounds = range
+            .iter()
+            .fold(Aabb::default(), |b, i| b.union(&bounds[*i]));
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            bounds: node_bounds,
+            offset: start,
+            count: end - start,
+            axis: 0,
+        });
+        let count = end - start;
+        if count == 1 {
+            return index;
+        }
+
+        let centroid_bounds = range
+            .iter()
+            .fold(Aabb::default(), |b, i| b.grow(centroids[*i]));
+        let axis = centroid_bounds.longest_axis();
+        let min = centroid_bounds.min[axis];
+        let extent = centroid_bounds.max[axis] - min;
+        let mid = if extent <= 0. {
+            // All centroids are on the same spot, so they cannot be told
+            // apart, and are split in halves if there are too many
+            if count <= MAX_LEAF_SIZE {
+                return index;
+            }
+            start + count / 2
+        } else {
+            let bin = |i: usize| {
+                (((centroids[i][axis] - min) / extent * BINS as f64) as usize).min(BINS - 1)
+            };
+
+            let mut bins = [(Aabb::default(), 0usize); BINS];
+            for i in range.iter() {
+                let b = &mut bins[bin(*i)];
+                b.0 = b.0.union(&bounds[*i]);
+                b.1 += 1;
+            }
+            // The SAH cost of splitting after each bin, relative to
+            // intersecting one primitive
+            let mut costs = [0.0; BINS - 1];
+            let (mut left, mut n_left) = (Aabb::default(), 0);
+            for (split, cost) in costs.iter_mut().enumerate() {
+                left = left.union(&bins[split].0);
+                n_left += bins[split].1;
+                *cost = left.surface_area() * n_left as f64;
+            }
+            let (mut right, mut n_right) = (Aabb::default(), 0);
+            for split in (0..BINS - 1).rev() {
+                right = right.union(&bins[split + 1].0);
+                n_right += bins[split + 1].1;
+                costs[split] += right.surface_area() * n_right as f64;
+            }
+            let area = node_bounds.surface_area();
+            let (best, best_cost) = costs
+                .iter()
+                .map(|c| TRAVERSAL_COST + c / area)
+                .enumerate()
+                .fold(
+                    (0, f64::INFINITY),
+                    |best, (i, c)| if c < best.1 { (i, c) } else { best },
+                );
+            if count <= MAX_LEAF_SIZE && best_cost >= count as f64 {
+                return index;
+            }
+
+            // Partition the primitives
+            let range = &mut self.indices[start..end];
+            let mut mid = 0;
+            for i in 0..range.len() {
+                if bin(range[i]) <= best {
+                    range.swap(i, mid);
+                    mid += 1;
+                }
+            }
+            if mid == 0 || mid == count {
+                start + count / 2
+            } else {
+                start + mid
+            }
+        };
+
+        self.nodes[index].count = 0;
+        self.nodes[index].axis = axis;
+        self.build(start, mid, bounds, centroids);
+        let second = self.build(mid, end, bounds, centroids);
+        self.nodes[index].offset = second;
+        index
+    }
+
+    /// Visits the leaves that `ray` crosses before `t_max`, roughly from
+    /// front to back, calling `visit` with each of their primitives and
+    /// the current `t_max`. `visit` returns the new `t_max`, and whether
+    /// to stop.
+    fn traverse<F>(&self, ray: &Ray, t_max: f64, mut visit: F)
+    where
+        F: FnMut(usize, f64) -> (f64, bool),
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let inv_direction = Vector3D::new(
+            1. / ray.direction.x,
+            1. / ray.direction.y,
+            1. / ray.direction.z,
+        );
+        let mut t_max = t_max;
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if node
+                .bounds
+                .intersect_inv(ray.origin, inv_direction, t_max)
+                .is_none()
+            {
+                continue;
+            }
+            if node.count > 0 {
+                for primitive in &self.indices[node.offset..node.offset + node.count] {
+                    let (t, stop) = visit(*primitive, t_max);
+                    if stop {
+                        return;
+                    }
+                    t_max = t;
+                }
+            } else if inv_direction[node.axis] < 0. {
+                // The second child is closer
+                stack.push(i + 1);
+                stack.push(node.offset);
+            } else {
+                stack.push(node.offset);
+                stack.push(i + 1);
+            }
+        }
+    }
+
+    /// The closest primitive hit by `ray` before `t_max`, where
+    /// `intersect(i, t_max)` returns the distance to the hit with primitive
+    /// `i` (if it is closer than `t_max`) and any data about it
+    pub fn closest_hit<H, F>(&self, ray: &Ray, t_max: f64, mut intersect: F) -> Option<(usize, H)>
+    where
+        F: FnMut(usize, f64) -> Option<(f64, H)>,
+    {
+        let mut closest = None;
+        self.traverse(ray, t_max, |i, t_max| match intersect(i, t_max) {
+            Some((t, hit)) => {
+                closest = Some((i, hit));
+                (t, false)
+            }
+            None => (t_max, false),
+        });
+        closest
+    }
+
+    /// Whether `ray` hits any primitive before `t_max`, where
+    /// `intersect(i, t_max)` tells whether it hits primitive `i`
+    pub fn any_hit<F>(&self, ray: &Ray, t_max: f64, mut intersect: F) -> bool
+    where
+        F: FnMut(usize, f64) -> bool,
+    {
+        let mut hit = false;
+        self.traverse(ray, t_max, |i, t_max| {
+            hit = intersect(i, t_max);
+            (t_max, hit)
+        });
+        hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intersections::{sphere, triangle};
+    use lesson4_hemispheres::shapes::sample_uniform_sphere;
+    use rand::Rng;
+
+    fn random_point(rng: &mut Rng) -> Vector3D {
+        Vector3D::new(rng.next_float(), rng.next_float(), rng.next_float()) * 10.
+    }
+
+    fn random_triangles(n: usize, rng: &mut Rng) -> Vec<[Vector3D; 3]> {
+        (0..n)
+            .map(|_| {
+                let a = random_point(rng);
+                let size = 0.1 + 2. * rng.next_float();
+                let mut vertex =
+                    || a + sample_uniform_sphere((rng.next_float(), rng.next_float())) * size;
+                [a, vertex(), vertex()]
+            })
+            .collect()
+    }
+
+    fn random_rays(n: usize, rng: &mut Rng) -> Vec<Ray> {
+        (0..n)
+            .map(|_| {
+                let direction = sample_uniform_sphere((rng.next_float(), rng.next_float()));
+                // Some start outside of the bounds, and look inwards
+                let origin = random_point(rng) * 1.4 - Vector3D::new(2., 2., 2.);
+                Ray::new(origin, direction)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn structure() {
+        let mut rng = Rng::with_seed(0);
+        let triangles = random_triangles(1000, &mut rng);
+        let bvh = Bvh::from_triangles(&triangles);
+        assert_eq!(bvh.len(), 1000);
+
+        // Every primitive is in exactly one leaf, within its bounds
+        let mut seen = vec![0; triangles.len()];
+        for node in bvh.nodes.iter().filter(|n| n.count > 0) {
+            assert!(node.count <= MAX_LEAF_SIZE);
+            for i in &bvh.indices[node.offset..node.offset + node.count] {
+                seen[*i] += 1;
+                let b = Aabb::from_points(&triangles[*i]);
+                assert_eq!(node.bounds.union(&b), node.bounds);
+            }
+        }
+        assert!(seen.iter().all(|s| *s == 1));
+        // ... and interior nodes contain their children
+        for (i, node) in bvh.nodes.iter().enumerate().filter(|(_, n)| n.count == 0) {
+            for child in [i + 1, node.offset] {
+                let b = bvh.nodes[child].bounds;
+                assert_eq!(node.bounds.union(&b), node.bounds);
+            }
+        }
+
+        assert!(Bvh::new(&[]).is_empty());
+        let ray = Ray::new(Vector3D::default(), Vector3D::new(1., 0., 0.));
+        assert!(Bvh::new(&[])
+            .closest_hit(&ray, f64::INFINITY, |_, _| Some((0., ())))
+            .is_none());
+    }
+
+    #[test]
+    fn coincident_centroids() {
+        // Scaled copies of a triangle, all with the same centroid
+        let [a, b, c] = [
+            Vector3D::new(1., 0., 0.),
+            Vector3D::new(0., 1., 0.),
+            Vector3D::new(0., 0., 1.),
+        ];
+        let centroid = (a + b + c) / 3.;
+        let triangles: Vec<[Vector3D; 3]> = (1..=50)
+            .map(|i| {
+                let scale = i as f64 / 50.;
+                [a, b, c].map(|v| centroid + (v - centroid) * scale)
+            })
+            .collect();
+        let bvh = Bvh::from_triangles(&triangles);
+
+        let mut seen = vec![0; triangles.len()];
+        for node in bvh.nodes.iter().filter(|n| n.count > 0) {
+            assert!(
+                node.count <= MAX_LEAF_SIZE,
+                "expecting at most {} primitives per leaf... found {}",
+                MAX_LEAF_SIZE,
+                node.count
+            );
+            for i in &bvh.indices[node.offset..node.offset + node.count] {
+                seen[*i] += 1;
+            }
+        }
+        assert!(seen.iter().all(|s| *s == 1));
+    }
+
+    #[test]
+    fn triangles_match_brute_force() {
+        let mut rng = Rng::with_seed(1);
+        for n in [1, 7, 100, 2000] {
+            let triangles = random_triangles(n, &mut rng);
+            let bvh = Bvh::from_triangles(&triangles);
+            let mut n_hits = 0;
+            for ray in random_rays(1000, &mut rng) {
+                let hit = |i: usize, t_max: f64| {
+                    let [a, b, c] = triangles[i];
+                    triangle(&ray, a, b, c, t_max)
+                };
+                let found = bvh.closest_hit(&ray, f64::INFINITY, |i, t_max| {
+                    hit(i, t_max).map(|h| (h.t, h))
+                });
+                let mut expected = None;
+                let mut t_max = f64::INFINITY;
+                for i in 0..triangles.len() {
+                    if let Some(h) = hit(i, t_max) {
+                        t_max = h.t;
+                        expected = Some((i, h));
+                    }
+                }
+                match (found, expected) {
+                    (Some((i, a)), Some((j, b))) => {
+                        assert_eq!(i, j);
+                        assert_eq!(a.t, b.t);
+                        n_hits += 1;
+                    }
+                    (None, None) => {}
+                    _ => panic!("expecting {:?}... found {:?}", expected, found),
+                }
+                assert_eq!(
+                    bvh.any_hit(&ray, f64::INFINITY, |i, t_max| hit(i, t_max).is_some()),
+                    expected.is_some()
+                );
+                if let Some((_, h)) = expected {
+                    assert!(!bvh.any_hit(&ray, h.t, |i, t_max| hit(i, t_max).is_some()));
+                }
+            }
+            // The rays are not all missing
+            assert!(n < 100 || n_hits > 50, "{} hits", n_hits);
+        }
+    }
+
+    #[test]
+    fn spheres_match_brute_force() {
+        let mut rng = Rng::with_seed(2);
+        let spheres: Vec<(Vector3D, f64)> = (0..500)
+            .map(|_| (random_point(&mut rng), 0.05 + 0.5 * rng.next_float()))
+            .collect();
+        let bounds: Vec<Aabb> = spheres
+            .iter()
+            .map(|(c, r)| Aabb {
+                min: *c - Vector3D::new(*r, *r, *r),
+                max: *c + Vector3D::new(*r, *r, *r),
+            })
+            .collect();
+        let bvh = Bvh::new(&bounds);
+        for ray in random_rays(1000, &mut rng) {
+            let found = bvh.closest_hit(&ray, f64::INFINITY, |i, t_max| {
+                sphere(&ray, spheres[i].0, spheres[i].1, t_max).map(|t| (t, t))
+            });
+            let expected = spheres
+                .iter()
+                .enumerate()
+                .filter_map(|(i, (c, r))| sphere(&ray, *c, *r, f64::INFINITY).map(|t| (i, t)))
+                .fold(None, |best: Option<(usize, f64)>, (i, t)| match best {
+                    Some((_, best_t)) if best_t <= t => best,
+                    _ => Some((i, t)),
+                });
+            assert_eq!(found, expected);
+        }
+    }
+}
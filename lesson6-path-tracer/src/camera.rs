@@ -0,0 +1,78 @@
+use crate::geometry::Ray;
+use lesson4_hemispheres::Vector3D;
+
+/// A pinhole camera
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    origin: Vector3D,
+    forward: Vector3D,
+    right: Vector3D,
+    up: Vector3D,
+    /// The tangent of half the vertical field of view
+    tan_half_fov: f64,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Camera {
+    /// A camera at `from` looking towards `to`, with a vertical
+    /// field of view in degrees, producing `width` by `height` pixels
+    pub fn look_at(
+        from: Vector3D,
+        to: Vector3D,
+        up: Vector3D,
+        vertical_fov: f64,
+        width: usize,
+        height: usize,
+    ) -> Self {
+        let forward = (to - from).normalize();
+        let right = forward.cross(up).normalize();
+        assert!(
+            right.length_squared() > 0.5,
+            "expecting the up vector not to be parallel to the view direction"
+        );
+        Self {
+            origin: from,
+            forward,
+            right,
+            up: right.cross(forward),
+            tan_half_fov: (vertical_fov / 2.).to_radians().tan(),
+            width,
+            height,
+        }
+    }
+
+    /// The ray through pixel `(x, y)` (with `y = 0` at the top), where
+    /// `u` is the position within the pixel
+    pub fn ray(&self, x: usize, y: usize, u: (f64, f64)) -> Ray {
+        let aspect = self.width as f64 / self.height as f64;
+        let sx = (2. * (x as f64 + u.0) / self.width as f64 - 1.) * self.tan_half_fov * aspect;
+        let sy = (1. - 2. * (y as f64 + u.1) / self.height as f64) * self.tan_half_fov;
+        let direction = (self.forward + self.right * sx + self.up * sy).normalize();
+        Ray::new(self.origin, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rays() {
+        let camera = Camera::look_at(
+            Vector3D::new(0., -3., 0.),
+            Vector3D::new(0., 0., 0.),
+            Vector3D::new(0., 0., 1.),
+            90.,
+            200,
+            100,
+        );
+        let centre = camera.ray(100, 50, (0., 0.));
+        assert!(centre.direction.approx_eq(Vector3D::new(0., 1., 0.), 1e-12));
+
+        // The top left corner is 45 degrees up, and to the left
+        let corner = camera.ray(0, 0, (0., 0.));
+        let expected = Vector3D::new(-2., 1., 1.).normalize();
+        assert!(corner.direction.approx_eq(expected, 1e-12));
+    }
+}
@@ -0,0 +1,248 @@
+//! The surfaces a scene is made of, and how rays hit them
+
+use crate::aabb::Aabb;
+use crate::intersections;
+use lesson4_hemispheres::shapes::{
+    concentric_sample_disc, sample_uniform_sphere, sample_uniform_triangle,
+};
+use lesson4_hemispheres::{OrthonormalBasis, Vector3D};
+use std::f64::consts::PI;
+
+/// Hits closer than this are ignored, so rays leaving a
+/// surface do not hit it again
+pub const EPSILON: f64 = 1e-7;
+
+/// A half-line, starting at `origin`
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vector3D,
+    /// Normalized
+    pub direction: Vector3D,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3D, direction: Vector3D) -> Self {
+        Self { origin, direction }
+    }
+
+    /// The point at a distance `t` from the origin
+    pub fn at(&self, t: f64) -> Vector3D {
+        self.origin + self.direction * t
+    }
+}
+
+/// Where a ray hits a surface
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    /// The distance along the ray
+    pub t: f64,
+    pub point: Vector3D,
+    /// The normalized geometric normal, pointing to the front of
+    /// the surface (which may be away from the ray)
+    pub normal: Vector3D,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    /// Its front faces outwards
+    Sphere { centre: Vector3D, radius: f64 },
+    /// An infinite plane, whose front faces `normal` (normalized)
+    Plane { point: Vector3D, normal: Vector3D },
+    /// Its front is the side from which the vertices are seen
+    /// counter-clockwise
+    Triangle {
+        a: Vector3D,
+        b: Vector3D,
+        c: Vector3D,
+    },
+    /// A disc of `radius` around `centre`, whose front faces
+    /// `normal` (normalized)
+    Disc {
+        centre: Vector3D,
+        normal: Vector3D,
+        radius: f64,
+    },
+}
+
+impl Shape {
+    /// The closest hit of `ray` within `(EPSILON, t_max)`
+    pub fn intersect(&self, ray: &Ray, t_max: f64) -> Option<Hit> {
+        let t = match *self {
+            Shape::Sphere { centre, radius } => intersections::sphere(ray, centre, radius, t_max)?,
+            Shape::Plane { point, normal } => intersections::plane(ray, point, normal, t_max)?,
+            Shape::Triangle { a, b, c } => intersections::triangle(ray, a, b, c, t_max)?.t,
+            Shape::Disc {
+                centre,
+                normal,
+                radius,
+            } => intersections::disc(ray, centre, normal, radius, t_max)?,
+        };
+        let point = ray.at(t);
+        Some(Hit {
+            t,
+            point,
+            normal: self.normal(point),
+        })
+    }
+
+    /// The smallest box containing the shape, which is
+    /// `None` for unbounded ones
+    pub fn bounds(&self) -> Option<Aabb> {
+        match *self {
+            Shape::Sphere { centre, radius } => {
+                let r = Vector3D::new(radius, radius, radius);
+                Some(Aabb {
+                    min: centre - r,
+                    max: centre + r,
+                })
+            }
+            Shape::Plane { .. } => None,
+            Shape::Triangle { a, b, c } => Some(Aabb::from_points(&[a, b, c])),
+            Shape::Disc {
+                centre,
+                normal,
+                radius,
+            } => {
+                // The extent along each axis is proportional to the sine of
+                // the angle between it and the normal
+                let e = |n: f64| radius * (1. - n * n).max(0.).sqrt();
+                let e = Vector3D::new(e(normal.x), e(normal.y), e(normal.z));
+                Some(Aabb {
+                    min: centre - e,
+                    max: centre + e,
+                })
+            }
+        }
+    }
+
+    /// The normal at a `point` on the surface
+    pub fn normal(&self, point: Vector3D) -> Vector3D {
+        match *self {
+            Shape::Sphere { centre, .. } => (point - centre).normalize(),
+            Shape::Plane { normal, .. } => normal,
+            Shape::Triangle { a, b, c } => (b - a).cross(c - a).normalize(),
+            Shape::Disc { normal, .. } => normal,
+        }
+    }
+
+    /// The surface area, which is infinite for planes
+    pub fn area(&self) -> f64 {
+        match *self {
+            Shape::Sphere { radius, .. } => 4. * PI * radius * radius,
+            Shape::Plane { .. } => f64::INFINITY,
+            Shape::Triangle { a, b, c } => (b - a).cross(c - a).length() / 2.,
+            Shape::Disc { radius, .. } => PI * radius * radius,
+        }
+    }
+
+    /// Samples a point uniformly over the surface (i.e., with a PDF
+    /// of `1/area`), returning it with its normal. Planes cannot
+    /// be sampled.
+    pub fn sample(&self, u: (f64, f64)) -> (Vector3D, Vector3D) {
+        match *self {
+            Shape::Sphere { centre, radius } => {
+                let n = sample_uniform_sphere(u);
+                (centre + n * radius, n)
+            }
+            Shape::Plane { .. } => panic!("infinite planes cannot be sampled"),
+            Shape::Triangle { a, b, c } => {
+                let p = sample_uniform_triangle(u, a, b, c);
+                (p, self.normal(p))
+            }
+            Shape::Disc {
+                centre,
+                normal,
+                radius,
+            } => {
+                let (x, y) = concentric_sample_disc(u);
+                let basis = OrthonormalBasis::new(normal);
+                (
+                    centre + basis.to_world(Vector3D::new(x, y, 0.)) * radius,
+                    normal,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn intersections() {
+        let ray = Ray::new(Vector3D::new(0., 0., -5.), Vector3D::new(0., 0., 1.));
+
+        let sphere = Shape::Sphere {
+            centre: Vector3D::new(0., 0., 0.),
+            radius: 1.,
+        };
+        let hit = sphere.intersect(&ray, f64::INFINITY).unwrap();
+        assert!((hit.t - 4.).abs() < 1e-12);
+        assert!(hit.normal.approx_eq(Vector3D::new(0., 0., -1.), 1e-12));
+        assert!(sphere.intersect(&ray, 3.).is_none());
+        // From the inside
+        let inside = Ray::new(Vector3D::new(0., 0., 0.), Vector3D::new(0., 1., 0.));
+        let hit = sphere.intersect(&inside, f64::INFINITY).unwrap();
+        assert!((hit.t - 1.).abs() < 1e-12);
+
+        let plane = Shape::Plane {
+            point: Vector3D::new(0., 0., 2.),
+            normal: Vector3D::new(0., 0., -1.),
+        };
+        let hit = plane.intersect(&ray, f64::INFINITY).unwrap();
+        assert!((hit.t - 7.).abs() < 1e-12);
+        assert!(plane.intersect(&inside, f64::INFINITY).is_none());
+
+        let triangle = Shape::Triangle {
+            a: Vector3D::new(-1., -1., 1.),
+            b: Vector3D::new(1., -1., 1.),
+            c: Vector3D::new(0., 1., 1.),
+        };
+        let hit = triangle.intersect(&ray, f64::INFINITY).unwrap();
+        assert!((hit.t - 6.).abs() < 1e-12);
+        assert!(hit.normal.approx_eq(Vector3D::new(0., 0., 1.), 1e-12));
+        let miss = Ray::new(Vector3D::new(1., 1., -5.), Vector3D::new(0., 0., 1.));
+        assert!(triangle.intersect(&miss, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn surface_samples() {
+        let mut rng = Rng::with_seed(0);
+        let sphere = Shape::Sphere {
+            centre: Vector3D::new(1., 2., 3.),
+            radius: 2.,
+        };
+        let triangle = Shape::Triangle {
+            a: Vector3D::new(0., 0., 0.),
+            b: Vector3D::new(2., 0., 0.),
+            c: Vector3D::new(0., 3., 0.),
+        };
+        let normal = Vector3D::new(1., -1., 2.).normalize();
+        let disc = Shape::Disc {
+            centre: Vector3D::new(0., 1., 0.),
+            normal,
+            radius: 0.5,
+        };
+        assert!((triangle.area() - 3.).abs() < 1e-12);
+        let bounds = disc.bounds().unwrap();
+        for _ in 0..100 {
+            let u = (rng.next_float(), rng.next_float());
+            let (p, n) = sphere.sample(u);
+            assert!(((p - Vector3D::new(1., 2., 3.)).length() - 2.).abs() < 1e-9);
+            assert!(n.approx_eq(sphere.normal(p), 1e-9));
+
+            let (p, n) = triangle.sample(u);
+            assert!(p.z.abs() < 1e-12 && p.x >= 0. && p.y >= 0.);
+            assert!(p.x / 2. + p.y / 3. <= 1. + 1e-12);
+            assert!(n.approx_eq(Vector3D::new(0., 0., 1.), 1e-12));
+
+            let (p, n) = disc.sample(u);
+            let d = p - Vector3D::new(0., 1., 0.);
+            assert!(d.dot(normal).abs() < 1e-12 && d.length() <= 0.5 + 1e-12);
+            assert_eq!(n, normal);
+            assert_eq!(bounds.grow(p), bounds);
+        }
+    }
+}
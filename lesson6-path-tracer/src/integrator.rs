@@ -0,0 +1,439 @@
+//! A unidirectional path tracer, with next-event estimation
+//! and Russian roulette
+
+use crate::camera::Camera;
+use crate::geometry::{Hit, Ray};
+use crate::scene::{Material, Scene};
+use image::Image;
+use lesson1_sampling::step_pdf::DiscretePdf;
+use lesson4_hemispheres::{OrthonormalBasis, Vector3D};
+use rand::Rng;
+
+/// Rays leaving a surface start this far from it
+const RAY_OFFSET: f64 = 1e-6;
+
+/// The power heuristic (with an exponent of 2), weighting a sample
+/// taken with `pdf_a` against the strategy with `pdf_b`
+fn power_heuristic(pdf_a: f64, pdf_b: f64) -> f64 {
+    let a = pdf_a * pdf_a;
+    let b = pdf_b * pdf_b;
+    if a + b == 0. {
+        0.
+    } else {
+        a / (a + b)
+    }
+}
+
+/// A ray leaving `point` (on a surface with normal `normal`)
+/// towards `direction`
+fn spawn(point: Vector3D, normal: Vector3D, direction: Vector3D) -> Ray {
+    let offset = if direction.dot(normal) > 0. {
+        normal * RAY_OFFSET
+    } else {
+        -normal * RAY_OFFSET
+    };
+    Ray::new(point + offset, direction)
+}
+
+pub struct PathTracer<'a> {
+    scene: &'a Scene,
+    /// The objects that can be sampled as lights
+    lights: Vec<usize>,
+    /// For each object, its position in `lights`
+    light_index: Vec<Option<usize>>,
+    /// Chooses lights proportionally to their power. Each of them takes
+    /// a range of the same width between 0 and 1.
+    light_selection: Option<DiscretePdf>,
+    /// Paths are cut after this number of bounces
+    pub max_depth: usize,
+    /// Paths are terminated by Russian roulette after this number of bounces
+    pub roulette_depth: usize,
+    /// Whether to sample the lights directly (and combine that with
+    /// BSDF sampling through MIS), or to rely on BSDF sampling alone
+    pub next_event: bool,
+}
+
+impl<'a> PathTracer<'a> {
+    pub fn new(scene: &'a Scene) -> Self {
+        let lights: Vec<usize> = scene
+            .emitters()
+            .into_iter()
+            .filter(|i| scene.objects()[*i].shape.area().is_finite())
+            .collect();
+        let mut light_index = vec![None; scene.objects().len()];
+        for (k, i) in lights.iter().enumerate() {
+            light_index[*i] = Some(k);
+        }
+
+        let light_selection = if lights.is_empty() {
+            None
+        } else {
+            let powers: Vec<f64> = lights
+                .iter()
+                .map(|i| scene.material(*i).emission * scene.objects()[*i].shape.area())
+                .collect();
+            let total: f64 = powers.iter().sum();
+            let n = lights.len() as f64;
+            let steps = (1..=lights.len()).map(|k| k as f64 / n).collect();
+            let pdfs = powers.iter().map(|p| n * p / total).collect();
+            Some(DiscretePdf::new(0.0, steps, pdfs))
+        };
+
+        Self {
+            scene,
+            lights,
+            light_index,
+            light_selection,
+            max_depth: 64,
+            roulette_depth: 3,
+            next_event: true,
+        }
+    }
+
+    /// The probability of choosing object `i` when sampling a light
+    fn selection_probability(&self, i: usize) -> f64 {
+        match (self.light_index[i], &self.light_selection) {
+            (Some(k), Some(selection)) => {
+                let n = self.lights.len() as f64;
+                selection.pdf((k as f64 + 0.5) / n) / n
+            }
+            _ => 0.,
+        }
+    }
+
+    /// The PDF (per unit solid angle) with which a light sample taken
+    /// from the origin of `ray` would reach object `i` at `hit`
+    fn light_pdf(&self, i: usize, ray: &Ray, hit: &Hit) -> f64 {
+        let cos = hit.normal.dot(ray.direction).abs();
+        if cos == 0. {
+            return 0.;
+        }
+        let area = self.scene.objects()[i].shape.area();
+        self.selection_probability(i) * hit.t * hit.t / (cos * area)
+    }
+
+    /// Estimates the light reflected at `point` towards `wo` that arrives
+    /// directly from a light, weighted by MIS
+    fn sample_light(
+        &self,
+        point: Vector3D,
+        basis: &OrthonormalBasis,
+        wo: Vector3D,
+        material: &Material,
+        rng: &mut Rng,
+    ) -> f64 {
+        let selection = match &self.light_selection {
+            Some(s) => s,
+            None => return 0.,
+        };
+        let n = self.lights.len();
+        let (x, _) = selection.sample(rng);
+        let light = self.lights[((x * n as f64) as usize).min(n - 1)];
+        let shape = &self.scene.objects()[light].shape;
+        let (y, normal) = shape.sample((rng.next_float(), rng.next_float()));
+
+        let d = y - point;
+        let distance = d.length();
+        if distance == 0. {
+            return 0.;
+        }
+        let wi = d / distance;
+        // Lights only emit from their front
+        let cos_light = -normal.dot(wi);
+        let wi_local = basis.to_local(wi);
+        if cos_light <= 0. || wi_local.z <= 0. {
+            return 0.;
+        }
+        let f = material.bsdf.eval(wo, wi_local);
+        if f == 0. {
+            return 0.;
+        }
+        let ray = spawn(point, basis.w, wi);
+        if self
+            .scene
+            .is_occluded(&ray, distance * (1. - 1e-6) - RAY_OFFSET)
+        {
+            return 0.;
+        }
+
+        let pdf =
+            self.selection_probability(light) * distance * distance / (cos_light * shape.area());
+        let weight = power_heuristic(pdf, material.bsdf.pdf(wo, wi_local));
+        self.scene.material(light).emission * f * wi_local.z * weight / pdf
+    }
+
+    /// Estimates the radiance arriving at the origin of `ray`,
+    /// from its direction
+    pub fn radiance(&self, ray: Ray, rng: &mut Rng) -> f64 {
+        let mut radiance = 0.0;
+        let mut throughput = 1.0;
+        let mut ray = ray;
+        // Whether the last bounce can only be found by BSDF sampling
+        let mut specular = true;
+        let mut bsdf_pdf = 0.0;
+        let mut depth = 0;
+
+        while let Some((i, hit)) = self.scene.intersect(&ray, f64::INFINITY) {
+            let material = self.scene.material(i);
+            let front = hit.normal.dot(ray.direction) < 0.;
+            if material.emission > 0. && front {
+                let weight = if specular || !self.next_event {
+                    1.
+                } else {
+                    power_heuristic(bsdf_pdf, self.light_pdf(i, &ray, &hit))
+                };
+                radiance += throughput * material.emission * weight;
+            }
+            if depth == self.max_depth {
+                break;
+            }
+
+            // Shade on the side the ray arrives from
+            let normal = if front { hit.normal } else { -hit.normal };
+            let basis = OrthonormalBasis::new(normal);
+            let wo = basis.to_local(-ray.direction);
+            if self.next_event {
+                radiance += throughput * self.sample_light(hit.point, &basis, wo, material, rng);
+            }
+
+            let sample = match material
+                .bsdf
+                .sample(wo, (rng.next_float(), rng.next_float()))
+            {
+                Some(s) => s,
+                None => break,
+            };
+            throughput *= sample.f * sample.wi.z / sample.pdf;
+            specular = sample.specular;
+            bsdf_pdf = sample.pdf;
+            ray = spawn(hit.point, normal, basis.to_world(sample.wi));
+            depth += 1;
+
+            if depth >= self.roulette_depth {
+                let survival = throughput.min(0.95);
+                if survival <= 0. || rng.next_float() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+        }
+        radiance
+    }
+
+    /// Renders the scene as seen by `camera`, averaging `spp` samples per
+    /// pixel. Each pixel has its own stream of `seed` (see
+    /// [`Rng::with_stream`]), so the same seed always produces the same
+    /// image.
+    pub fn render(&self, camera: &Camera, spp: usize, seed: u64) -> Image {
+        let mut image = Image::new(camera.width, camera.height);
+        for y in 0..camera.height {
+            for x in 0..camera.width {
+                let pixel = (y * camera.width + x) as u64;
+                let mut rng = Rng::with_stream(seed, pixel);
+                let mut sum = 0.0;
+                for _ in 0..spp {
+                    let ray = camera.ray(x, y, (rng.next_float(), rng.next_float()));
+                    sum += self.radiance(ray, &mut rng);
+                }
+                image.set(x, y, sum / spp as f64);
+            }
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Shape;
+    use lesson4_hemispheres::sample_cosine_weighted_hemisphere_around;
+    use lesson4_hemispheres::shapes::sample_uniform_sphere;
+
+    const N: usize = 20_000;
+
+    /// A closed cube made of `material`, facing inwards
+    fn furnace(material: Material) -> Scene {
+        let mut scene = Scene::new();
+        let m = scene.add_material(material);
+        let v = Vector3D::new;
+        let faces = [
+            [
+                v(-1., -1., -1.),
+                v(1., -1., -1.),
+                v(1., 1., -1.),
+                v(-1., 1., -1.),
+            ],
+            [
+                v(-1., -1., 1.),
+                v(-1., 1., 1.),
+                v(1., 1., 1.),
+                v(1., -1., 1.),
+            ],
+            [
+                v(-1., 1., -1.),
+                v(1., 1., -1.),
+                v(1., 1., 1.),
+                v(-1., 1., 1.),
+            ],
+            [
+                v(-1., -1., -1.),
+                v(-1., -1., 1.),
+                v(1., -1., 1.),
+                v(1., -1., -1.),
+            ],
+            [
+                v(-1., -1., -1.),
+                v(-1., 1., -1.),
+                v(-1., 1., 1.),
+                v(-1., -1., 1.),
+            ],
+            [
+                v(1., -1., -1.),
+                v(1., -1., 1.),
+                v(1., 1., 1.),
+                v(1., 1., -1.),
+            ],
+        ];
+        for [p0, p1, p2, p3] in faces {
+            scene.add_quad(p0, p1, p2, p3, m);
+        }
+        scene
+    }
+
+    /// The average radiance arriving at the centre of the scene
+    fn mean_radiance(tracer: &PathTracer, rng: &mut Rng) -> f64 {
+        let origin = Vector3D::new(0.1, -0.2, 0.05);
+        (0..N)
+            .map(|_| {
+                let direction = sample_uniform_sphere((rng.next_float(), rng.next_float()));
+                tracer.radiance(Ray::new(origin, direction), rng)
+            })
+            .sum::<f64>()
+            / N as f64
+    }
+
+    #[test]
+    fn white_furnace() {
+        // Inside a closed emitter with a reflectance of rho, the radiance
+        // is the same everywhere: L = Le + rho * L
+        let mut rng = Rng::with_seed(0);
+        let (emission, reflectance) = (1., 0.5);
+        let expected = emission / (1. - reflectance);
+        let scenes = [
+            furnace(Material::emitter(emission, reflectance)),
+            furnace(Material {
+                emission,
+                ..Material::mirror(reflectance)
+            }),
+        ];
+        for scene in scenes.iter() {
+            let mut tracer = PathTracer::new(scene);
+            for next_event in [true, false] {
+                tracer.next_event = next_event;
+                let found = mean_radiance(&tracer, &mut rng);
+                assert!(
+                    (found - expected).abs() / expected < 2e-2,
+                    "expecting {:.6}... found {:.6} (next event = {})",
+                    expected,
+                    found,
+                    next_event
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cornell_box_energy() {
+        // In a closed box where every surface reflects rho, all the
+        // emitted power (PI * Le * A_light) is eventually absorbed, so the
+        // irradiance added over all surfaces is PI * Le * A_light / (1 - rho).
+        // The average incoming radiance over the surfaces (i.e., with
+        // cosine-weighted directions) is therefore Le * A_light / ((1 - rho) * A)
+        let mut rng = Rng::with_seed(1);
+        let (reflectance, emission, light_half_size) = (0.6, 5., 0.25);
+        let scene = Scene::cornell_box(reflectance, emission, light_half_size);
+        let areas: Vec<f64> = scene.objects().iter().map(|o| o.shape.area()).collect();
+        let total_area: f64 = areas.iter().sum();
+        let light_area = 4. * light_half_size * light_half_size;
+        let expected = emission * light_area / ((1. - reflectance) * total_area);
+
+        let mut tracer = PathTracer::new(&scene);
+        for next_event in [true, false] {
+            tracer.next_event = next_event;
+            let mut sum = 0.0;
+            for _ in 0..N {
+                // Pick a surface proportionally to its area
+                let mut target = rng.next_float() * total_area;
+                let mut i = 0;
+                while i < areas.len() - 1 && target >= areas[i] {
+                    target -= areas[i];
+                    i += 1;
+                }
+                let shape = &scene.objects()[i].shape;
+                let (point, normal) = shape.sample((rng.next_float(), rng.next_float()));
+                let direction = sample_cosine_weighted_hemisphere_around(
+                    (rng.next_float(), rng.next_float()),
+                    normal,
+                );
+                sum += tracer.radiance(spawn(point, normal, direction), &mut rng);
+            }
+            let found = sum / N as f64;
+            assert!(
+                (found - expected).abs() / expected < 3e-2,
+                "expecting {:.6}... found {:.6} (next event = {})",
+                expected,
+                found,
+                next_event
+            );
+        }
+    }
+
+    #[test]
+    fn glossy_next_event_is_unbiased() {
+        // Sampling the lights and combining with MIS changes
+        // the variance, not the result
+        let mut scene = Scene::cornell_box(0.5, 5., 0.4);
+        let glossy = scene.add_material(Material::glossy(0.2, 0.6, 30.));
+        scene.add_object(
+            Shape::Sphere {
+                centre: Vector3D::new(-0.4, -0.3, -0.6),
+                radius: 0.35,
+            },
+            glossy,
+        );
+        let mut tracer = PathTracer::new(&scene);
+
+        let mut rng = Rng::with_seed(2);
+        tracer.next_event = true;
+        let with_nee = mean_radiance(&tracer, &mut rng);
+        tracer.next_event = false;
+        let without_nee = mean_radiance(&tracer, &mut rng);
+        assert!(
+            (with_nee - without_nee).abs() / with_nee < 4e-2,
+            "{:.6} vs {:.6}",
+            with_nee,
+            without_nee
+        );
+    }
+
+    #[test]
+    fn deterministic_render() {
+        let scene = Scene::cornell_box(0.5, 5., 0.25);
+        let camera = Camera::look_at(
+            Vector3D::new(0., -0.95, 0.),
+            Vector3D::new(0., 0., 0.),
+            Vector3D::new(0., 0., 1.),
+            70.,
+            12,
+            8,
+        );
+        let tracer = PathTracer::new(&scene);
+        let a = tracer.render(&camera, 4, 7);
+        let b = tracer.render(&camera, 4, 7);
+        let c = tracer.render(&camera, 4, 8);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(a.pixels().iter().all(|v| v.is_finite() && *v >= 0.));
+        assert!(a.mean() > 0.);
+    }
+}
@@ -0,0 +1,205 @@
+//! Ray intersection routines. They all ignore hits outside of
+//! `(EPSILON, t_max)`, and expect the direction of the ray
+//! to be normalized.
+
+use crate::geometry::{Ray, EPSILON};
+use lesson4_hemispheres::Vector3D;
+
+/// Where a ray hits a triangle `a, b, c`
+#[derive(Debug, Clone, Copy)]
+pub struct TriangleHit {
+    pub t: f64,
+    /// The weights of `a`, `b` and `c` at the hit point
+    pub barycentrics: (f64, f64, f64),
+}
+
+/// The distance to the closest hit with a sphere
+pub fn sphere(ray: &Ray, centre: Vector3D, radius: f64, t_max: f64) -> Option<f64> {
+    let oc = ray.origin - centre;
+    let b = oc.dot(ray.direction);
+    // The squared distance from the centre to the line, computed as in
+    // "Precision Improvements for Ray/Sphere Intersection" (Haines et al. 2019)
+    let closest = oc - ray.direction * b;
+    let discriminant = radius * radius - closest.length_squared();
+    if discriminant < 0. {
+        return None;
+    }
+    let c = oc.length_squared() - radius * radius;
+    // Avoid the cancellation in -b + sqrt when both are similar
+    let q = -b - discriminant.sqrt().copysign(b);
+    let (t0, t1) = if q == 0. { (0., 0.) } else { (c / q, q) };
+    let (t0, t1) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+    [t0, t1].into_iter().find(|t| *t > EPSILON && *t < t_max)
+}
+
+/// The hit with a triangle, using the watertight algorithm from Woop et
+/// al. (2013) "Watertight Ray/Triangle Intersection", so rays never slip
+/// through the edges shared by two triangles. Both sides are hit.
+pub fn triangle(
+    ray: &Ray,
+    a: Vector3D,
+    b: Vector3D,
+    c: Vector3D,
+    t_max: f64,
+) -> Option<TriangleHit> {
+    let d = ray.direction;
+    // Permute the axes so that the largest component of the direction is z
+    let kz = if d.x.abs() > d.y.abs() {
+        if d.x.abs() > d.z.abs() {
+            0
+        } else {
+            2
+        }
+    } else if d.y.abs() > d.z.abs() {
+        1
+    } else {
+        2
+    };
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if d[kz] < 0. {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    // Shear, so the ray points towards +z
+    let sx = d[kx] / d[kz];
+    let sy = d[ky] / d[kz];
+    let sz = 1. / d[kz];
+    let a = a - ray.origin;
+    let b = b - ray.origin;
+    let c = c - ray.origin;
+    let (ax, ay) = (a[kx] - sx * a[kz], a[ky] - sy * a[kz]);
+    let (bx, by) = (b[kx] - sx * b[kz], b[ky] - sy * b[kz]);
+    let (cx, cy) = (c[kx] - sx * c[kz], c[ky] - sy * c[kz]);
+
+    // Scaled barycentrics, as edge functions
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+    if (u < 0. || v < 0. || w < 0.) && (u > 0. || v > 0. || w > 0.) {
+        return None;
+    }
+    let det = u + v + w;
+    if det == 0. {
+        return None;
+    }
+
+    let t = (u * sz * a[kz] + v * sz * b[kz] + w * sz * c[kz]) / det;
+    if t <= EPSILON || t >= t_max {
+        return None;
+    }
+    Some(TriangleHit {
+        t,
+        barycentrics: (u / det, v / det, w / det),
+    })
+}
+
+/// The distance to the hit with a disc of `radius` around `centre`,
+/// lying on the plane with normal `normal`. Both sides are hit.
+pub fn disc(ray: &Ray, centre: Vector3D, normal: Vector3D, radius: f64, t_max: f64) -> Option<f64> {
+    let t = plane(ray, centre, normal, t_max)?;
+    if (ray.at(t) - centre).length_squared() > radius * radius {
+        None
+    } else {
+        Some(t)
+    }
+}
+
+/// The distance to the hit with the plane through `point` with
+/// normal `normal`. Both sides are hit.
+pub fn plane(ray: &Ray, point: Vector3D, normal: Vector3D, t_max: f64) -> Option<f64> {
+    let cos = normal.dot(ray.direction);
+    if cos == 0. {
+        return None;
+    }
+    let t = (point - ray.origin).dot(normal) / cos;
+    if t <= EPSILON || t >= t_max {
+        None
+    } else {
+        Some(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lesson4_hemispheres::shapes::sample_uniform_sphere;
+    use rand::Rng;
+
+    fn random_direction(rng: &mut Rng) -> Vector3D {
+        sample_uniform_sphere((rng.next_float(), rng.next_float()))
+    }
+
+    #[test]
+    fn spheres() {
+        let centre = Vector3D::new(1., 2., 3.);
+        let ray = Ray::new(Vector3D::new(1., 2., -7.), Vector3D::new(0., 0., 1.));
+        assert!((sphere(&ray, centre, 2., f64::INFINITY).unwrap() - 8.).abs() < 1e-12);
+        assert!(sphere(&ray, centre, 2., 7.).is_none());
+        // From the inside, the far side is hit
+        let inside = Ray::new(centre, Vector3D::new(0., 1., 0.));
+        assert!((sphere(&inside, centre, 2., f64::INFINITY).unwrap() - 2.).abs() < 1e-12);
+
+        // Far away rays still land on the surface
+        let mut rng = Rng::with_seed(0);
+        for _ in 0..1000 {
+            let d = random_direction(&mut rng);
+            let origin = centre - d * 1e5 + random_direction(&mut rng) * 0.5;
+            let ray = Ray::new(origin, d);
+            let t = sphere(&ray, centre, 2., f64::INFINITY).unwrap();
+            assert!(((ray.at(t) - centre).length() - 2.).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn watertight_triangles() {
+        // Rays through the edges and the vertex shared by a fan of
+        // triangles always hit one of them
+        let v = Vector3D::new;
+        let centre = v(0.5, 0.5, 0.);
+        let n = 7;
+        let corner = |i: usize| {
+            let angle = 2. * std::f64::consts::PI * (i % n) as f64 / n as f64;
+            centre + v(angle.cos(), angle.sin(), 0.)
+        };
+        let mut rng = Rng::with_seed(1);
+        for _ in 0..10_000 {
+            let spoke = (rng.next_float() * n as f64) as usize;
+            let s = if rng.next_float() < 0.2 {
+                0.
+            } else {
+                0.9 * rng.next_float()
+            };
+            let target = centre + (corner(spoke) - centre) * s;
+            let d = random_direction(&mut rng);
+            let ray = Ray::new(target - d * 3., d);
+            let hit = (0..n)
+                .find_map(|i| triangle(&ray, centre, corner(i), corner(i + 1), f64::INFINITY));
+            let hit = hit.expect("a ray slipped through a shared edge");
+            assert!((hit.t - 3.).abs() < 1e-9);
+        }
+
+        let (p0, p1, p2, p3) = (v(0., 0., 0.), v(1., 0., 0.), v(1., 1., 0.), v(0., 1., 0.));
+        let ray = Ray::new(v(0.25, 0.5, 1.), v(0., 0., -1.));
+        let hit = triangle(&ray, p0, p2, p3, f64::INFINITY).unwrap();
+        assert!((hit.t - 1.).abs() < 1e-12);
+        let (b0, b1, b2) = hit.barycentrics;
+        assert!((b0 + b1 + b2 - 1.).abs() < 1e-12);
+        let point = p0 * b0 + p2 * b1 + p3 * b2;
+        assert!(point.approx_eq(v(0.25, 0.5, 0.), 1e-12));
+        assert!(triangle(&ray, p0, p1, p2, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn discs() {
+        let centre = Vector3D::new(0., 0., 1.);
+        let normal = Vector3D::new(0., 0., -1.);
+        let up = Vector3D::new(0., 0., 1.);
+        let hit = Ray::new(Vector3D::new(0.5, 0.5, 0.), up);
+        assert!((disc(&hit, centre, normal, 1., f64::INFINITY).unwrap() - 1.).abs() < 1e-12);
+        let miss = Ray::new(Vector3D::new(0.8, 0.8, 0.), up);
+        assert!(disc(&miss, centre, normal, 1., f64::INFINITY).is_none());
+        let parallel = Ray::new(Vector3D::new(0., 0., 0.), Vector3D::new(1., 0., 0.));
+        assert!(plane(&parallel, centre, normal, f64::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,22 @@
+//! A small Monte Carlo path tracer, putting together the sampling
+//! techniques from the previous lessons: light selection with a
+//! `DiscretePdf`, hemisphere and BSDF sampling, multiple importance
+//! sampling and Russian roulette.
+//!
+//! Radiance is monochrome, as the BSDFs in lesson 4 are.
+
+pub mod aabb;
+pub mod bvh;
+pub mod camera;
+pub mod geometry;
+pub mod integrator;
+pub mod intersections;
+pub mod mesh;
+pub mod obj;
+pub mod scene;
+
+pub use crate::camera::Camera;
+pub use crate::integrator::PathTracer;
+pub use crate::mesh::TriangleMesh;
+pub use crate::scene::{Material, Scene};
+pub use image::Image;
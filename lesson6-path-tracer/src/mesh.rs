@@ -0,0 +1,310 @@
+//! Triangle meshes, e.g., loaded from OBJ files (see [`crate::obj`])
+
+use crate::aabb::Aabb;
+use lesson4_hemispheres::shapes::{sample_uniform_barycentric, uniform_barycentric_inverse};
+use lesson4_hemispheres::Vector3D;
+
+/// A triangle in a [`TriangleMesh`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Triangle {
+    /// Indices into the positions of the mesh
+    pub vertices: [usize; 3],
+    /// Indices into the normals of the mesh, if they were given
+    pub normals: Option<[usize; 3]>,
+    /// Index into the group names of the mesh
+    pub group: Option<usize>,
+    /// Index into the material names of the mesh
+    pub material: Option<usize>,
+}
+
+/// A point sampled on the surface of a mesh
+#[derive(Debug, Clone, Copy)]
+pub struct MeshSample {
+    pub point: Vector3D,
+    /// The geometric normal of the triangle
+    pub normal: Vector3D,
+    /// The index of the triangle
+    pub triangle: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TriangleMesh {
+    positions: Vec<Vector3D>,
+    normals: Vec<Vector3D>,
+    triangles: Vec<Triangle>,
+    groups: Vec<String>,
+    materials: Vec<String>,
+    /// The cumulative area of the triangles, for sampling them
+    cumulative_area: Vec<f64>,
+}
+
+impl TriangleMesh {
+    /// Builds a mesh, checking that the triangles only refer
+    /// to existing positions, normals, groups and materials
+    pub fn new(
+        positions: Vec<Vector3D>,
+        normals: Vec<Vector3D>,
+        triangles: Vec<Triangle>,
+        groups: Vec<String>,
+        materials: Vec<String>,
+    ) -> Self {
+        for t in triangles.iter() {
+            assert!(
+                t.vertices.iter().all(|i| *i < positions.len()),
+                "expecting vertex indices lower than {}... found {:?}",
+                positions.len(),
+                t.vertices
+            );
+            if let Some(n) = t.normals {
+                assert!(
+                    n.iter().all(|i| *i < normals.len()),
+                    "expecting normal indices lower than {}... found {:?}",
+                    normals.len(),
+                    n
+                );
+            }
+            assert!(t.group.is_none_or(|g| g < groups.len()));
+            assert!(t.material.is_none_or(|m| m < materials.len()));
+        }
+        let mut total = 0.0;
+        let cumulative_area = triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = t.vertices.map(|i| positions[i]);
+                total += (b - a).cross(c - a).length() / 2.;
+                total
+            })
+            .collect();
+        Self {
+            positions,
+            normals,
+            triangles,
+            groups,
+            materials,
+            cumulative_area,
+        }
+    }
+
+    pub fn positions(&self) -> &[Vector3D] {
+        &self.positions
+    }
+
+    pub fn normals(&self) -> &[Vector3D] {
+        &self.normals
+    }
+
+    pub fn triangles(&self) -> &[Triangle] {
+        &self.triangles
+    }
+
+    pub fn groups(&self) -> &[String] {
+        &self.groups
+    }
+
+    pub fn materials(&self) -> &[String] {
+        &self.materials
+    }
+
+    /// The number of triangles
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    /// The corners of triangle `i`
+    pub fn vertices(&self, i: usize) -> [Vector3D; 3] {
+        self.triangles[i].vertices.map(|v| self.positions[v])
+    }
+
+    /// The area of triangle `i`
+    pub fn area(&self, i: usize) -> f64 {
+        let previous = if i == 0 {
+            0.
+        } else {
+            self.cumulative_area[i - 1]
+        };
+        self.cumulative_area[i] - previous
+    }
+
+    /// The area of the whole mesh
+    pub fn total_area(&self) -> f64 {
+        self.cumulative_area.last().copied().unwrap_or(0.)
+    }
+
+    /// The geometric normal of triangle `i`, on the side from which
+    /// its vertices are seen counter-clockwise
+    pub fn normal(&self, i: usize) -> Vector3D {
+        let [a, b, c] = self.vertices(i);
+        (b - a).cross(c - a).normalize()
+    }
+
+    /// The normal at a point of triangle `i`, given its barycentric
+    /// coordinates. It is interpolated from the vertex normals, if
+    /// the triangle has them.
+    pub fn shading_normal(&self, i: usize, barycentrics: (f64, f64, f64)) -> Vector3D {
+        match self.triangles[i].normals {
+            Some(n) => {
+                let [na, nb, nc] = n.map(|k| self.normals[k]);
+                let (b0, b1, b2) = barycentrics;
+                (na * b0 + nb * b1 + nc * b2).normalize()
+            }
+            None => self.normal(i),
+        }
+    }
+
+    /// The box containing the whole mesh
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(&self.positions)
+    }
+
+    /// The index of the group called `name`
+    pub fn group(&self, name: &str) -> Option<usize> {
+        self.groups.iter().position(|g| g == name)
+    }
+
+    /// The index of the material called `name`
+    pub fn material(&self, name: &str) -> Option<usize> {
+        self.materials.iter().position(|m| m == name)
+    }
+
+    /// A mesh with only the triangles for which `keep` returns `true`
+    /// (e.g., those of a group). Positions, normals and names are kept.
+    pub fn filter<F: Fn(&Triangle) -> bool>(&self, keep: F) -> Self {
+        Self::new(
+            self.positions.clone(),
+            self.normals.clone(),
+            self.triangles.iter().filter(|t| keep(t)).copied().collect(),
+            self.groups.clone(),
+            self.materials.clone(),
+        )
+    }
+
+    /// Samples a point uniformly over the surface of the mesh (i.e., with
+    /// a PDF of `1/total_area`). The first number chooses a triangle,
+    /// proportionally to its area, and is then reused within it.
+    pub fn sample(&self, u: (f64, f64)) -> MeshSample {
+        assert!(!self.is_empty(), "cannot sample an empty mesh");
+        let total = self.total_area();
+        let target = u.0 * total;
+        let i = self
+            .cumulative_area
+            .partition_point(|a| *a <= target)
+            .min(self.len() - 1);
+        let previous = if i == 0 {
+            0.
+        } else {
+            self.cumulative_area[i - 1]
+        };
+        let area = self.area(i);
+        let u0 = if area > 0. {
+            ((target - previous) / area).clamp(0., 1.)
+        } else {
+            0.
+        };
+
+        let [a, b, c] = self.vertices(i);
+        let (b0, b1) = sample_uniform_barycentric((u0, u.1));
+        MeshSample {
+            point: a * b0 + b * b1 + c * (1. - b0 - b1),
+            normal: self.normal(i),
+            triangle: i,
+        }
+    }
+
+    /// The inverse of [`TriangleMesh::sample`], for a point with the
+    /// barycentric coordinates `(b0, b1)` in triangle `i`
+    pub fn sample_inverse(&self, i: usize, b: (f64, f64)) -> (f64, f64) {
+        let (u0, u1) = uniform_barycentric_inverse(b);
+        let previous = if i == 0 {
+            0.
+        } else {
+            self.cumulative_area[i - 1]
+        };
+        ((previous + u0 * self.area(i)) / self.total_area(), u1)
+    }
+
+    /// The PDF (per unit area) of the points produced by [`TriangleMesh::sample`]
+    pub fn pdf(&self) -> f64 {
+        1. / self.total_area()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    /// Two triangles, with areas 1 and 3
+    fn mesh() -> TriangleMesh {
+        let v = Vector3D::new;
+        let triangle = |vertices, group| Triangle {
+            vertices,
+            normals: None,
+            group: Some(group),
+            material: None,
+        };
+        TriangleMesh::new(
+            vec![
+                v(0., 0., 0.),
+                v(2., 0., 0.),
+                v(0., 1., 0.),
+                v(0., 0., 1.),
+                v(0., 3., 1.),
+                v(2., 0., 1.),
+            ],
+            vec![],
+            vec![triangle([0, 1, 2], 0), triangle([3, 4, 5], 1)],
+            vec!["small".to_string(), "large".to_string()],
+            vec![],
+        )
+    }
+
+    #[test]
+    fn areas() {
+        let mesh = mesh();
+        assert_eq!(mesh.len(), 2);
+        assert!((mesh.area(0) - 1.).abs() < 1e-12);
+        assert!((mesh.area(1) - 3.).abs() < 1e-12);
+        assert!((mesh.total_area() - 4.).abs() < 1e-12);
+        assert!(mesh.normal(0).approx_eq(Vector3D::new(0., 0., 1.), 1e-12));
+        assert!(mesh.normal(1).approx_eq(Vector3D::new(0., 0., -1.), 1e-12));
+        assert_eq!(mesh.bounds().max, Vector3D::new(2., 3., 1.));
+
+        let large = mesh.filter(|t| t.group == mesh.group("large"));
+        assert_eq!(large.len(), 1);
+        assert!((large.total_area() - 3.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn area_weighted_sampling() {
+        let mesh = mesh();
+        let mut rng = Rng::with_seed(0);
+        let n = 100_000;
+        let mut in_large = 0;
+        for _ in 0..n {
+            let u = (rng.next_float(), rng.next_float());
+            let s = mesh.sample(u);
+            let [a, b, c] = mesh.vertices(s.triangle);
+            // On the plane of the triangle, and inside it
+            assert!((s.point - a).dot(s.normal).abs() < 1e-12);
+            let area = |p: Vector3D, q: Vector3D, r: Vector3D| (q - p).cross(r - p).length() / 2.;
+            let sum = area(s.point, b, c) + area(a, s.point, c) + area(a, b, s.point);
+            assert!((sum - mesh.area(s.triangle)).abs() < 1e-9);
+            if s.triangle == 1 {
+                in_large += 1;
+            }
+
+            // ... and the mapping can be inverted
+            let b0 = area(s.point, b, c) / mesh.area(s.triangle);
+            let b1 = area(a, s.point, c) / mesh.area(s.triangle);
+            let (u0, u1) = mesh.sample_inverse(s.triangle, (b0, b1));
+            assert!((u0 - u.0).abs() < 1e-6 && (u1 - u.1).abs() < 1e-6);
+        }
+        let fraction = in_large as f64 / n as f64;
+        assert!((fraction - 0.75).abs() < 1e-2, "{}", fraction);
+        assert!((mesh.pdf() - 0.25).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,440 @@
+//! A reader for Wavefront OBJ files.
+//!
+//! It reads vertices (`v`), normals (`vn`), faces (`f`, where polygons
+//! are split into a fan of triangles), groups (`g` and `o`) and the names
+//! of the materials (`usemtl`). Texture coordinates are accepted in faces,
+//! but ignored, as are other statements (e.g., `mtllib`, `s` or `vt`).
+
+use crate::mesh::{Triangle, TriangleMesh};
+use lesson4_hemispheres::Vector3D;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// What is wrong with a line of an OBJ file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjErrorKind {
+    /// A value that should be a number is not
+    InvalidNumber(String),
+    /// A statement has the wrong number of values
+    WrongValueCount {
+        statement: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    /// A face vertex that is not `v`, `v/vt`, `v//vn` or `v/vt/vn`
+    InvalidFaceVertex(String),
+    /// A face refers to a vertex or normal that has not been defined
+    /// (indices start at 1, and negative ones count from the last one)
+    IndexOutOfRange { index: i64, available: usize },
+    /// A face has fewer than three vertices
+    DegenerateFace(usize),
+    /// Some vertices of a face have normals, and others do not
+    MixedNormals,
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(std::io::Error),
+    Parse {
+        /// Starting at 1
+        line: usize,
+        kind: ObjErrorKind,
+    },
+}
+
+impl fmt::Display for ObjErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjErrorKind::InvalidNumber(s) => write!(f, "expecting a number... found '{}'", s),
+            ObjErrorKind::WrongValueCount {
+                statement,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expecting {} values in '{}'... found {}",
+                expected, statement, found
+            ),
+            ObjErrorKind::InvalidFaceVertex(s) => {
+                write!(f, "expecting a face vertex like 'v/vt/vn'... found '{}'", s)
+            }
+            ObjErrorKind::IndexOutOfRange { index, available } => write!(
+                f,
+                "index {} is out of range, as there are {} elements",
+                index, available
+            ),
+            ObjErrorKind::DegenerateFace(n) => {
+                write!(f, "expecting at least 3 vertices in a face... found {}", n)
+            }
+            ObjErrorKind::MixedNormals => {
+                write!(
+                    f,
+                    "expecting normals in all the vertices of the face, or none"
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(e) => write!(f, "could not read OBJ file: {}", e),
+            ObjError::Parse { line, kind } => write!(f, "line {}: {}", line, kind),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io(e) => Some(e),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ObjError {
+    fn from(e: std::io::Error) -> Self {
+        ObjError::Io(e)
+    }
+}
+
+/// Reads the OBJ file at `path`
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<TriangleMesh, ObjError> {
+    read_obj(BufReader::new(File::open(path)?))
+}
+
+/// Parses OBJ data that is already in memory
+pub fn parse_obj(source: &str) -> Result<TriangleMesh, ObjError> {
+    read_obj(source.as_bytes())
+}
+
+/// Reads OBJ data from `reader`
+pub fn read_obj<R: BufRead>(reader: R) -> Result<TriangleMesh, ObjError> {
+    let mut parser = Parser::default();
+    for (i, line) in reader.lines().enumerate() {
+        parser
+            .parse_line(&line?)
+            .map_err(|kind| ObjError::Parse { line: i + 1, kind })?;
+    }
+    Ok(TriangleMesh::new(
+        parser.positions,
+        parser.normals,
+        parser.triangles,
+        parser.groups,
+        parser.materials,
+    ))
+}
+
+#[derive(Default)]
+struct Parser {
+    positions: Vec<Vector3D>,
+    normals: Vec<Vector3D>,
+    triangles: Vec<Triangle>,
+    groups: Vec<String>,
+    materials: Vec<String>,
+    group: Option<usize>,
+    material: Option<usize>,
+}
+
+/// The index of `name` in `names`, adding it if needed
+fn intern(names: &mut Vec<String>, name: &str) -> usize {
+    match names.iter().position(|n| n == name) {
+        Some(i) => i,
+        None => {
+            names.push(name.to_string());
+            names.len() - 1
+        }
+    }
+}
+
+fn parse_number(s: &str) -> Result<f64, ObjErrorKind> {
+    s.parse()
+        .map_err(|_| ObjErrorKind::InvalidNumber(s.to_string()))
+}
+
+/// Turns a (1-based, or negative) OBJ index into a 0-based one
+fn resolve_index(s: &str, available: usize) -> Result<usize, ObjErrorKind> {
+    let index: i64 = s
+        .parse()
+        .map_err(|_| ObjErrorKind::InvalidNumber(s.to_string()))?;
+    let resolved = if index < 0 {
+        available as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= available as i64 {
+        return Err(ObjErrorKind::IndexOutOfRange { index, available });
+    }
+    Ok(resolved as usize)
+}
+
+impl Parser {
+    fn parse_line(&mut self, line: &str) -> Result<(), ObjErrorKind> {
+        let line = match line.find('#') {
+            Some(i) => &line[..i],
+            None => line,
+        };
+        let mut tokens = line.split_whitespace();
+        let statement = match tokens.next() {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+        let values: Vec<&str> = tokens.collect();
+        match statement {
+            "v" => {
+                // An optional fourth value (w) is allowed, and ignored
+                let p = self.parse_vector("v", &values, 3..=4)?;
+                self.positions.push(p);
+            }
+            "vn" => {
+                let n = self.parse_vector("vn", &values, 3..=3)?;
+                self.normals.push(n);
+            }
+            "f" => self.parse_face(&values)?,
+            "g" | "o" => {
+                // Only the first name is kept when there are several
+                self.group = values.first().map(|name| intern(&mut self.groups, name));
+            }
+            "usemtl" => {
+                let name = match values.as_slice() {
+                    [name] => name,
+                    _ => {
+                        return Err(ObjErrorKind::WrongValueCount {
+                            statement: "usemtl",
+                            expected: 1,
+                            found: values.len(),
+                        })
+                    }
+                };
+                self.material = Some(intern(&mut self.materials, name));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn parse_vector(
+        &self,
+        statement: &'static str,
+        values: &[&str],
+        count: std::ops::RangeInclusive<usize>,
+    ) -> Result<Vector3D, ObjErrorKind> {
+        if !count.contains(&values.len()) {
+            return Err(ObjErrorKind::WrongValueCount {
+                statement,
+                expected: *count.start(),
+                found: values.len(),
+            });
+        }
+        Ok(Vector3D::new(
+            parse_number(values[0])?,
+            parse_number(values[1])?,
+            parse_number(values[2])?,
+        ))
+    }
+
+    fn parse_face(&mut self, values: &[&str]) -> Result<(), ObjErrorKind> {
+        if values.len() < 3 {
+            return Err(ObjErrorKind::DegenerateFace(values.len()));
+        }
+        let mut vertices = Vec::with_capacity(values.len());
+        let mut normals = Vec::with_capacity(values.len());
+        for value in values {
+            let parts: Vec<&str> = value.split('/').collect();
+            if parts.len() > 3 || parts[0].is_empty() {
+                return Err(ObjErrorKind::InvalidFaceVertex(value.to_string()));
+            }
+            vertices.push(resolve_index(parts[0], self.positions.len())?);
+            match parts.get(2) {
+                Some(n) if !n.is_empty() => normals.push(resolve_index(n, self.normals.len())?),
+                _ => {}
+            }
+        }
+        if !normals.is_empty() && normals.len() != vertices.len() {
+            return Err(ObjErrorKind::MixedNormals);
+        }
+
+        for i in 1..vertices.len() - 1 {
+            self.triangles.push(Triangle {
+                vertices: [vertices[0], vertices[i], vertices[i + 1]],
+                normals: if normals.is_empty() {
+                    None
+                } else {
+                    Some([normals[0], normals[i], normals[i + 1]])
+                },
+                group: self.group,
+                material: self.material,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE: &str = "# A unit cube, with its faces as quads
+mtllib cube.mtl
+o cube
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+vn 0 0 -1
+vn 0 0 1
+vt 0 0
+vt 1 1
+
+g floor
+usemtl concrete
+f 1//1 4//1 3//1 2//1
+g roof
+usemtl tiles
+f 5/1/2 6/2/2 7/1/2 8/2/2   # the roof
+g walls
+usemtl concrete
+f 1 2 6 5
+f -7 -6 -2 -3
+f 3 4 8 7
+f 4 1 5 8
+";
+
+    #[test]
+    fn cube() {
+        let mesh = parse_obj(CUBE).unwrap();
+        assert_eq!(mesh.positions().len(), 8);
+        assert_eq!(mesh.normals().len(), 2);
+        assert_eq!(mesh.len(), 12);
+        assert!((mesh.total_area() - 6.).abs() < 1e-12);
+        assert_eq!(mesh.groups(), ["cube", "floor", "roof", "walls"]);
+        assert_eq!(mesh.materials(), ["concrete", "tiles"]);
+
+        let floor = mesh.group("floor");
+        let roof = mesh.group("roof");
+        for (i, t) in mesh.triangles().iter().enumerate() {
+            if t.group == floor {
+                assert_eq!(t.normals, Some([0, 0, 0]));
+                assert!(mesh.normal(i).approx_eq(Vector3D::new(0., 0., -1.), 1e-12));
+            } else if t.group == roof {
+                assert_eq!(t.material, mesh.material("tiles"));
+                assert!(mesh
+                    .shading_normal(i, (0.2, 0.3, 0.5))
+                    .approx_eq(Vector3D::new(0., 0., 1.), 1e-12));
+            } else {
+                assert_eq!(t.material, mesh.material("concrete"));
+                assert!(t.normals.is_none());
+            }
+        }
+        // Negative indices count from the end
+        assert_eq!(mesh.triangles()[6].vertices, [1, 2, 6]);
+
+        // Faces are outwards, so the cube is closed
+        let centre = Vector3D::new(0.5, 0.5, 0.5);
+        for i in 0..mesh.len() {
+            let [a, ..] = mesh.vertices(i);
+            assert!(mesh.normal(i).dot(a - centre) > 0., "triangle {}", i);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let error = |source: &str| match parse_obj(source) {
+            Err(ObjError::Parse { line, kind }) => (line, kind),
+            other => panic!(
+                "expecting a parse error... found {:?}",
+                other.map(|m| m.len())
+            ),
+        };
+        let vertices = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+
+        assert_eq!(
+            error("v 0 0 0\nv 1 x 0\n"),
+            (2, ObjErrorKind::InvalidNumber("x".to_string()))
+        );
+        assert_eq!(
+            error("\n\nvn 0 1\n"),
+            (
+                3,
+                ObjErrorKind::WrongValueCount {
+                    statement: "vn",
+                    expected: 3,
+                    found: 2
+                }
+            )
+        );
+        assert_eq!(
+            error(&format!("{}f 1 2 4\n", vertices)),
+            (
+                4,
+                ObjErrorKind::IndexOutOfRange {
+                    index: 4,
+                    available: 3
+                }
+            )
+        );
+        assert_eq!(
+            error(&format!("{}f 0 1 2\n", vertices)),
+            (
+                4,
+                ObjErrorKind::IndexOutOfRange {
+                    index: 0,
+                    available: 3
+                }
+            )
+        );
+        assert_eq!(
+            error(&format!("{}f 1 2\n", vertices)),
+            (4, ObjErrorKind::DegenerateFace(2))
+        );
+        assert_eq!(
+            error(&format!("{}f 1/2/3/4 2 3\n", vertices)),
+            (4, ObjErrorKind::InvalidFaceVertex("1/2/3/4".to_string()))
+        );
+        assert_eq!(
+            error(&format!("{}vn 0 0 1\nf 1//1 2 3\n", vertices)),
+            (5, ObjErrorKind::MixedNormals)
+        );
+        assert_eq!(
+            error("usemtl\n"),
+            (
+                1,
+                ObjErrorKind::WrongValueCount {
+                    statement: "usemtl",
+                    expected: 1,
+                    found: 0
+                }
+            )
+        );
+
+        let message = format!(
+            "{}",
+            ObjError::Parse {
+                line: 2,
+                kind: ObjErrorKind::InvalidNumber("x".to_string()),
+            }
+        );
+        assert_eq!(message, "line 2: expecting a number... found 'x'");
+        assert!(matches!(
+            load_obj("this/file/does/not/exist.obj"),
+            Err(ObjError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn load_file() {
+        let path = std::env::temp_dir().join("lesson6-path-tracer-cube.obj");
+        std::fs::write(&path, CUBE).unwrap();
+        let mesh = load_obj(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(mesh.len(), 12);
+    }
+}
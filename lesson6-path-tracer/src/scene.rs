@@ -0,0 +1,409 @@
+//! Objects, the materials they are made of, and the lights among them
+
+use crate::bvh::Bvh;
+use crate::geometry::{Hit, Ray, Shape};
+use crate::mesh::TriangleMesh;
+use lesson4_hemispheres::bsdf::{Bsdf, Lambertian, Mirror, Phong};
+use lesson4_hemispheres::Vector3D;
+use std::cell::OnceCell;
+
+/// How a surface reflects and emits light
+pub struct Material {
+    pub bsdf: Box<dyn Bsdf>,
+    /// The radiance emitted from the front of the surface
+    pub emission: f64,
+}
+
+impl Material {
+    pub fn lambertian(reflectance: f64) -> Self {
+        Self {
+            bsdf: Box::new(Lambertian { reflectance }),
+            emission: 0.,
+        }
+    }
+
+    /// A glossy surface, following the modified Phong model
+    pub fn glossy(diffuse: f64, specular: f64, exponent: f64) -> Self {
+        Self {
+            bsdf: Box::new(Phong::new(diffuse, specular, exponent)),
+            emission: 0.,
+        }
+    }
+
+    pub fn mirror(reflectance: f64) -> Self {
+        Self {
+            bsdf: Box::new(Mirror { reflectance }),
+            emission: 0.,
+        }
+    }
+
+    /// A diffuse area light
+    pub fn emitter(emission: f64, reflectance: f64) -> Self {
+        Self {
+            bsdf: Box::new(Lambertian { reflectance }),
+            emission,
+        }
+    }
+}
+
+/// A shape and the index of its material
+#[derive(Debug, Clone, Copy)]
+pub struct Object {
+    pub shape: Shape,
+    pub material: usize,
+}
+
+/// A BVH over the objects that have bounds, and the list of
+/// those that do not (e.g., planes)
+struct Accelerator {
+    bvh: Bvh,
+    bounded: Vec<usize>,
+    unbounded: Vec<usize>,
+}
+
+impl Accelerator {
+    fn new(objects: &[Object]) -> Self {
+        let mut bounds = Vec::new();
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            match object.shape.bounds() {
+                Some(b) => {
+                    bounds.push(b);
+                    bounded.push(i);
+                }
+                None => unbounded.push(i),
+            }
+        }
+        Self {
+            bvh: Bvh::new(&bounds),
+            bounded,
+            unbounded,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Scene {
+    objects: Vec<Object>,
+    pub materials: Vec<Material>,
+    /// Built the first time the scene is intersected
+    accelerator: OnceCell<Accelerator>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a material, returning its index
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    /// Adds an object, returning its index
+    pub fn add_object(&mut self, shape: Shape, material: usize) -> usize {
+        assert!(
+            material < self.materials.len(),
+            "expecting a material index lower than {}... found {}",
+            self.materials.len(),
+            material
+        );
+        self.objects.push(Object { shape, material });
+        self.accelerator = OnceCell::new();
+        self.objects.len() - 1
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    /// Adds the quadrilateral `p0, p1, p2, p3` as two triangles. Its
+    /// front is the side from which the corners are seen counter-clockwise.
+    pub fn add_quad(
+        &mut self,
+        p0: Vector3D,
+        p1: Vector3D,
+        p2: Vector3D,
+        p3: Vector3D,
+        material: usize,
+    ) {
+        self.add_object(
+            Shape::Triangle {
+                a: p0,
+                b: p1,
+                c: p2,
+            },
+            material,
+        );
+        self.add_object(
+            Shape::Triangle {
+                a: p0,
+                b: p2,
+                c: p3,
+            },
+            material,
+        );
+    }
+
+    /// Adds every triangle of `mesh` with the same material. Use
+    /// [`TriangleMesh::filter`] to give different materials to
+    /// different groups.
+    pub fn add_mesh(&mut self, mesh: &TriangleMesh, material: usize) {
+        for i in 0..mesh.len() {
+            let [a, b, c] = mesh.vertices(i);
+            self.add_object(Shape::Triangle { a, b, c }, material);
+        }
+    }
+
+    /// The material of object `i`
+    pub fn material(&self, i: usize) -> &Material {
+        &self.materials[self.objects[i].material]
+    }
+
+    fn accelerator(&self) -> &Accelerator {
+        self.accelerator
+            .get_or_init(|| Accelerator::new(&self.objects))
+    }
+
+    /// The closest object hit by `ray` before `t_max`, and where
+    pub fn intersect(&self, ray: &Ray, t_max: f64) -> Option<(usize, Hit)> {
+        let accelerator = self.accelerator();
+        let mut closest = accelerator
+            .bvh
+            .closest_hit(ray, t_max, |i, t_max| {
+                let hit = self.objects[accelerator.bounded[i]]
+                    .shape
+                    .intersect(ray, t_max)?;
+                Some((hit.t, hit))
+            })
+            .map(|(i, hit)| (accelerator.bounded[i], hit));
+        let mut t_max = closest.map_or(t_max, |(_, hit)| hit.t);
+        for i in accelerator.unbounded.iter() {
+            if let Some(hit) = self.objects[*i].shape.intersect(ray, t_max) {
+                t_max = hit.t;
+                closest = Some((*i, hit));
+            }
+        }
+        closest
+    }
+
+    /// Whether anything is hit by `ray` before `t_max`
+    pub fn is_occluded(&self, ray: &Ray, t_max: f64) -> bool {
+        let accelerator = self.accelerator();
+        accelerator
+            .unbounded
+            .iter()
+            .any(|i| self.objects[*i].shape.intersect(ray, t_max).is_some())
+            || accelerator.bvh.any_hit(ray, t_max, |i, t_max| {
+                self.objects[accelerator.bounded[i]]
+                    .shape
+                    .intersect(ray, t_max)
+                    .is_some()
+            })
+    }
+
+    /// The indices of the objects that emit light
+    pub fn emitters(&self) -> Vec<usize> {
+        (0..self.objects.len())
+            .filter(|i| self.material(*i).emission > 0.)
+            .collect()
+    }
+
+    /// A closed box spanning from -1 to 1 in every axis (+Z up), with a
+    /// square light of side `2*light_half_size` in the middle of its ceiling
+    /// and a sphere resting above the floor. Every surface is Lambertian,
+    /// with the same `reflectance`, and faces the inside of the box.
+    pub fn cornell_box(reflectance: f64, emission: f64, light_half_size: f64) -> Self {
+        assert!(
+            light_half_size > 0. && light_half_size < 1.,
+            "expecting the light to be smaller than the ceiling... found half size {:.6}",
+            light_half_size
+        );
+        let mut scene = Self::new();
+        let white = scene.add_material(Material::lambertian(reflectance));
+        let light = scene.add_material(Material::emitter(emission, reflectance));
+
+        let v = Vector3D::new;
+        let h = light_half_size;
+        // Floor and walls
+        let walls = [
+            [
+                v(-1., -1., -1.),
+                v(1., -1., -1.),
+                v(1., 1., -1.),
+                v(-1., 1., -1.),
+            ],
+            [
+                v(-1., 1., -1.),
+                v(1., 1., -1.),
+                v(1., 1., 1.),
+                v(-1., 1., 1.),
+            ],
+            [
+                v(-1., -1., -1.),
+                v(-1., -1., 1.),
+                v(1., -1., 1.),
+                v(1., -1., -1.),
+            ],
+            [
+                v(-1., -1., -1.),
+                v(-1., 1., -1.),
+                v(-1., 1., 1.),
+                v(-1., -1., 1.),
+            ],
+            [
+                v(1., -1., -1.),
+                v(1., -1., 1.),
+                v(1., 1., 1.),
+                v(1., 1., -1.),
+            ],
+        ];
+        // The ceiling, around the light
+        let ceiling = [
+            [v(-1., h, 1.), v(-1., 1., 1.), v(1., 1., 1.), v(1., h, 1.)],
+            [
+                v(-1., -1., 1.),
+                v(-1., -h, 1.),
+                v(1., -h, 1.),
+                v(1., -1., 1.),
+            ],
+            [v(-1., -h, 1.), v(-1., h, 1.), v(-h, h, 1.), v(-h, -h, 1.)],
+            [v(h, -h, 1.), v(h, h, 1.), v(1., h, 1.), v(1., -h, 1.)],
+        ];
+        for [p0, p1, p2, p3] in walls.into_iter().chain(ceiling) {
+            scene.add_quad(p0, p1, p2, p3, white);
+        }
+        scene.add_quad(
+            v(-h, -h, 1.),
+            v(-h, h, 1.),
+            v(h, h, 1.),
+            v(h, -h, 1.),
+            light,
+        );
+
+        scene.add_object(
+            Shape::Sphere {
+                centre: v(0.3, 0.2, -0.55),
+                radius: 0.4,
+            },
+            white,
+        );
+        scene
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cornell_box() {
+        let scene = Scene::cornell_box(0.5, 1., 0.25);
+        let centre = Vector3D::new(0., 0., 0.);
+        let mut area = 0.;
+        for object in scene.objects().iter() {
+            if let Shape::Triangle { a, .. } = object.shape {
+                // Facing the inside of the box
+                assert!(object.shape.normal(a).dot(centre - a) > 0.);
+                area += object.shape.area();
+            }
+        }
+        assert!((area - 24.).abs() < 1e-12);
+
+        let emitters = scene.emitters();
+        assert_eq!(emitters.len(), 2);
+        let light_area: f64 = emitters
+            .iter()
+            .map(|i| scene.objects()[*i].shape.area())
+            .sum();
+        assert!((light_area - 0.25).abs() < 1e-12);
+
+        // Looking up from the centre, the light is seen
+        let up = Ray::new(centre, Vector3D::new(0., 0., 1.));
+        let (i, hit) = scene.intersect(&up, f64::INFINITY).unwrap();
+        assert!(emitters.contains(&i));
+        assert!((hit.t - 1.).abs() < 1e-12);
+        assert!(scene.is_occluded(&up, 2.));
+        assert!(!scene.is_occluded(&up, 0.5));
+    }
+
+    #[test]
+    fn accelerated_queries() {
+        // A plane (which is not in the BVH) cutting through the box
+        let mut scene = Scene::cornell_box(0.5, 1., 0.25);
+        let m = scene.add_material(Material::mirror(0.9));
+        scene.add_object(
+            Shape::Plane {
+                point: Vector3D::new(0., 0., 0.2),
+                normal: Vector3D::new(0.1, 0., 1.).normalize(),
+            },
+            m,
+        );
+        scene.add_object(
+            Shape::Disc {
+                centre: Vector3D::new(-0.5, 0.5, -0.5),
+                normal: Vector3D::new(0., -1., 0.),
+                radius: 0.3,
+            },
+            m,
+        );
+
+        let mut rng = rand::Rng::with_seed(0);
+        for _ in 0..2000 {
+            let v = |rng: &mut rand::Rng| 2. * rng.next_float() - 1.;
+            let origin = Vector3D::new(v(&mut rng), v(&mut rng), v(&mut rng)) * 1.5;
+            let direction = lesson4_hemispheres::shapes::sample_uniform_sphere((
+                rng.next_float(),
+                rng.next_float(),
+            ));
+            let ray = Ray::new(origin, direction);
+
+            let mut expected = None;
+            let mut t_max = f64::INFINITY;
+            for (i, object) in scene.objects().iter().enumerate() {
+                if let Some(hit) = object.shape.intersect(&ray, t_max) {
+                    t_max = hit.t;
+                    expected = Some((i, hit.t));
+                }
+            }
+            let found = scene
+                .intersect(&ray, f64::INFINITY)
+                .map(|(i, hit)| (i, hit.t));
+            assert_eq!(found, expected);
+            if t_max.is_finite() {
+                assert!(scene.is_occluded(&ray, t_max + 1e-9));
+                assert!(!scene.is_occluded(&ray, t_max - 1e-9));
+            } else {
+                assert!(!scene.is_occluded(&ray, f64::INFINITY));
+            }
+        }
+    }
+
+    #[test]
+    fn meshes() {
+        // A tetrahedron from an OBJ file, split into two groups
+        let mesh = crate::obj::parse_obj(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\ng base\nf 1 3 2\ng sides\nf 1 2 4\nf 2 3 4\nf 3 1 4\n",
+        )
+        .unwrap();
+        let mut scene = Scene::new();
+        let white = scene.add_material(Material::lambertian(0.5));
+        let light = scene.add_material(Material::emitter(1., 0.5));
+        let base = mesh.group("base");
+        scene.add_mesh(&mesh.filter(|t| t.group == base), light);
+        scene.add_mesh(&mesh.filter(|t| t.group != base), white);
+        assert_eq!(scene.objects().len(), 4);
+        assert_eq!(scene.emitters(), vec![0]);
+
+        let ray = Ray::new(Vector3D::new(0.2, 0.2, -1.), Vector3D::new(0., 0., 1.));
+        let (i, hit) = scene.intersect(&ray, f64::INFINITY).unwrap();
+        assert_eq!(i, 0);
+        assert!((hit.t - 1.).abs() < 1e-12);
+        let ray = Ray::new(Vector3D::new(0.2, 0.2, 2.), Vector3D::new(0., 0., -1.));
+        let (i, hit) = scene.intersect(&ray, f64::INFINITY).unwrap();
+        assert_eq!(scene.objects()[i].material, white);
+        assert!((hit.t - 1.4).abs() < 1e-12);
+    }
+}
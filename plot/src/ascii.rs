@@ -0,0 +1,156 @@
+//! Renders a [`Chart`] as ASCII art, e.g., to check results in CI logs
+
+use crate::Chart;
+
+/// The characters of the series, in order. The histogram uses `#`.
+const MARKERS: [char; 6] = ['*', 'o', '+', 'x', '%', '@'];
+/// The character of dashed series
+const DASHED: char = '.';
+
+pub fn render(chart: &Chart, width: usize, height: usize) -> String {
+    assert!(
+        width >= 2 && height >= 2,
+        "expecting at least 2 rows and columns... found {}x{}",
+        width,
+        height
+    );
+    let (x, y) = chart.axes();
+    let mut grid = vec![vec![' '; width]; height];
+    let column = |v: f64| (x.position(v) * (width - 1) as f64).round();
+    let row = |v: f64| (height - 1) as f64 - (y.position(v) * (height - 1) as f64).round();
+
+    if let Some(h) = &chart.histogram {
+        for c in 0..width {
+            let v = x.min + (x.max - x.min) * (c as f64 + 0.5) / width as f64;
+            if v < h.min || v >= h.max {
+                continue;
+            }
+            let bin = (((v - h.min) / h.bin_width()) as usize).min(h.bins() - 1);
+            let density = h.density(bin);
+            if density <= 0. || !y.accepts(density) {
+                continue;
+            }
+            let top = row(density).clamp(0., (height - 1) as f64) as usize;
+            for line in grid.iter_mut().skip(top) {
+                line[c] = '#';
+            }
+        }
+    }
+
+    // Series are drawn last, on top of the bars, and sampled finely
+    // enough to look continuous
+    for (i, series) in chart.series.iter().enumerate() {
+        let marker = if series.dashed {
+            DASHED
+        } else {
+            MARKERS[i % MARKERS.len()]
+        };
+        let points: Vec<(f64, f64)> = series
+            .points
+            .iter()
+            .filter(|(px, py)| x.accepts(*px) && y.accepts(*py))
+            .map(|(px, py)| (column(*px), row(*py)))
+            .collect();
+        let mut plot = |c: f64, r: f64| {
+            let (c, r) = (c.round(), r.round());
+            if c >= 0. && r >= 0. && (c as usize) < width && (r as usize) < height {
+                grid[r as usize][c as usize] = marker;
+            }
+        };
+        if let [(c, r)] = points.as_slice() {
+            plot(*c, *r);
+        }
+        for pair in points.windows(2) {
+            let ((c0, r0), (c1, r1)) = (pair[0], pair[1]);
+            let steps = (c1 - c0).abs().max((r1 - r0).abs()).max(1.) as usize;
+            for s in 0..=steps {
+                let t = s as f64 / steps as f64;
+                plot(c0 + (c1 - c0) * t, r0 + (r1 - r0) * t);
+            }
+        }
+    }
+
+    let top_label = y.format(y.max);
+    let bottom_label = y.format(y.min);
+    let gutter = top_label.len().max(bottom_label.len());
+    let mut out = format!("{}\n", chart.title);
+    for (r, line) in grid.iter().enumerate() {
+        let label = if r == 0 {
+            top_label.as_str()
+        } else if r == height - 1 {
+            bottom_label.as_str()
+        } else {
+            ""
+        };
+        let line: String = line.iter().collect();
+        out.push_str(&format!("{:>gutter$} |{}\n", label, line.trim_end()));
+    }
+    out.push_str(&format!("{:>gutter$} +{}\n", "", "-".repeat(width)));
+    let left = x.format(x.min);
+    let right = x.format(x.max);
+    let padding = (width + 1).saturating_sub(left.len() + right.len());
+    out.push_str(&format!(
+        "{:>gutter$}  {}{}{}  ({})\n",
+        "",
+        left,
+        " ".repeat(padding.saturating_sub(1)),
+        right,
+        chart.x_label
+    ));
+
+    let mut legend: Vec<String> = Vec::new();
+    if chart.histogram.is_some() {
+        legend.push("# samples".to_string());
+    }
+    for (i, series) in chart.series.iter().enumerate() {
+        let marker = if series.dashed {
+            DASHED
+        } else {
+            MARKERS[i % MARKERS.len()]
+        };
+        legend.push(format!("{} {}", marker, series.label));
+    }
+    if !legend.is_empty() {
+        out.push_str(&format!(
+            "{:>gutter$}  {}  [{}]\n",
+            "",
+            legend.join("   "),
+            chart.y_label
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Histogram, Series};
+
+    #[test]
+    fn art() {
+        let histogram = Histogram::from_samples(0., 1., 2, [0.1, 0.2, 0.3, 0.7]);
+        let chart = Chart::histogram("Samples", histogram, |_| 1.);
+        let art = render(&chart, 20, 6);
+        let lines: Vec<&str> = art.lines().collect();
+        assert_eq!(lines[0], "Samples");
+        // Title, rows, axis, range and legend
+        assert_eq!(lines.len(), 1 + 6 + 3);
+        // The first bin (with a density of 1.5) reaches the top
+        assert!(lines[1].starts_with("1.5 |####"));
+        assert!(lines[6].starts_with("0.0 |####"));
+        assert!(lines[9].contains("# samples"));
+        assert!(lines[9].contains("* pdf"));
+        // The PDF runs through the middle of the second bin's bar
+        assert!(lines[3].ends_with('*'));
+
+        let points = vec![(1., 1.), (100., 1e-2), (10_000., 1e-4)];
+        let chart = Chart::convergence("Error", vec![Series::new("MC", points)]);
+        let art = chart.to_ascii(30, 10);
+        assert!(art.contains(". N^-1/2"));
+        let lines: Vec<&str> = art.lines().collect();
+        // Straight lines from the top left, where the reference is drawn last
+        assert!(lines[1].starts_with(" 1e0 |."));
+        assert!(lines[10].ends_with('*'));
+        assert!(lines[12].contains("1e0") && lines[12].contains("1e4"));
+    }
+}
@@ -0,0 +1,401 @@
+//! Charts of lesson results, as SVG files or as ASCII art for the
+//! terminal, so they can be checked without external plotting scripts.
+//!
+//! A [`Chart`] holds an optional [`Histogram`] (e.g., of samples drawn
+//! from a PDF) and a few [`Series`] of points (e.g., the PDF itself, or
+//! the error of an estimator as the number of samples grows).
+
+pub mod ascii;
+pub mod svg;
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// Counts of values falling in equal bins between `min` and `max`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    counts: Vec<usize>,
+    /// Including the values outside of `[min, max)`
+    total: usize,
+}
+
+impl Histogram {
+    pub fn new(min: f64, max: f64, bins: usize) -> Self {
+        assert!(
+            max > min && bins > 0,
+            "expecting a non-empty range and bins... found [{:.6}, {:.6}) and {} bins",
+            min,
+            max,
+            bins
+        );
+        Self {
+            min,
+            max,
+            counts: vec![0; bins],
+            total: 0,
+        }
+    }
+
+    pub fn from_samples<I: IntoIterator<Item = f64>>(
+        min: f64,
+        max: f64,
+        bins: usize,
+        samples: I,
+    ) -> Self {
+        let mut histogram = Self::new(min, max, bins);
+        for x in samples {
+            histogram.add(x);
+        }
+        histogram
+    }
+
+    pub fn add(&mut self, x: f64) {
+        self.total += 1;
+        if x >= self.min && x < self.max {
+            let bin = ((x - self.min) / self.bin_width()) as usize;
+            let last = self.counts.len() - 1;
+            self.counts[bin.min(last)] += 1;
+        }
+    }
+
+    pub fn bins(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn counts(&self) -> &[usize] {
+        &self.counts
+    }
+
+    pub fn bin_width(&self) -> f64 {
+        (self.max - self.min) / self.counts.len() as f64
+    }
+
+    /// The lower and upper bounds of bin `i`
+    pub fn bin_range(&self, i: usize) -> (f64, f64) {
+        let w = self.bin_width();
+        (self.min + i as f64 * w, self.min + (i + 1) as f64 * w)
+    }
+
+    /// The estimated PDF within bin `i`, so it can be compared
+    /// with the PDF the values were drawn from
+    pub fn density(&self, i: usize) -> f64 {
+        if self.total == 0 {
+            return 0.;
+        }
+        self.counts[i] as f64 / (self.total as f64 * self.bin_width())
+    }
+}
+
+/// A line through some points
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub label: String,
+    pub points: Vec<(f64, f64)>,
+    /// Whether it is drawn as a dashed line (e.g., a reference)
+    pub dashed: bool,
+}
+
+impl Series {
+    pub fn new(label: &str, points: Vec<(f64, f64)>) -> Self {
+        Self {
+            label: label.to_string(),
+            points,
+            dashed: false,
+        }
+    }
+
+    /// Samples `f` at `n` evenly spaced points between `min` and `max`
+    pub fn from_fn<F: Fn(f64) -> f64>(label: &str, min: f64, max: f64, n: usize, f: F) -> Self {
+        let points = (0..n)
+            .map(|i| {
+                let x = min + (max - min) * i as f64 / (n - 1).max(1) as f64;
+                (x, f(x))
+            })
+            .collect();
+        Self::new(label, points)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scale {
+    Linear,
+    /// Base 10. Values that are not positive are left out.
+    Log,
+}
+
+/// Where a value lands along an axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Axis {
+    pub scale: Scale,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Axis {
+    fn transform(&self, v: f64) -> f64 {
+        match self.scale {
+            Scale::Linear => v,
+            Scale::Log => v.log10(),
+        }
+    }
+
+    /// Whether `v` can be shown on this axis
+    pub fn accepts(&self, v: f64) -> bool {
+        v.is_finite() && (self.scale == Scale::Linear || v > 0.)
+    }
+
+    /// The position of `v`, from 0 at `min` to 1 at `max`
+    pub fn position(&self, v: f64) -> f64 {
+        let (a, b) = (self.transform(self.min), self.transform(self.max));
+        (self.transform(v) - a) / (b - a)
+    }
+
+    /// A few round values within the axis, to label it
+    pub fn ticks(&self) -> Vec<f64> {
+        match self.scale {
+            Scale::Log => {
+                let first = self.min.log10().ceil() as i32;
+                let last = self.max.log10().floor() as i32;
+                let decades: Vec<f64> = (first..=last).map(|k| 10f64.powi(k)).collect();
+                if decades.len() >= 2 {
+                    // At most 8 labels
+                    let stride = decades.len().div_ceil(8);
+                    decades.into_iter().step_by(stride).collect()
+                } else {
+                    vec![self.min, self.max]
+                }
+            }
+            Scale::Linear => {
+                let step = self.tick_step();
+                let first = (self.min / step).ceil() as i64;
+                let last = (self.max / step).floor() as i64;
+                (first..=last).map(|k| k as f64 * step).collect()
+            }
+        }
+    }
+
+    /// A round step giving about 5 ticks on a linear axis
+    fn tick_step(&self) -> f64 {
+        let rough = (self.max - self.min) / 5.;
+        let magnitude = 10f64.powf(rough.log10().floor());
+        let normalized = rough / magnitude;
+        let nice = if normalized < 1.5 {
+            1.
+        } else if normalized < 3.5 {
+            2.
+        } else if normalized < 7.5 {
+            5.
+        } else {
+            10.
+        };
+        nice * magnitude
+    }
+
+    /// A short label for `v`
+    pub fn format(&self, v: f64) -> String {
+        match self.scale {
+            Scale::Log => {
+                let k = v.log10();
+                if (k - k.round()).abs() < 1e-9 {
+                    format!("1e{}", k.round() as i32)
+                } else {
+                    format!("{:.2e}", v)
+                }
+            }
+            Scale::Linear => {
+                let decimals = (-self.tick_step().log10().floor()).max(0.) as usize;
+                format!("{:.*}", decimals, v)
+            }
+        }
+    }
+}
+
+/// A histogram and some series, sharing the same axes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chart {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub x_scale: Scale,
+    pub y_scale: Scale,
+    pub histogram: Option<Histogram>,
+    pub series: Vec<Series>,
+}
+
+impl Chart {
+    /// An empty chart, with linear axes
+    pub fn new(title: &str, x_label: &str, y_label: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            x_label: x_label.to_string(),
+            y_label: y_label.to_string(),
+            x_scale: Scale::Linear,
+            y_scale: Scale::Linear,
+            histogram: None,
+            series: Vec::new(),
+        }
+    }
+
+    /// The density of `histogram` as bars, against the `pdf` the
+    /// samples were drawn from
+    pub fn histogram<F: Fn(f64) -> f64>(title: &str, histogram: Histogram, pdf: F) -> Self {
+        let mut chart = Self::new(title, "x", "pdf");
+        // Enough points to show the steps of a piecewise constant PDF
+        let n = 8 * histogram.bins() + 1;
+        chart
+            .series
+            .push(Series::from_fn("pdf", histogram.min, histogram.max, n, pdf));
+        chart.histogram = Some(histogram);
+        chart
+    }
+
+    /// The error of some estimators against the number of samples `N`,
+    /// on log-log axes, with a reference `N^-1/2` slope through the
+    /// first point of the first series
+    pub fn convergence(title: &str, series: Vec<Series>) -> Self {
+        let mut chart = Self::new(title, "N", "error");
+        chart.x_scale = Scale::Log;
+        chart.y_scale = Scale::Log;
+        let reference = series.first().and_then(|s| {
+            let positive: Vec<(f64, f64)> = s
+                .points
+                .iter()
+                .copied()
+                .filter(|(x, y)| *x > 0. && *y > 0.)
+                .collect();
+            let (x0, y0) = *positive.first()?;
+            let x1 = positive.iter().map(|(x, _)| *x).fold(x0, f64::max);
+            let mut reference =
+                Series::new("N^-1/2", vec![(x0, y0), (x1, y0 * (x1 / x0).powf(-0.5))]);
+            reference.dashed = true;
+            Some(reference)
+        });
+        chart.series = series;
+        chart.series.extend(reference);
+        chart
+    }
+
+    /// The range covered by the histogram and the series, on both axes
+    pub fn axes(&self) -> (Axis, Axis) {
+        let mut xs = Vec::new();
+        let mut ys = Vec::new();
+        if let Some(h) = &self.histogram {
+            xs.extend([h.min, h.max]);
+            ys.push(0.);
+            ys.extend((0..h.bins()).map(|i| h.density(i)));
+        }
+        for s in self.series.iter() {
+            xs.extend(s.points.iter().map(|(x, _)| *x));
+            ys.extend(s.points.iter().map(|(_, y)| *y));
+        }
+        (axis(self.x_scale, &xs), axis(self.y_scale, &ys))
+    }
+
+    /// Writes the chart as an SVG file
+    pub fn save_svg<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        File::create(path)?.write_all(svg::render(self).as_bytes())
+    }
+
+    /// The chart as ASCII art, with a plotting area of `width`
+    /// columns and `height` rows
+    pub fn to_ascii(&self, width: usize, height: usize) -> String {
+        ascii::render(self, width, height)
+    }
+}
+
+/// An axis spanning all the `values` it accepts
+fn axis(scale: Scale, values: &[f64]) -> Axis {
+    let mut axis = Axis {
+        scale,
+        min: f64::INFINITY,
+        max: f64::NEG_INFINITY,
+    };
+    for v in values.iter().copied() {
+        if axis.accepts(v) {
+            axis.min = axis.min.min(v);
+            axis.max = axis.max.max(v);
+        }
+    }
+    if axis.min > axis.max {
+        // Nothing to show
+        axis.min = if scale == Scale::Log { 0.1 } else { 0. };
+        axis.max = 1.;
+    } else if axis.min == axis.max {
+        match scale {
+            Scale::Linear => {
+                axis.min -= 0.5;
+                axis.max += 0.5;
+            }
+            Scale::Log => {
+                axis.min /= 2.;
+                axis.max *= 2.;
+            }
+        }
+    }
+    axis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histograms() {
+        let h = Histogram::from_samples(0., 2., 4, [0., 0.1, 0.6, 1.9, 2., -1.]);
+        assert_eq!(h.counts(), &[2, 1, 0, 1]);
+        assert_eq!(h.bin_range(1), (0.5, 1.));
+        // Values outside of the range still count for the density
+        assert!((h.density(0) - 2. / (6. * 0.5)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn slopes() {
+        let points: Vec<(f64, f64)> = (1..10)
+            .map(|k| {
+                let n = 2f64.powi(k);
+                (n, 3. / n.sqrt())
+            })
+            .collect();
+        let chart = Chart::convergence("Convergence", vec![Series::new("MC", points.clone())]);
+        let reference = chart.series.last().unwrap();
+        assert!(reference.dashed);
+        assert_eq!(reference.points[0], points[0]);
+        // ... with the slope of `N^-1/2` on log-log axes
+        let (first, last) = (reference.points[0], *reference.points.last().unwrap());
+        let slope = (last.1 / first.1).ln() / (last.0 / first.0).ln();
+        assert!((slope + 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn axes() {
+        let axis = Axis {
+            scale: Scale::Linear,
+            min: 0.,
+            max: 9.1,
+        };
+        assert_eq!(axis.ticks(), vec![0., 2., 4., 6., 8.]);
+        assert_eq!(axis.format(2.), "2");
+        assert_eq!(axis.position(9.1), 1.);
+
+        let axis = Axis {
+            scale: Scale::Log,
+            min: 2.,
+            max: 3e4,
+        };
+        assert_eq!(axis.ticks(), vec![10., 100., 1e3, 1e4]);
+        assert_eq!(axis.format(1e3), "1e3");
+        assert!((axis.position(2.)).abs() < 1e-12);
+        assert!(!axis.accepts(0.));
+
+        let mut chart = Chart::histogram("", Histogram::new(0., 1., 10), |_| 1.);
+        let (x, y) = chart.axes();
+        assert_eq!((x.min, x.max), (0., 1.));
+        assert_eq!((y.min, y.max), (0., 1.));
+        chart.series.clear();
+        chart.histogram = None;
+        assert_eq!(chart.axes().0.max, 1.);
+    }
+}
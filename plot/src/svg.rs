@@ -0,0 +1,212 @@
+//! Renders a [`Chart`] as an SVG document
+
+use crate::{Axis, Chart};
+use std::fmt::Write;
+
+const WIDTH: f64 = 640.;
+const HEIGHT: f64 = 400.;
+const LEFT: f64 = 70.;
+const RIGHT: f64 = 20.;
+const TOP: f64 = 40.;
+const BOTTOM: f64 = 50.;
+
+/// Colors for the series, in order
+const PALETTE: [&str; 6] = [
+    "#d62728", "#1f77b4", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b",
+];
+
+/// Escapes the characters that have a meaning in XML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Where a point lands in the plotting area, in pixels
+struct Frame {
+    x: Axis,
+    y: Axis,
+}
+
+impl Frame {
+    fn px(&self, x: f64) -> f64 {
+        LEFT + self.x.position(x) * (WIDTH - LEFT - RIGHT)
+    }
+
+    fn py(&self, y: f64) -> f64 {
+        HEIGHT - BOTTOM - self.y.position(y) * (HEIGHT - TOP - BOTTOM)
+    }
+}
+
+pub fn render(chart: &Chart) -> String {
+    let (x, y) = chart.axes();
+    let frame = Frame { x, y };
+    // Writing to a String never fails
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="12">"#,
+        w = WIDTH,
+        h = HEIGHT
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="{}" height="{}" fill="white"/>"#,
+        WIDTH, HEIGHT
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="24" text-anchor="middle" font-size="16">{}</text>"#,
+        WIDTH / 2.,
+        escape(&chart.title)
+    );
+
+    // Grid, ticks and labels
+    for t in x.ticks() {
+        let px = frame.px(t);
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{px:.2}" y1="{}" x2="{px:.2}" y2="{}" stroke="#ddd"/>"##,
+            TOP,
+            HEIGHT - BOTTOM
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{px:.2}" y="{}" text-anchor="middle">{}</text>"#,
+            HEIGHT - BOTTOM + 16.,
+            x.format(t)
+        );
+    }
+    for t in y.ticks() {
+        let py = frame.py(t);
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{}" y1="{py:.2}" x2="{}" y2="{py:.2}" stroke="#ddd"/>"##,
+            LEFT,
+            WIDTH - RIGHT
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{:.2}" text-anchor="end">{}</text>"#,
+            LEFT - 6.,
+            py + 4.,
+            y.format(t)
+        );
+    }
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+        (LEFT + WIDTH - RIGHT) / 2.,
+        HEIGHT - 12.,
+        escape(&chart.x_label)
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="16" y="{y}" text-anchor="middle" transform="rotate(-90 16 {y})">{}</text>"#,
+        escape(&chart.y_label),
+        y = (TOP + HEIGHT - BOTTOM) / 2.
+    );
+
+    if let Some(h) = &chart.histogram {
+        let base = frame.py(y.min.max(0.));
+        for i in 0..h.bins() {
+            let density = h.density(i);
+            if density <= 0. || !y.accepts(density) {
+                continue;
+            }
+            let (a, b) = h.bin_range(i);
+            let top = frame.py(density);
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="#9ecae1"/>"##,
+                frame.px(a),
+                top,
+                frame.px(b) - frame.px(a),
+                base - top
+            );
+        }
+    }
+
+    for (i, series) in chart.series.iter().enumerate() {
+        let color = PALETTE[i % PALETTE.len()];
+        let points: Vec<String> = series
+            .points
+            .iter()
+            .filter(|(px, py)| x.accepts(*px) && y.accepts(*py))
+            .map(|(px, py)| format!("{:.2},{:.2}", frame.px(*px), frame.py(*py)))
+            .collect();
+        let dash = if series.dashed {
+            r#" stroke-dasharray="6 4""#
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            svg,
+            r#"<polyline fill="none" stroke="{}" stroke-width="1.5"{} points="{}"/>"#,
+            color,
+            dash,
+            points.join(" ")
+        );
+
+        // Legend, in the top right corner
+        let ly = TOP + 14. + 16. * i as f64;
+        let lx = WIDTH - RIGHT - 130.;
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{}" y1="{ly}" x2="{}" y2="{ly}" stroke="{}" stroke-width="1.5"{}/>"#,
+            lx,
+            lx + 20.,
+            color,
+            dash
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}">{}</text>"#,
+            lx + 26.,
+            ly + 4.,
+            escape(&series.label)
+        );
+    }
+
+    let _ = writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="black"/>"#,
+        LEFT,
+        TOP,
+        WIDTH - LEFT - RIGHT,
+        HEIGHT - TOP - BOTTOM
+    );
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Histogram, Series};
+
+    #[test]
+    fn documents() {
+        let histogram = Histogram::from_samples(0., 1., 4, [0.1, 0.3, 0.35, 0.9]);
+        let mut chart = Chart::histogram("Samples <&>", histogram, |_| 1.);
+        chart.series.push(Series::new("empty", vec![]));
+        let svg = render(&chart);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains("Samples &lt;&amp;&gt;"));
+        // One bar per non-empty bin, plus the background and the frame
+        assert_eq!(svg.matches("<rect").count(), 3 + 2);
+        assert_eq!(svg.matches("<polyline").count(), 2);
+
+        let points = vec![(1., 1.), (10., 0.3), (100., 0.1), (1000., 0.)];
+        let chart = Chart::convergence("Error", vec![Series::new("MC", points)]);
+        let svg = render(&chart);
+        assert!(svg.contains("stroke-dasharray"));
+        assert!(svg.contains(">1e2<"));
+        // The zero error is left out
+        let first = svg.find("<polyline").unwrap();
+        let line = &svg[first..first + svg[first..].find('\n').unwrap()];
+        assert_eq!(line.matches(',').count(), 3);
+    }
+}
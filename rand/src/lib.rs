@@ -1,5 +1,10 @@
+use std::f64::consts::PI;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// A description of the generator of [`Rng`], for the metadata of results
+pub const GENERATOR: &str =
+    "LCG (a = 6364136223846793005, c = 1, m = 2^32), seeded with SplitMix64";
+
 pub struct Rng {
     seed: u64,
 }
@@ -15,6 +20,27 @@ impl Rng {
         Rng { seed }
     }
 
+    /// Creates a generator that always produces the same sequence
+    /// for the same `seed`, so experiments can be reproduced.
+    pub fn with_seed(seed: u64) -> Self {
+        // Scrambled so that consecutive seeds do not produce correlated
+        // sequences
+        Rng {
+            seed: splitmix(seed),
+        }
+    }
+
+    /// One of many generators for the same `seed` (e.g., one per trial of
+    /// an experiment), starting at scrambled points that depend on every
+    /// bit of both `seed` and `stream`.
+    ///
+    /// All of them are points on the same cycle of the LCG (with a period
+    /// of 2^32), so they are not independent: long enough runs of
+    /// different streams can overlap.
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        Self::with_seed(splitmix(seed) ^ stream)
+    }
+
     fn next(&mut self) -> u64 {
         // Parameters for Rng (these values are chosen to provide a full period)
         const A: u64 = 6364136223846793005;
@@ -30,21 +56,93 @@ impl Rng {
         // Convert to a floating point number in the range [0, 1)
         self.next() as f64 / (1u64 << 32) as f64
     }
+
+    /// A normally distributed number, with mean 0 and standard deviation 1
+    /// (Box-Muller)
+    pub fn next_normal(&mut self) -> f64 {
+        let u = 1.0 - self.next_float();
+        let v = self.next_float();
+        (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+    }
+}
+
+/// SplitMix64 of a single value, which scrambles all of its bits
+fn splitmix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::Rng;
-    use std::fs::File;
-    use std::io::prelude::*;
 
     #[test]
     fn sample() {
         let mut rng = Rng::new();
-        let mut file = File::create("data/uniform_samples.txt").unwrap();
-        for _ in 0..10000 {
-            let ln = format!("{}\n", rng.next_float());
-            file.write_all(ln.as_bytes()).unwrap();
+        let n = 10000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let x = rng.next_float();
+            assert!((0.0..1.0).contains(&x));
+            sum += x;
+        }
+        let mean = sum / n as f64;
+        assert!(
+            (mean - 0.5).abs() < 0.02,
+            "expecting a mean of 0.5... found {:.6}",
+            mean
+        );
+    }
+
+    #[test]
+    fn normal() {
+        let mut rng = Rng::with_seed(1);
+        let samples: Vec<f64> = (0..10_000).map(|_| rng.next_normal()).collect();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        assert!(mean.abs() < 0.05, "{:.6}", mean);
+        assert!((variance - 1.0).abs() < 0.05, "{:.6}", variance);
+    }
+
+    #[test]
+    fn seeded() {
+        let mut a = Rng::with_seed(123);
+        let mut b = Rng::with_seed(123);
+        let mut c = Rng::with_seed(124);
+        for _ in 0..100 {
+            let x = a.next_float();
+            assert_eq!(x, b.next_float());
+            assert_ne!(x, c.next_float());
+        }
+
+        let mut a = Rng::with_stream(123, 0);
+        let mut b = Rng::with_stream(123, 1);
+        let mut c = Rng::with_stream(124, 0);
+        for _ in 0..100 {
+            let x = a.next_float();
+            assert_ne!(x, b.next_float());
+            assert_ne!(x, c.next_float());
+        }
+
+        // The high bits of the seed count too
+        for high in [1 << 32, 1 << 63] {
+            let mut a = Rng::with_stream(0, 0);
+            let mut b = Rng::with_stream(high, 0);
+            let mut c = Rng::with_stream(high + 123, 0);
+            let mut d = Rng::with_stream(123, 0);
+            for _ in 0..100 {
+                assert_ne!(a.next_float(), b.next_float());
+                assert_ne!(c.next_float(), d.next_float());
+            }
         }
     }
 }
@@ -0,0 +1,473 @@
+//! Tables of results written as CSV or JSON Lines, with typed columns,
+//! floats at full precision (i.e., they read back to the same value) and
+//! a header of [`Metadata`] on how they were made.
+//!
+//! In CSV, the metadata are comment lines (`# name: value`) before the
+//! names of the columns. In JSON Lines, the first line is an object with
+//! the `metadata` and the `columns` (names and types), and each of the
+//! next ones is a row, as an object from the names of the columns to
+//! their values.
+
+pub mod metadata;
+
+pub use crate::metadata::Metadata;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Text,
+    Bool,
+}
+
+impl ColumnType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Text => "text",
+            ColumnType::Bool => "bool",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub kind: ColumnType,
+}
+
+impl Column {
+    pub fn new(name: &str, kind: ColumnType) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+        }
+    }
+    pub fn integer(name: &str) -> Self {
+        Self::new(name, ColumnType::Integer)
+    }
+    pub fn float(name: &str) -> Self {
+        Self::new(name, ColumnType::Float)
+    }
+    pub fn text(name: &str) -> Self {
+        Self::new(name, ColumnType::Text)
+    }
+    pub fn bool(name: &str) -> Self {
+        Self::new(name, ColumnType::Bool)
+    }
+}
+
+/// A value in a row
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Bool(bool),
+}
+
+impl Cell {
+    pub fn kind(&self) -> ColumnType {
+        match self {
+            Cell::Integer(_) => ColumnType::Integer,
+            Cell::Float(_) => ColumnType::Float,
+            Cell::Text(_) => ColumnType::Text,
+            Cell::Bool(_) => ColumnType::Bool,
+        }
+    }
+}
+
+impl From<i64> for Cell {
+    fn from(v: i64) -> Self {
+        Cell::Integer(v)
+    }
+}
+impl From<usize> for Cell {
+    fn from(v: usize) -> Self {
+        Cell::Integer(v as i64)
+    }
+}
+impl From<u64> for Cell {
+    fn from(v: u64) -> Self {
+        Cell::Integer(i64::try_from(v).expect("expecting an integer up to i64::MAX"))
+    }
+}
+impl From<f64> for Cell {
+    fn from(v: f64) -> Self {
+        Cell::Float(v)
+    }
+}
+impl From<&str> for Cell {
+    fn from(v: &str) -> Self {
+        Cell::Text(v.to_string())
+    }
+}
+impl From<String> for Cell {
+    fn from(v: String) -> Self {
+        Cell::Text(v)
+    }
+}
+impl From<bool> for Cell {
+    fn from(v: bool) -> Self {
+        Cell::Bool(v)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    JsonLines,
+}
+
+impl Format {
+    /// JSON Lines for `.jsonl` files, and CSV for anything else
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(e) if e.eq_ignore_ascii_case("jsonl") => Format::JsonLines,
+            _ => Format::Csv,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ResultsError {
+    Io(std::io::Error),
+    WrongCellCount {
+        expected: usize,
+        found: usize,
+    },
+    WrongType {
+        column: String,
+        expected: ColumnType,
+        found: ColumnType,
+    },
+}
+
+impl fmt::Display for ResultsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResultsError::Io(e) => write!(f, "{}", e),
+            ResultsError::WrongCellCount { expected, found } => {
+                write!(
+                    f,
+                    "expecting {} cells in a row... found {}",
+                    expected, found
+                )
+            }
+            ResultsError::WrongType {
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expecting {} in column '{}'... found {}",
+                expected.name(),
+                column,
+                found.name()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ResultsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ResultsError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ResultsError {
+    fn from(e: std::io::Error) -> Self {
+        ResultsError::Io(e)
+    }
+}
+
+/// Writes rows of typed cells, after a header with the metadata and the
+/// columns
+pub struct ResultsWriter<W: Write> {
+    writer: W,
+    format: Format,
+    columns: Vec<Column>,
+}
+
+impl ResultsWriter<BufWriter<File>> {
+    /// Creates the file at `path`, in the format given by its extension
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        columns: Vec<Column>,
+        metadata: &Metadata,
+    ) -> Result<Self, ResultsError> {
+        let format = Format::from_path(path.as_ref());
+        let file = BufWriter::new(File::create(path)?);
+        Self::new(file, format, columns, metadata)
+    }
+}
+
+impl<W: Write> ResultsWriter<W> {
+    pub fn new(
+        mut writer: W,
+        format: Format,
+        columns: Vec<Column>,
+        metadata: &Metadata,
+    ) -> Result<Self, ResultsError> {
+        match format {
+            Format::Csv => {
+                for (name, value) in metadata.entries() {
+                    writeln!(writer, "# {}: {}", name, value.replace('\n', " "))?;
+                }
+                let names: Vec<String> = columns.iter().map(|c| csv_text(&c.name)).collect();
+                writeln!(writer, "{}", names.join(","))?;
+            }
+            Format::JsonLines => {
+                let entries: Vec<String> = metadata
+                    .entries()
+                    .map(|(n, v)| format!("{}:{}", json_text(n), json_text(v)))
+                    .collect();
+                let columns: Vec<String> = columns
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{{\"name\":{},\"type\":\"{}\"}}",
+                            json_text(&c.name),
+                            c.kind.name()
+                        )
+                    })
+                    .collect();
+                writeln!(
+                    writer,
+                    "{{\"metadata\":{{{}}},\"columns\":[{}]}}",
+                    entries.join(","),
+                    columns.join(",")
+                )?;
+            }
+        }
+        Ok(Self {
+            writer,
+            format,
+            columns,
+        })
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Writes a row, which has a cell of the right type for each column
+    pub fn row(&mut self, cells: &[Cell]) -> Result<(), ResultsError> {
+        if cells.len() != self.columns.len() {
+            return Err(ResultsError::WrongCellCount {
+                expected: self.columns.len(),
+                found: cells.len(),
+            });
+        }
+        for (column, cell) in self.columns.iter().zip(cells) {
+            if column.kind != cell.kind() {
+                return Err(ResultsError::WrongType {
+                    column: column.name.clone(),
+                    expected: column.kind,
+                    found: cell.kind(),
+                });
+            }
+        }
+        let line: Vec<String> = match self.format {
+            Format::Csv => cells.iter().map(csv_cell).collect(),
+            Format::JsonLines => self
+                .columns
+                .iter()
+                .zip(cells)
+                .map(|(column, cell)| format!("{}:{}", json_text(&column.name), json_cell(cell)))
+                .collect(),
+        };
+        match self.format {
+            Format::Csv => writeln!(self.writer, "{}", line.join(","))?,
+            Format::JsonLines => writeln!(self.writer, "{{{}}}", line.join(","))?,
+        }
+        Ok(())
+    }
+
+    /// Writes a row of numbers, converting them to the type of each
+    /// column (e.g., a number of samples into an integer)
+    pub fn numbers(&mut self, values: &[f64]) -> Result<(), ResultsError> {
+        let cells: Vec<Cell> = self
+            .columns
+            .iter()
+            .zip(values)
+            .map(|(column, v)| match column.kind {
+                ColumnType::Integer if v.fract() == 0. => Cell::Integer(*v as i64),
+                ColumnType::Bool => Cell::Bool(*v != 0.),
+                _ => Cell::Float(*v),
+            })
+            .collect();
+        if cells.len() < values.len() {
+            return Err(ResultsError::WrongCellCount {
+                expected: self.columns.len(),
+                found: values.len(),
+            });
+        }
+        self.row(&cells)
+    }
+
+    /// Flushes the writer, and returns it
+    pub fn finish(mut self) -> Result<W, ResultsError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Floats are written with as many digits as needed to read them back
+/// exactly (and always with a `.` or an exponent, so they are not
+/// mistaken for integers)
+fn float(v: f64) -> String {
+    format!("{:?}", v)
+}
+
+fn csv_text(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) || s.starts_with('#') {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn csv_cell(cell: &Cell) -> String {
+    match cell {
+        Cell::Integer(v) => v.to_string(),
+        Cell::Float(v) => float(*v),
+        Cell::Text(s) => csv_text(s),
+        Cell::Bool(b) => b.to_string(),
+    }
+}
+
+fn json_text(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// JSON has no infinities or NaN, so they become `null`
+fn json_cell(cell: &Cell) -> String {
+    match cell {
+        Cell::Float(v) if !v.is_finite() => "null".to_string(),
+        Cell::Text(s) => json_text(s),
+        cell => csv_cell(cell),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<Column> {
+        vec![
+            Column::text("estimator"),
+            Column::integer("N"),
+            Column::float("error"),
+            Column::bool("converged"),
+        ]
+    }
+
+    fn write(format: Format, rows: &[Vec<Cell>]) -> String {
+        let metadata = Metadata::new().with("seed", 7).with("generator", "lcg");
+        let mut writer = ResultsWriter::new(Vec::new(), format, columns(), &metadata).unwrap();
+        for row in rows {
+            writer.row(row).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn csv() {
+        let error = 0.1 + 0.2;
+        let csv = write(
+            Format::Csv,
+            &[
+                vec!["A, \"B\"".into(), 16usize.into(), error.into(), true.into()],
+                vec!["C".into(), 32usize.into(), 1e-9.into(), false.into()],
+            ],
+        );
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "# seed: 7");
+        assert_eq!(lines[1], "# generator: lcg");
+        assert_eq!(lines[2], "estimator,N,error,converged");
+        assert_eq!(lines[3], "\"A, \"\"B\"\"\",16,0.30000000000000004,true");
+        assert_eq!(lines[4], "C,32,1e-9,false");
+        // Lossless
+        let found: f64 = lines[3].split(',').nth(3).unwrap().parse().unwrap();
+        assert_eq!(found, error);
+    }
+
+    #[test]
+    fn json_lines() {
+        let json = write(
+            Format::JsonLines,
+            &[vec![
+                "A\n".into(),
+                1usize.into(),
+                f64::NAN.into(),
+                true.into(),
+            ]],
+        );
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(
+            lines[0],
+            "{\"metadata\":{\"seed\":\"7\",\"generator\":\"lcg\"},\"columns\":[\
+             {\"name\":\"estimator\",\"type\":\"text\"},{\"name\":\"N\",\"type\":\"integer\"},\
+             {\"name\":\"error\",\"type\":\"float\"},{\"name\":\"converged\",\"type\":\"bool\"}]}"
+        );
+        assert_eq!(
+            lines[1],
+            "{\"estimator\":\"A\\n\",\"N\":1,\"error\":null,\"converged\":true}"
+        );
+    }
+
+    #[test]
+    fn typed_rows() {
+        let mut writer =
+            ResultsWriter::new(Vec::new(), Format::Csv, columns(), &Metadata::new()).unwrap();
+        assert!(matches!(
+            writer.row(&["A".into(), 1usize.into()]),
+            Err(ResultsError::WrongCellCount {
+                expected: 4,
+                found: 2
+            })
+        ));
+        let e = writer
+            .row(&["A".into(), 0.5.into(), 0.5.into(), true.into()])
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "expecting integer in column 'N'... found float"
+        );
+        // Numbers are converted to the type of their column
+        let mut writer = ResultsWriter::new(
+            Vec::new(),
+            Format::Csv,
+            vec![Column::integer("N"), Column::float("error")],
+            &Metadata::new(),
+        )
+        .unwrap();
+        writer.numbers(&[16., 2.]).unwrap();
+        assert!(writer.numbers(&[16.5, 2.]).is_err());
+        assert!(writer.numbers(&[16., 2., 3.]).is_err());
+        let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(csv, "N,error\n16,2.0\n");
+    }
+}
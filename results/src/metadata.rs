@@ -0,0 +1,125 @@
+//! Where a results file comes from, so it can be reproduced
+
+use std::fmt::Display;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Pairs of names and values, in the order in which they were added
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    entries: Vec<(String, String)>,
+}
+
+impl Metadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds (or replaces) an entry
+    pub fn with<V: Display>(mut self, name: &str, value: V) -> Self {
+        let value = value.to_string();
+        match self.entries.iter_mut().find(|(n, _)| n == name) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((name.to_string(), value)),
+        }
+        self
+    }
+
+    /// Adds the revision of the code (`git_revision`) and when it runs
+    /// (`timestamp`)
+    pub fn with_environment(self) -> Self {
+        self.with("git_revision", git_revision())
+            .with("timestamp", timestamp(SystemTime::now()))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+}
+
+/// The commit checked out in the working directory (with `-dirty` if it
+/// has changes), or `unknown` outside of a git repository
+pub fn git_revision() -> String {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+    };
+    match git(&["rev-parse", "HEAD"]) {
+        Some(revision) => match git(&["status", "--porcelain", "--untracked-files=no"]) {
+            Some(changes) if !changes.is_empty() => format!("{}-dirty", revision),
+            _ => revision,
+        },
+        None => "unknown".to_string(),
+    }
+}
+
+/// `time` in UTC, as in RFC 3339 (e.g., `2024-03-01T12:30:00Z`)
+pub fn timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, seconds) = ((seconds / 86400) as i64, seconds % 86400);
+    // From the days since 1970-01-01 to a date (Howard Hinnant's
+    // "civil_from_days"), with years starting in March
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamps() {
+        let at = |s| timestamp(UNIX_EPOCH + Duration::from_secs(s));
+        assert_eq!(at(0), "1970-01-01T00:00:00Z");
+        assert_eq!(at(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(at(1_709_296_200), "2024-03-01T12:30:00Z");
+        assert_eq!(at(4_102_444_799), "2099-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn entries() {
+        let metadata = Metadata::new()
+            .with("seed", 3)
+            .with("name", "mis")
+            .with("seed", 4);
+        assert_eq!(metadata.get("seed"), Some("4"));
+        let names: Vec<&str> = metadata.entries().map(|(n, _)| n).collect();
+        assert_eq!(names, vec!["seed", "name"]);
+
+        let metadata = metadata.with_environment();
+        assert!(metadata.get("git_revision").is_some());
+        assert!(metadata.get("timestamp").unwrap().ends_with('Z'));
+    }
+}
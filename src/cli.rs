@@ -0,0 +1,316 @@
+//! Parsing of command lines like `learning <command> [--option value]...`
+
+use crate::config::ConfigError;
+use results::ResultsError;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub enum CliError {
+    MissingCommand,
+    UnknownCommand(String),
+    UnknownOption {
+        command: &'static str,
+        option: String,
+    },
+    /// An option that needs a value is the last argument
+    MissingValue(String),
+    /// A required option is not given
+    MissingOption(&'static str),
+    InvalidValue {
+        option: String,
+        value: String,
+        expected: String,
+    },
+    Io(std::io::Error),
+    Config(ConfigError),
+    Results(ResultsError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::MissingCommand => write!(f, "expecting a command"),
+            CliError::UnknownCommand(c) => write!(f, "unknown command '{}'", c),
+            CliError::UnknownOption { command, option } => {
+                write!(f, "unknown option '{}' for '{}'", option, command)
+            }
+            CliError::MissingValue(o) => write!(f, "expecting a value after '--{}'", o),
+            CliError::MissingOption(o) => write!(f, "expecting option '--{}'", o),
+            CliError::InvalidValue {
+                option,
+                value,
+                expected,
+            } => write!(
+                f,
+                "expecting {} in '--{}'... found '{}'",
+                expected, option, value
+            ),
+            CliError::Io(e) => write!(f, "{}", e),
+            CliError::Config(e) => write!(f, "{}", e),
+            CliError::Results(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CliError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CliError::Io(e) => Some(e),
+            CliError::Config(e) => Some(e),
+            CliError::Results(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConfigError> for CliError {
+    fn from(e: ConfigError) -> Self {
+        CliError::Config(e)
+    }
+}
+
+impl From<ResultsError> for CliError {
+    fn from(e: ResultsError) -> Self {
+        CliError::Results(e)
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(e: std::io::Error) -> Self {
+        CliError::Io(e)
+    }
+}
+
+/// An option accepted by a command
+pub struct OptionSpec {
+    pub name: &'static str,
+    /// What the value looks like (e.g., `N`), or `None` for flags
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+/// A subcommand, and the options it accepts
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub about: &'static str,
+    pub options: &'static [OptionSpec],
+}
+
+impl CommandSpec {
+    /// A description of the command and its options
+    pub fn usage(&self) -> String {
+        let mut usage = format!("learning {} [OPTIONS]\n    {}\n", self.name, self.about);
+        for option in self.options {
+            let name = match option.value {
+                Some(v) => format!("--{} <{}>", option.name, v),
+                None => format!("--{}", option.name),
+            };
+            usage.push_str(&format!("    {:<24} {}\n", name, option.help));
+        }
+        usage
+    }
+}
+
+/// The options given to a command
+#[derive(Debug, Default)]
+pub struct Options {
+    command: &'static str,
+    values: Vec<(&'static str, String)>,
+    flags: Vec<&'static str>,
+}
+
+impl Options {
+    /// Parses `args` (i.e., what follows the name of the command), which
+    /// look like `--name value`, `--name=value` or `--flag`
+    pub fn parse(spec: &CommandSpec, args: &[String]) -> Result<Self, CliError> {
+        let mut options = Self {
+            command: spec.name,
+            ..Self::default()
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let unknown = || CliError::UnknownOption {
+                command: spec.name,
+                option: arg.clone(),
+            };
+            let stripped = arg.strip_prefix("--").ok_or_else(unknown)?;
+            let (name, inline) = match stripped.split_once('=') {
+                Some((n, v)) => (n, Some(v.to_string())),
+                None => (stripped, None),
+            };
+            let option = spec
+                .options
+                .iter()
+                .find(|o| o.name == name)
+                .ok_or_else(unknown)?;
+            match (option.value, inline) {
+                (None, None) => options.flags.push(option.name),
+                (None, Some(_)) => {
+                    return Err(CliError::InvalidValue {
+                        option: name.to_string(),
+                        value: arg.clone(),
+                        expected: "no value".to_string(),
+                    })
+                }
+                (Some(_), Some(v)) => options.values.push((option.name, v)),
+                (Some(_), None) => {
+                    let v = args
+                        .next()
+                        .ok_or_else(|| CliError::MissingValue(name.to_string()))?;
+                    options.values.push((option.name, v.clone()));
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    /// The name of the command
+    pub fn command(&self) -> &'static str {
+        self.command
+    }
+
+    /// The options as they were given (e.g., `--seed=3 --exact`), to
+    /// record how results were made
+    pub fn arguments(&self) -> String {
+        let values = self.values.iter().map(|(n, v)| format!("--{}={}", n, v));
+        let flags = self.flags.iter().map(|f| format!("--{}", f));
+        values.chain(flags).collect::<Vec<_>>().join(" ")
+    }
+
+    /// The value of option `name` (the last one, if it is repeated)
+    pub fn string(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains(&name)
+    }
+
+    /// The value of option `name`, parsed, or `default` if it is not given
+    pub fn value<T: FromStr>(&self, name: &str, default: T) -> Result<T, CliError> {
+        match self.string(name) {
+            None => Ok(default),
+            Some(v) => v.parse().map_err(|_| CliError::InvalidValue {
+                option: name.to_string(),
+                value: v.to_string(),
+                expected: format!("a value of type {}", std::any::type_name::<T>()),
+            }),
+        }
+    }
+
+    /// A comma-separated list of numbers, or `default`
+    pub fn list(&self, name: &str, default: &[f64]) -> Result<Vec<f64>, CliError> {
+        match self.string(name) {
+            None => Ok(default.to_vec()),
+            Some(v) => v
+                .split(',')
+                .map(|x| x.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| CliError::InvalidValue {
+                    option: name.to_string(),
+                    value: v.to_string(),
+                    expected: "a comma-separated list of numbers".to_string(),
+                }),
+        }
+    }
+
+    /// One of `choices` (the first one by default)
+    pub fn choice(&self, name: &str, choices: &[&'static str]) -> Result<&'static str, CliError> {
+        match self.string(name) {
+            None => Ok(choices[0]),
+            Some(v) => {
+                choices
+                    .iter()
+                    .find(|c| **c == v)
+                    .copied()
+                    .ok_or_else(|| CliError::InvalidValue {
+                        option: name.to_string(),
+                        value: v.to_string(),
+                        expected: format!("one of {}", choices.join(", ")),
+                    })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: CommandSpec = CommandSpec {
+        name: "test",
+        about: "A command for testing",
+        options: &[
+            OptionSpec {
+                name: "seed",
+                value: Some("N"),
+                help: "The seed",
+            },
+            OptionSpec {
+                name: "format",
+                value: Some("FORMAT"),
+                help: "csv or svg",
+            },
+            OptionSpec {
+                name: "exact",
+                value: None,
+                help: "A flag",
+            },
+        ],
+    };
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parsing() {
+        let options = Options::parse(&SPEC, &args("--seed 3 --exact --format=svg")).unwrap();
+        assert_eq!(options.value("seed", 0u64).unwrap(), 3);
+        assert!(options.flag("exact"));
+        assert_eq!(options.choice("format", &["csv", "svg"]).unwrap(), "svg");
+        assert_eq!(options.command(), "test");
+        assert_eq!(options.arguments(), "--seed=3 --format=svg --exact");
+
+        let options = Options::parse(&SPEC, &[]).unwrap();
+        assert_eq!(options.value("seed", 7u64).unwrap(), 7);
+        assert!(!options.flag("exact"));
+        assert_eq!(options.choice("format", &["csv", "svg"]).unwrap(), "csv");
+        assert_eq!(options.list("seed", &[1., 2.]).unwrap(), vec![1., 2.]);
+
+        let options = Options::parse(&SPEC, &args("--format 0.1,0.5 --seed 1 --seed 2")).unwrap();
+        assert_eq!(options.list("format", &[]).unwrap(), vec![0.1, 0.5]);
+        assert_eq!(options.value("seed", 0u64).unwrap(), 2);
+        assert!(SPEC.usage().contains("--seed <N>"));
+    }
+
+    #[test]
+    fn errors() {
+        let error = |s: &str| Options::parse(&SPEC, &args(s)).unwrap_err();
+        assert!(matches!(
+            error("--samples 3"),
+            CliError::UnknownOption { option, .. } if option == "--samples"
+        ));
+        assert!(matches!(error("seed"), CliError::UnknownOption { .. }));
+        assert!(matches!(error("--seed"), CliError::MissingValue(o) if o == "seed"));
+        assert!(matches!(error("--exact=1"), CliError::InvalidValue { .. }));
+
+        let options = Options::parse(&SPEC, &args("--seed x --format pdf")).unwrap();
+        let e = options.value("seed", 0u64).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "expecting a value of type u64 in '--seed'... found 'x'"
+        );
+        let e = options.choice("format", &["csv", "svg"]).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "expecting one of csv, svg in '--format'... found 'pdf'"
+        );
+        assert!(options.list("seed", &[]).is_err());
+    }
+}
@@ -0,0 +1,805 @@
+//! The experiments of each lesson, as subcommands of `learning`
+
+use crate::cli::{CliError, CommandSpec, OptionSpec, Options};
+use crate::experiment::{self, Experiment, InvalidPdf, PdfSpec};
+use image::{Colormap, Image, Tonemap};
+use lesson1_sampling::step_pdf::DiscretePdf;
+use lesson2_montecarlo::convergence::ConvergenceStudy;
+use lesson2_montecarlo::importance_sampling::{self, Importance, Uniform, EXP_INTEGRAL};
+use lesson2_montecarlo::integrands::{Quad, Triangle};
+use lesson2_montecarlo::montecarlo_integrable::MontecarloIntegrable;
+use lesson2_montecarlo::multiple_importance_2::{self as mis, MISHeuristic, MISSingle, MIS};
+use lesson2_montecarlo::stratified::{QuasiMonteCarlo, Stratified};
+use lesson3_russian_roulette::MIS as RouletteMIS;
+use lesson4_hemispheres::projection::{direction_histogram, direction_image};
+use lesson4_hemispheres::{
+    cosine_weighted_hemisphere_pdf, sample_cosine_weighted_horizontal_hemisphere,
+    sample_uniform_hemisphere, uniform_hemisphere_pdf, Vector3D,
+};
+use plot::{Chart, Histogram, Series};
+use rand::Rng;
+use results::{Column, Format, Metadata, ResultsWriter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+const SEED: OptionSpec = OptionSpec {
+    name: "seed",
+    value: Some("N"),
+    help: "Seed of the random numbers (default: 0)",
+};
+const OUTPUT: OptionSpec = OptionSpec {
+    name: "output",
+    value: Some("PATH"),
+    help: "Where to write the results (default: standard output)",
+};
+const CHART_FORMAT: OptionSpec = OptionSpec {
+    name: "format",
+    value: Some("FORMAT"),
+    help: "csv, jsonl, svg or ascii (default: csv)",
+};
+const MAX_POW: OptionSpec = OptionSpec {
+    name: "max-pow",
+    value: Some("P"),
+    help: "Run with N = 2, 4, ..., 2^P samples",
+};
+
+pub const COMMANDS: [CommandSpec; 6] = [
+    CommandSpec {
+        name: "sample-pdf",
+        about: "Draws samples from a piecewise constant PDF over [0, 1] (lesson 1)",
+        options: &[
+            OptionSpec {
+                name: "steps",
+                value: Some("X,..."),
+                help: "Upper bound of each step (default: 0.45,0.55,1)",
+            },
+            OptionSpec {
+                name: "pdfs",
+                value: Some("P,..."),
+                help: "Density of each step (default: 0.1,9.1,0.1)",
+            },
+            OptionSpec {
+                name: "samples",
+                value: Some("N"),
+                help: "Number of samples (default: 8000)",
+            },
+            OptionSpec {
+                name: "bins",
+                value: Some("N"),
+                help: "Bins of the histogram in charts (default: 40)",
+            },
+            OptionSpec {
+                name: "table",
+                value: Some("TABLE"),
+                help: "samples, pdf, cdf or inv-cdf (default: samples)",
+            },
+            SEED,
+            CHART_FORMAT,
+            OUTPUT,
+        ],
+    },
+    CommandSpec {
+        name: "integrate",
+        about: "Mean, bias, variance and RMSE of Monte Carlo integration as N grows (lesson 2)",
+        options: &[
+            OptionSpec {
+                name: "integrand",
+                value: Some("F"),
+                help: "exp (a spike at 0.5), triangle or quad (default: exp)",
+            },
+            OptionSpec {
+                name: "sampler",
+                value: Some("S"),
+                help: "uniform, importance, bad-importance, stratified or qmc (default: uniform)",
+            },
+            OptionSpec {
+                name: "trials",
+                value: Some("M"),
+                help: "Independent runs for each N (default: 16)",
+            },
+            MAX_POW,
+            SEED,
+            CHART_FORMAT,
+            OUTPUT,
+        ],
+    },
+    CommandSpec {
+        name: "mis",
+        about: "Error of multiple importance sampling as N grows (lesson 2)",
+        options: &[MAX_POW, SEED, CHART_FORMAT, OUTPUT],
+    },
+    CommandSpec {
+        name: "roulette",
+        about: "Error and cost of Russian roulette as N grows (lesson 3)",
+        options: &[MAX_POW, SEED, CHART_FORMAT, OUTPUT],
+    },
+    CommandSpec {
+        name: "hemisphere",
+        about: "Density of directions sampled over the hemisphere (lesson 4)",
+        options: &[
+            OptionSpec {
+                name: "sampler",
+                value: Some("S"),
+                help: "cosine or uniform (default: cosine)",
+            },
+            OptionSpec {
+                name: "samples",
+                value: Some("N"),
+                help: "Number of samples (default: 100000)",
+            },
+            OptionSpec {
+                name: "resolution",
+                value: Some("N"),
+                help: "Pixels per side of the image (default: 64)",
+            },
+            OptionSpec {
+                name: "exact",
+                value: None,
+                help: "Show the PDF instead of a histogram of samples",
+            },
+            SEED,
+            OptionSpec {
+                name: "format",
+                value: Some("FORMAT"),
+                help: "ppm (false color), pfm, hdr, csv or jsonl (default: ppm)",
+            },
+            OUTPUT,
+        ],
+    },
+    CommandSpec {
+        name: "run",
+        about: "Runs the experiment described in a TOML or JSON file, e.g., experiments/mis.toml",
+        options: &[
+            OptionSpec {
+                name: "config",
+                value: Some("PATH"),
+                help: "The description of the experiment",
+            },
+            OptionSpec {
+                name: "output",
+                value: Some("PATH"),
+                help: "Where to write the results (default: as in the file, or standard output)",
+            },
+            OptionSpec {
+                name: "format",
+                value: Some("FORMAT"),
+                help: "csv or jsonl (default: by the extension of the output, or csv)",
+            },
+        ],
+    },
+];
+
+/// A description of every command
+pub fn usage() -> String {
+    let mut usage = String::from("Usage: learning <COMMAND> [OPTIONS]\n\n");
+    for command in COMMANDS.iter() {
+        usage.push_str(&command.usage());
+        usage.push('\n');
+    }
+    usage
+}
+
+/// Runs the command in `args` (i.e., the arguments after the program name)
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    let (name, rest) = args.split_first().ok_or(CliError::MissingCommand)?;
+    if name == "help" || name == "--help" {
+        print!("{}", usage());
+        return Ok(());
+    }
+    let spec = COMMANDS
+        .iter()
+        .find(|c| c.name == name)
+        .ok_or_else(|| CliError::UnknownCommand(name.clone()))?;
+    if rest.iter().any(|a| a == "--help") {
+        print!("{}", spec.usage());
+        return Ok(());
+    }
+    let options = Options::parse(spec, rest)?;
+    match spec.name {
+        "sample-pdf" => sample_pdf(&options),
+        "integrate" => integrate(&options),
+        "mis" => multiple_importance(&options),
+        "roulette" => roulette(&options),
+        "hemisphere" => hemisphere(&options),
+        "run" => run_experiment(&options),
+        _ => unreachable!(),
+    }
+}
+
+/// Writes `bytes` to `--output`, or to the standard output
+fn write_output(options: &Options, bytes: &[u8]) -> Result<(), CliError> {
+    match options.string("output") {
+        Some(path) => File::create(path)?.write_all(bytes)?,
+        None => std::io::stdout().write_all(bytes)?,
+    }
+    Ok(())
+}
+
+/// How the results of `options` were made, including the seed even if
+/// it was left to its default
+fn metadata(options: &Options) -> Result<Metadata, CliError> {
+    Ok(Metadata::new()
+        .with("command", options.command())
+        .with("arguments", options.arguments())
+        .with("generator", rand::GENERATOR)
+        .with("seed", options.value("seed", 0u64)?)
+        .with_environment())
+}
+
+/// The columns of a table whose first column is `first`, and the rest
+/// are floats
+fn columns(first: Column, rest: &[&str]) -> Vec<Column> {
+    std::iter::once(first)
+        .chain(rest.iter().map(|name| Column::float(name)))
+        .collect()
+}
+
+/// Writes a table to `--output` (or the standard output) in `format`,
+/// which is either `csv` or `jsonl`
+fn write_table(
+    options: &Options,
+    format: &str,
+    columns: Vec<Column>,
+    rows: &[Vec<f64>],
+) -> Result<(), CliError> {
+    let format = match format {
+        "jsonl" => Format::JsonLines,
+        _ => Format::Csv,
+    };
+    let writer: Box<dyn Write> = match options.string("output") {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut writer = ResultsWriter::new(writer, format, columns, &metadata(options)?)?;
+    for row in rows {
+        writer.numbers(row)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Writes the rows of a table as CSV or JSON Lines, or `chart` as SVG
+/// or ASCII art
+fn write_results<F: FnOnce() -> Chart>(
+    options: &Options,
+    columns: Vec<Column>,
+    rows: &[Vec<f64>],
+    chart: F,
+) -> Result<(), CliError> {
+    let text = match options.choice("format", &["csv", "jsonl", "svg", "ascii"])? {
+        "svg" => plot::svg::render(&chart()),
+        "ascii" => chart().to_ascii(72, 20),
+        format => return write_table(options, format, columns, rows),
+    };
+    write_output(options, text.as_bytes())
+}
+
+/// The number of samples of each run, up to `2^max_pow`
+fn sample_counts(options: &Options, default: u32) -> Result<Vec<usize>, CliError> {
+    let max_pow: u32 = options.value("max-pow", default)?;
+    if !(1..=40).contains(&max_pow) {
+        return Err(CliError::InvalidValue {
+            option: "max-pow".to_string(),
+            value: max_pow.to_string(),
+            expected: "a power between 1 and 40".to_string(),
+        });
+    }
+    Ok((1..=max_pow).map(|p| 2_usize.pow(p)).collect())
+}
+
+/// The series of column `column` against the first one
+fn column(label: &str, rows: &[Vec<f64>], column: usize) -> Series {
+    Series::new(label, rows.iter().map(|r| (r[0], r[column])).collect())
+}
+
+fn relative_error(found: f64, expected: f64) -> f64 {
+    (found - expected).abs() / expected
+}
+
+fn sample_pdf(options: &Options) -> Result<(), CliError> {
+    let steps = options.list("steps", &[0.45, 0.55, 1.0])?;
+    let pdfs = options.list("pdfs", &[0.1, 9.1, 0.1])?;
+    let pdf = PdfSpec::new(steps, pdfs)
+        .map_err(|e| {
+            let option = match e {
+                InvalidPdf::Steps => "steps",
+                InvalidPdf::Densities => "pdfs",
+            };
+            CliError::InvalidValue {
+                option: option.to_string(),
+                value: options.string(option).unwrap_or_default().to_string(),
+                expected: e.expected().to_string(),
+            }
+        })?
+        .pdf();
+
+    let table = options.choice("table", &["samples", "pdf", "cdf", "inv-cdf"])?;
+    if table != "samples" {
+        let xs = (0..100).map(|i| i as f64 / 100.0);
+        let (names, rows): (&[&str], Vec<Vec<f64>>) = match table {
+            "pdf" => (&["pdf"], xs.map(|x| vec![x, pdf.pdf(x)]).collect()),
+            "cdf" => (
+                &["CDF", "PDF"],
+                xs.map(|x| {
+                    let (cdf, p) = pdf.cdf(x);
+                    vec![x, cdf, p]
+                })
+                .collect(),
+            ),
+            _ => (
+                &["CDF-1", "PDF"],
+                xs.map(|x| {
+                    let (inv, p) = pdf.inv_cdf(x);
+                    vec![x, inv, p]
+                })
+                .collect(),
+            ),
+        };
+        return write_results(options, columns(Column::float("x"), names), &rows, || {
+            let mut chart = Chart::new(table, "x", table);
+            chart.series.push(column(table, &rows, 1));
+            chart
+        });
+    }
+
+    let n: usize = options.value("samples", 8000)?;
+    let bins: usize = options.value("bins", 40)?;
+    let mut rng = Rng::with_stream(options.value("seed", 0)?, 0);
+    let rows: Vec<Vec<f64>> = (0..n)
+        .map(|_| {
+            let (x, p) = pdf.sample(&mut rng);
+            vec![x, p]
+        })
+        .collect();
+    write_results(
+        options,
+        columns(Column::float("x"), &["pdf"]),
+        &rows,
+        || {
+            let histogram = Histogram::from_samples(0., 1., bins.max(1), rows.iter().map(|r| r[0]));
+            Chart::histogram("Samples of a step PDF", histogram, |x| pdf.pdf(x))
+        },
+    )
+}
+
+fn integrate(options: &Options) -> Result<(), CliError> {
+    let integrand = options.choice("integrand", &["exp", "triangle", "quad"])?;
+    let sampler = options.choice(
+        "sampler",
+        &[
+            "uniform",
+            "importance",
+            "bad-importance",
+            "stratified",
+            "qmc",
+        ],
+    )?;
+    let triangle = Triangle { side: 4.0 };
+    let quad = Quad {
+        range: 4.0,
+        a: 1.0,
+        b: 2.121,
+        c: 3.0,
+    };
+    let expected = match integrand {
+        "exp" => EXP_INTEGRAL,
+        "triangle" => triangle.analytically_integrate(),
+        _ => quad.analytically_integrate(),
+    };
+    // The integrand over [0, 1], for samplers of the unit interval
+    let unit: Box<dyn Fn(f64) -> f64> = match integrand {
+        "exp" => Box::new(importance_sampling::exp),
+        "triangle" => Box::new(move |u| triangle.side * triangle.eval(triangle.side * u)),
+        _ => Box::new(move |u| quad.range * quad.eval(quad.range * u)),
+    };
+    let estimator: Box<dyn MontecarloIntegrable<T = f64>> = match (integrand, sampler) {
+        (_, "stratified") => Box::new(Stratified { f: unit }),
+        (_, "qmc") => Box::new(QuasiMonteCarlo { f: unit }),
+        ("exp", "uniform") => Box::new(Uniform {}),
+        ("exp", "importance") => Box::new(Importance {
+            pdf: DiscretePdf::new(0.0, vec![0.45, 0.55, 1.0], vec![0.1, 9.1, 0.1]),
+        }),
+        ("exp", _) => Box::new(Importance {
+            pdf: DiscretePdf::new(0.0, vec![0.45, 0.55, 1.0], vec![1.1, 0.1, 1.1]),
+        }),
+        ("triangle", "uniform") => Box::new(triangle),
+        ("quad", "uniform") => Box::new(quad),
+        _ => {
+            return Err(CliError::InvalidValue {
+                option: "sampler".to_string(),
+                value: sampler.to_string(),
+                expected: format!("uniform, as {} has no importance sampler", integrand),
+            })
+        }
+    };
+
+    let trials: usize = options.value("trials", 16)?;
+    if trials == 0 {
+        return Err(CliError::InvalidValue {
+            option: "trials".to_string(),
+            value: "0".to_string(),
+            expected: "at least one trial".to_string(),
+        });
+    }
+    let study = ConvergenceStudy::new(sample_counts(options, 20)?, trials)
+        .with_seed(options.value("seed", 0)?);
+    let convergence = study.run(estimator.as_ref(), expected);
+    let rows: Vec<Vec<f64>> = convergence
+        .levels
+        .iter()
+        .map(|l| {
+            vec![
+                l.n as f64,
+                l.mean,
+                l.bias,
+                l.variance,
+                l.rmse,
+                l.quantile(0.05),
+                l.quantile(0.5),
+                l.quantile(0.95),
+            ]
+        })
+        .collect();
+    let names = ["mean", "bias", "variance", "RMSE", "q05", "median", "q95"];
+    write_results(
+        options,
+        columns(Column::integer("N"), &names),
+        &rows,
+        || {
+            let label = format!(
+                "{}, {} (rate {:.2})",
+                integrand,
+                sampler,
+                convergence.rate()
+            );
+            let relative = rows.iter().map(|r| (r[0], r[4] / expected)).collect();
+            let mut chart = Chart::convergence(
+                "Monte Carlo integration",
+                vec![Series::new(&label, relative)],
+            );
+            chart.y_label = "relative RMSE".to_string();
+            chart
+        },
+    )
+}
+
+fn multiple_importance(options: &Options) -> Result<(), CliError> {
+    let (fa, fb) = (mis::fa(), mis::fb());
+    let single = |sampling: DiscretePdf| MISSingle {
+        a: fa.clone(),
+        b: fb.clone(),
+        sampling,
+    };
+    let combined = |heuristic| MIS {
+        a: fa.clone(),
+        b: fb.clone(),
+        na: 9,
+        nb: 5,
+        heuristic,
+    };
+    let labels = ["Uniform", "A", "B", "Balanced MIS", "Power MIS"];
+    let estimators: [Box<dyn MontecarloIntegrable<T = f64>>; 5] = [
+        Box::new(single(DiscretePdf::new(0.0, vec![1.0], vec![1.]))),
+        Box::new(single(fa.clone())),
+        Box::new(single(fb.clone())),
+        Box::new(combined(MISHeuristic::Balance)),
+        Box::new(combined(MISHeuristic::Power)),
+    ];
+
+    let seed = options.value("seed", 0)?;
+    let rows: Vec<Vec<f64>> = sample_counts(options, 15)?
+        .into_iter()
+        .enumerate()
+        .map(|(run, n)| {
+            let mut row = vec![n as f64];
+            for (i, estimator) in estimators.iter().enumerate() {
+                let found =
+                    estimator.integrate(n, Rng::with_stream(seed, (run * labels.len() + i) as u64));
+                row.push(relative_error(found, mis::EXPECTED));
+            }
+            row
+        })
+        .collect();
+    write_results(
+        options,
+        columns(Column::integer("N"), &labels),
+        &rows,
+        || {
+            let series = labels
+                .iter()
+                .enumerate()
+                .map(|(i, label)| column(label, &rows, i + 1))
+                .collect();
+            Chart::convergence("Multiple importance sampling", series)
+        },
+    )
+}
+
+fn roulette(options: &Options) -> Result<(), CliError> {
+    let uniform = DiscretePdf::new(0.0, vec![1.0], vec![1.]);
+    let plain = RouletteMIS::new(mis::fa(), mis::fb(), uniform.clone(), false);
+    let roulette = RouletteMIS::new(mis::fa(), mis::fb(), uniform, true);
+
+    let seed = options.value("seed", 0)?;
+    let rows: Vec<Vec<f64>> = sample_counts(options, 15)?
+        .into_iter()
+        .enumerate()
+        .map(|(run, n)| {
+            let mut row = vec![n as f64];
+            let mut costs = Vec::new();
+            for (i, estimator) in [&plain, &roulette].iter().enumerate() {
+                estimator.cost().reset();
+                let found = estimator.integrate(n, Rng::with_stream(seed, (2 * run + i) as u64));
+                row.push(relative_error(found, mis::EXPECTED));
+                costs.push(estimator.cost().total());
+            }
+            row.extend(costs);
+            row
+        })
+        .collect();
+    let names = [
+        "no-roulette",
+        "roulette",
+        "cost no-roulette",
+        "cost roulette",
+    ];
+    write_results(
+        options,
+        columns(Column::integer("N"), &names),
+        &rows,
+        || {
+            Chart::convergence(
+                "Russian roulette",
+                vec![
+                    column("no roulette", &rows, 1),
+                    column("roulette", &rows, 2),
+                ],
+            )
+        },
+    )
+}
+
+fn hemisphere(options: &Options) -> Result<(), CliError> {
+    let up = Vector3D::new(0., 0., 1.);
+    let cosine = options.choice("sampler", &["cosine", "uniform"])? == "cosine";
+    let sample = |u| {
+        if cosine {
+            sample_cosine_weighted_horizontal_hemisphere(u)
+        } else {
+            sample_uniform_hemisphere(u)
+        }
+    };
+    let pdf = |d| {
+        if cosine {
+            cosine_weighted_hemisphere_pdf(d, up)
+        } else {
+            uniform_hemisphere_pdf(d, up)
+        }
+    };
+    let n: usize = options.value("samples", 100_000)?;
+    let resolution: usize = options.value("resolution", 64)?;
+    if resolution == 0 {
+        return Err(CliError::InvalidValue {
+            option: "resolution".to_string(),
+            value: "0".to_string(),
+            expected: "at least one pixel".to_string(),
+        });
+    }
+    let mut rng = Rng::with_stream(options.value("seed", 0)?, 0);
+    let directions: Vec<Vector3D> = (0..n)
+        .map(|_| sample((rng.next_float(), rng.next_float())))
+        .collect();
+
+    let format = options.choice("format", &["ppm", "pfm", "hdr", "csv", "jsonl"])?;
+    if format == "csv" || format == "jsonl" {
+        let rows: Vec<Vec<f64>> = directions.iter().map(|d| vec![d.x, d.y, d.z]).collect();
+        return write_table(
+            options,
+            format,
+            columns(Column::float("x"), &["y", "z"]),
+            &rows,
+        );
+    }
+    let image: Image = if options.flag("exact") {
+        direction_image(resolution, pdf)
+    } else {
+        direction_histogram(resolution, directions)
+    };
+    let mut bytes = Vec::new();
+    match format {
+        "ppm" => {
+            // The same colors for the histogram and the PDF
+            let max = direction_image(resolution, pdf).range().map_or(1., |r| r.1);
+            image
+                .false_color(Colormap::Viridis, Some((0., max)))
+                .write_ppm(&mut bytes, 1., Tonemap::Linear)?
+        }
+        "pfm" => image.write_pfm(&mut bytes)?,
+        _ => image.write_hdr(&mut bytes)?,
+    }
+    write_output(options, &bytes)
+}
+
+fn run_experiment(options: &Options) -> Result<(), CliError> {
+    let path = options
+        .string("config")
+        .ok_or(CliError::MissingOption("config"))?;
+    let experiment = Experiment::load(path)?;
+    let records = experiment.run();
+    let metadata = experiment
+        .metadata()
+        .with("arguments", options.arguments())
+        .with_environment();
+    let output = options.string("output").or(experiment.output.as_deref());
+    let format = match options.string("format") {
+        Some(_) => options.choice("format", &["csv", "jsonl"])?,
+        None if output.is_some_and(|o| o.ends_with(".jsonl")) => "jsonl",
+        None => "csv",
+    };
+    let format = match format {
+        "jsonl" => Format::JsonLines,
+        _ => Format::Csv,
+    };
+    let writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    experiment::write_records(writer, format, &metadata, &records)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `args`, writing into a temporary file, and returns what was written
+    fn output(args: &str) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!(
+            "learning-{}-{}",
+            std::process::id(),
+            args.replace(|c: char| !c.is_alphanumeric(), "_")
+        ));
+        let mut args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        args.push("--output".to_string());
+        args.push(path.to_string_lossy().to_string());
+        run(&args).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    /// The lines written by `args`, without the metadata
+    fn lines(args: &str) -> Vec<String> {
+        String::from_utf8(output(args))
+            .unwrap()
+            .lines()
+            .filter(|l| !l.starts_with('#'))
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn sample_pdf() {
+        let samples = lines("sample-pdf --samples 100 --seed 3");
+        assert_eq!(samples[0], "x,pdf");
+        assert_eq!(samples.len(), 101);
+        // Seeded runs can be reproduced
+        assert_eq!(samples, lines("sample-pdf --samples 100 --seed 3"));
+        assert_ne!(samples, lines("sample-pdf --samples 100 --seed 4"));
+
+        let pdf = lines("sample-pdf --table pdf --steps 0.5,1 --pdfs 2,0");
+        assert_eq!(pdf[0], "x,pdf");
+        assert_eq!(pdf[1], "0.0,2.0");
+        assert_eq!(pdf[100], "0.99,0.0");
+
+        let svg = lines("sample-pdf --format svg --samples 100");
+        assert!(svg[0].starts_with("<svg"));
+        let ascii = lines("sample-pdf --format ascii --samples 100");
+        assert_eq!(ascii[0], "Samples of a step PDF");
+
+        let invalid = |arguments: &str| {
+            let arguments: Vec<String> = arguments.split(' ').map(String::from).collect();
+            match run(&arguments) {
+                Err(CliError::InvalidValue { option, .. }) => option,
+                _ => panic!("expecting an invalid value for {:?}", arguments),
+            }
+        };
+        assert_eq!(invalid("sample-pdf --pdfs 1,2,1"), "pdfs");
+        // NaN fails the checks too
+        assert_eq!(invalid("sample-pdf --pdfs NaN,NaN,NaN"), "pdfs");
+        assert_eq!(invalid("sample-pdf --pdfs 1,1,inf"), "pdfs");
+        assert_eq!(
+            invalid("sample-pdf --steps NaN,0.5,1 --pdfs 1,1,1"),
+            "steps"
+        );
+        assert_eq!(
+            invalid("sample-pdf --steps 0.5,NaN,1 --pdfs 1,1,1"),
+            "steps"
+        );
+    }
+
+    #[test]
+    fn convergence() {
+        let integrate = lines("integrate --sampler importance --max-pow 12 --trials 4");
+        assert_eq!(integrate[0], "N,mean,bias,variance,RMSE,q05,median,q95");
+        assert_eq!(integrate.len(), 13);
+        let last: Vec<f64> = integrate[12]
+            .split(',')
+            .map(|v| v.parse().unwrap())
+            .collect();
+        assert_eq!(last[0], 4096.);
+        assert!(last[4] / EXP_INTEGRAL < 0.05);
+        assert!(last[5] <= last[6] && last[6] <= last[7]);
+        let qmc = lines("integrate --integrand triangle --sampler qmc --max-pow 8");
+        let last: Vec<f64> = qmc[8].split(',').map(|v| v.parse().unwrap()).collect();
+        assert!(last[4] < 0.1, "{:?}", last);
+        assert!(run(&[
+            "integrate".to_string(),
+            "--integrand=quad".into(),
+            "--sampler=importance".into()
+        ])
+        .is_err());
+
+        let mis = lines("mis --max-pow 4");
+        assert_eq!(mis[0], "N,Uniform,A,B,Balanced MIS,Power MIS");
+        assert_eq!(mis.len(), 5);
+
+        let roulette = lines("roulette --max-pow 8 --seed 1");
+        assert_eq!(
+            roulette[0],
+            "N,no-roulette,roulette,cost no-roulette,cost roulette"
+        );
+        let last: Vec<f64> = roulette[8].split(',').map(|v| v.parse().unwrap()).collect();
+        assert_eq!(last[3], 256. * 11.);
+        assert!(last[4] < last[3]);
+    }
+
+    #[test]
+    fn metadata() {
+        let csv = String::from_utf8(output("mis --max-pow 2 --seed 5")).unwrap();
+        let header: Vec<&str> = csv.lines().collect();
+        assert_eq!(header[0], "# command: mis");
+        assert!(header[1].starts_with("# arguments: --max-pow=2 --seed=5 --output="));
+        assert!(header.contains(&"# seed: 5"));
+        assert!(header.iter().any(|l| l.starts_with("# git_revision: ")));
+        assert!(header.iter().any(|l| l.starts_with("# timestamp: ")));
+
+        let jsonl = lines("mis --max-pow 2 --format jsonl");
+        assert_eq!(jsonl.len(), 3);
+        assert!(jsonl[0].starts_with("{\"metadata\":{\"command\":\"mis\""));
+        assert!(jsonl[0].contains("\"seed\":\"0\""), "{}", jsonl[0]);
+        assert!(jsonl[1].starts_with("{\"N\":2,\"Uniform\":"));
+    }
+
+    #[test]
+    fn hemisphere_images() {
+        let ppm = output("hemisphere --samples 1000 --resolution 8");
+        assert!(ppm.starts_with(b"P6\n8 8\n255\n"));
+        let pfm = output("hemisphere --exact --resolution 4 --format pfm");
+        assert_eq!(pfm.len(), b"Pf\n4 4\n-1.0\n".len() + 4 * 16);
+        let hdr = output("hemisphere --sampler uniform --format hdr --resolution 4");
+        assert!(hdr.starts_with(b"#?RADIANCE"));
+        let csv = lines("hemisphere --samples 10 --format csv");
+        assert_eq!(csv.len(), 11);
+    }
+
+    #[test]
+    fn commands() {
+        assert!(matches!(run(&[]), Err(CliError::MissingCommand)));
+        assert!(matches!(
+            run(&["plot".to_string()]),
+            Err(CliError::UnknownCommand(_))
+        ));
+        for command in COMMANDS.iter() {
+            assert!(usage().contains(command.about));
+        }
+        assert!(matches!(
+            run(&["run".to_string()]),
+            Err(CliError::MissingOption("config"))
+        ));
+        assert!(matches!(
+            run(&["run".to_string(), "--config=missing.toml".into()]),
+            Err(CliError::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,770 @@
+//! A reader of configuration files, written in a subset of TOML or in JSON.
+//!
+//! The TOML subset has `key = value` pairs, `[tables]`, `[[arrays.of.tables]]`
+//! and comments. Values are strings, numbers, booleans, arrays (which may
+//! span several lines) and inline tables (`{ key = value }`). Dates, literal
+//! strings and dotted keys outside of headers are not supported.
+
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// The entries, in the order in which they were written
+    Table(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "a boolean",
+            Value::Number(_) => "a number",
+            Value::String(_) => "a string",
+            Value::Array(_) => "an array",
+            Value::Table(_) => "a table",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// JSON for `.json` files, and TOML for anything else
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(e) if e.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Toml,
+        }
+    }
+}
+
+/// What is wrong with the syntax of a configuration file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxErrorKind {
+    UnexpectedEnd,
+    UnexpectedCharacter {
+        expected: &'static str,
+        found: char,
+    },
+    InvalidNumber(String),
+    InvalidEscape(char),
+    /// A key that is defined twice in the same table
+    DuplicateKey(String),
+    /// A header or an inline table goes through a key that is not a table
+    NotATable(String),
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Syntax {
+        /// Starting at 1
+        line: usize,
+        kind: SyntaxErrorKind,
+    },
+    /// A required key is not there (e.g., `estimators[1].kind`)
+    Missing(String),
+    WrongType {
+        key: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// A value of the right type that does not make sense
+    InvalidValue {
+        key: String,
+        expected: String,
+    },
+    /// A key that is not read by anyone (most likely, a typo)
+    UnknownKey(String),
+}
+
+impl fmt::Display for SyntaxErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyntaxErrorKind::UnexpectedEnd => write!(f, "unexpected end of file"),
+            SyntaxErrorKind::UnexpectedCharacter { expected, found } => {
+                write!(f, "expecting {}... found {:?}", expected, found)
+            }
+            SyntaxErrorKind::InvalidNumber(s) => write!(f, "expecting a number... found '{}'", s),
+            SyntaxErrorKind::InvalidEscape(c) => write!(f, "unknown escape sequence '\\{}'", c),
+            SyntaxErrorKind::DuplicateKey(k) => write!(f, "key '{}' is defined twice", k),
+            SyntaxErrorKind::NotATable(k) => write!(f, "expecting '{}' to be a table", k),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Syntax { line, kind } => write!(f, "line {}: {}", line, kind),
+            ConfigError::Missing(k) => write!(f, "missing key '{}'", k),
+            ConfigError::WrongType {
+                key,
+                expected,
+                found,
+            } => write!(f, "expecting {} in '{}'... found {}", expected, key, found),
+            ConfigError::InvalidValue { key, expected } => {
+                write!(f, "expecting {} in '{}'", expected, key)
+            }
+            ConfigError::UnknownKey(k) => write!(f, "unknown key '{}'", k),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(e: std::io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+/// Reads a configuration file, choosing its format by its extension
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Value, ConfigError> {
+    let path = path.as_ref();
+    parse(&std::fs::read_to_string(path)?, Format::from_path(path))
+}
+
+/// Parses a whole configuration file, which is always a table
+pub fn parse(text: &str, format: Format) -> Result<Value, ConfigError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        position: 0,
+        line: 1,
+        json: format == Format::Json,
+    };
+    match format {
+        Format::Json => {
+            parser.skip_space(true);
+            if parser.peek() != Some('{') {
+                return Err(parser.unexpected("a table"));
+            }
+            let value = parser.value()?;
+            parser.skip_space(true);
+            match parser.peek() {
+                None => Ok(value),
+                Some(_) => Err(parser.unexpected("the end of the file")),
+            }
+        }
+        Format::Toml => parser.toml(),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+    json: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, kind: SyntaxErrorKind) -> ConfigError {
+        ConfigError::Syntax {
+            line: self.line,
+            kind,
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> ConfigError {
+        match self.peek() {
+            None => self.error(SyntaxErrorKind::UnexpectedEnd),
+            Some(found) => self.error(SyntaxErrorKind::UnexpectedCharacter { expected, found }),
+        }
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), ConfigError> {
+        if self.peek() != Some(c) {
+            return Err(self.unexpected(expected));
+        }
+        self.next();
+        Ok(())
+    }
+
+    /// Skips spaces and comments, and also new lines if `newlines`
+    fn skip_space(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => {}
+                '\n' if newlines => {}
+                '#' if !self.json => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.next();
+                    }
+                    continue;
+                }
+                _ => return,
+            }
+            self.next();
+        }
+    }
+
+    fn toml(&mut self) -> Result<Value, ConfigError> {
+        let mut root = Vec::new();
+        let mut current: Vec<String> = Vec::new();
+        loop {
+            self.skip_space(true);
+            match self.peek() {
+                None => return Ok(Value::Table(root)),
+                Some('[') => {
+                    self.next();
+                    let array = self.peek() == Some('[');
+                    if array {
+                        self.next();
+                    }
+                    current = self.header()?;
+                    self.expect(']', "']'")?;
+                    if array {
+                        self.expect(']', "']]'")?;
+                    }
+                    let (key, parent) = current.split_last().unwrap();
+                    let parent = table_at(&mut root, parent).map_err(|k| self.error(k))?;
+                    match parent.iter_mut().find(|(k, _)| k == key) {
+                        Some((_, Value::Array(tables))) if array => {
+                            tables.push(Value::Table(Vec::new()))
+                        }
+                        Some(_) => {
+                            return Err(self.error(SyntaxErrorKind::DuplicateKey(current.join("."))))
+                        }
+                        None if array => {
+                            parent.push((key.clone(), Value::Array(vec![Value::Table(Vec::new())])))
+                        }
+                        None => parent.push((key.clone(), Value::Table(Vec::new()))),
+                    }
+                }
+                Some(_) => {
+                    let key = self.key()?;
+                    self.skip_space(false);
+                    self.expect('=', "'='")?;
+                    self.skip_space(false);
+                    let value = self.value()?;
+                    let table = table_at(&mut root, &current).map_err(|k| self.error(k))?;
+                    if table.iter().any(|(k, _)| *k == key) {
+                        return Err(self.error(SyntaxErrorKind::DuplicateKey(key)));
+                    }
+                    table.push((key, value));
+                }
+            }
+            self.skip_space(false);
+            if self.peek().is_some_and(|c| c != '\n') {
+                return Err(self.unexpected("a new line"));
+            }
+        }
+    }
+
+    /// The dotted keys of a `[table.header]`
+    fn header(&mut self) -> Result<Vec<String>, ConfigError> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_space(false);
+            keys.push(self.key()?);
+            self.skip_space(false);
+            if self.peek() != Some('.') {
+                return Ok(keys);
+            }
+            self.next();
+        }
+    }
+
+    /// A bare key (e.g., `max_pow` or `bad-importance`) or a quoted one
+    fn key(&mut self) -> Result<String, ConfigError> {
+        if self.peek() == Some('"') || self.json {
+            return self.string();
+        }
+        let mut key = String::new();
+        while let Some(c) = self
+            .peek()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        {
+            key.push(c);
+            self.next();
+        }
+        if key.is_empty() {
+            return Err(self.unexpected("a key"));
+        }
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<Value, ConfigError> {
+        match self.peek() {
+            None => Err(self.error(SyntaxErrorKind::UnexpectedEnd)),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('[') => self.array(),
+            Some('{') => self.table(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let mut word = String::new();
+                while let Some(c) = self.peek().filter(|c| c.is_ascii_alphanumeric()) {
+                    word.push(c);
+                    self.next();
+                }
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => Err(self.error(SyntaxErrorKind::InvalidNumber(word))),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let mut number = String::new();
+                while let Some(c) = self
+                    .peek()
+                    .filter(|c| c.is_ascii_alphanumeric() || "+-._".contains(*c))
+                {
+                    number.push(c);
+                    self.next();
+                }
+                let digits = if self.json {
+                    number.clone()
+                } else {
+                    number.replace('_', "")
+                };
+                let valid = digits
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+                match digits.parse() {
+                    Ok(n) if valid => Ok(Value::Number(n)),
+                    _ => Err(self.error(SyntaxErrorKind::InvalidNumber(number))),
+                }
+            }
+            Some(_) => Err(self.unexpected("a value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ConfigError> {
+        self.expect('"', "a string")?;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => return Err(self.error(SyntaxErrorKind::UnexpectedEnd)),
+                Some('"') => return Ok(string),
+                Some('\\') => match self.next() {
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('/') => string.push('/'),
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some('r') => string.push('\r'),
+                    Some(c) => return Err(self.error(SyntaxErrorKind::InvalidEscape(c))),
+                    None => return Err(self.error(SyntaxErrorKind::UnexpectedEnd)),
+                },
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, ConfigError> {
+        self.expect('[', "'['")?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_space(true);
+            if self.peek() == Some(']') {
+                self.next();
+                return Ok(Value::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_space(true);
+            match self.peek() {
+                Some(',') => {
+                    self.next();
+                }
+                Some(']') => {}
+                _ => return Err(self.unexpected("',' or ']'")),
+            }
+        }
+    }
+
+    /// An inline table in TOML, or an object in JSON
+    fn table(&mut self) -> Result<Value, ConfigError> {
+        self.expect('{', "'{'")?;
+        let mut entries: Vec<(String, Value)> = Vec::new();
+        loop {
+            self.skip_space(self.json);
+            if self.peek() == Some('}') {
+                self.next();
+                return Ok(Value::Table(entries));
+            }
+            let key = self.key()?;
+            self.skip_space(self.json);
+            if self.json {
+                self.expect(':', "':'")?;
+            } else {
+                self.expect('=', "'='")?;
+            }
+            self.skip_space(self.json);
+            let value = self.value()?;
+            if entries.iter().any(|(k, _)| *k == key) {
+                return Err(self.error(SyntaxErrorKind::DuplicateKey(key)));
+            }
+            entries.push((key, value));
+            self.skip_space(self.json);
+            match self.peek() {
+                Some(',') => {
+                    self.next();
+                }
+                Some('}') => {}
+                _ => return Err(self.unexpected("',' or '}'")),
+            }
+        }
+    }
+}
+
+/// The table at `path`, creating the missing ones. In arrays of tables,
+/// it goes into the last table (i.e., the one being defined).
+fn table_at<'t>(
+    root: &'t mut Vec<(String, Value)>,
+    path: &[String],
+) -> Result<&'t mut Vec<(String, Value)>, SyntaxErrorKind> {
+    let mut table = root;
+    for key in path {
+        let index = match table.iter().position(|(k, _)| k == key) {
+            Some(i) => i,
+            None => {
+                table.push((key.clone(), Value::Table(Vec::new())));
+                table.len() - 1
+            }
+        };
+        table = match &mut table[index].1 {
+            Value::Table(entries) => entries,
+            Value::Array(values) => match values.last_mut() {
+                Some(Value::Table(entries)) => entries,
+                _ => return Err(SyntaxErrorKind::NotATable(key.clone())),
+            },
+            _ => return Err(SyntaxErrorKind::NotATable(key.clone())),
+        };
+    }
+    Ok(table)
+}
+
+/// The entries of a table, along with where the table is in the file,
+/// so errors can name the keys as `estimators[1].heuristic`
+#[derive(Debug, Clone)]
+pub struct Fields<'a> {
+    path: String,
+    entries: &'a [(String, Value)],
+}
+
+/// What the readers of [`Fields`] return: `None` if the key is not there
+type Read<T> = Result<Option<T>, ConfigError>;
+
+impl<'a> Fields<'a> {
+    /// The fields of `value`, which is at `path` (empty for the root)
+    pub fn new(path: &str, value: &'a Value) -> Result<Self, ConfigError> {
+        match value {
+            Value::Table(entries) => Ok(Self {
+                path: path.to_string(),
+                entries,
+            }),
+            v => Err(ConfigError::WrongType {
+                key: path.to_string(),
+                expected: "a table",
+                found: v.type_name(),
+            }),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The full name of `key`
+    pub fn key(&self, key: &str) -> String {
+        if self.path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", self.path, key)
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&'a Value> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// The names and values of all the entries
+    pub fn entries(&self) -> impl Iterator<Item = (&'a str, &'a Value)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Fails if there is any key not in `known`
+    pub fn only(&self, known: &[&str]) -> Result<(), ConfigError> {
+        match self
+            .entries
+            .iter()
+            .find(|(k, _)| !known.contains(&k.as_str()))
+        {
+            Some((k, _)) => Err(ConfigError::UnknownKey(self.key(k))),
+            None => Ok(()),
+        }
+    }
+
+    /// An error about the value of `key`
+    pub fn invalid(&self, key: &str, expected: &str) -> ConfigError {
+        ConfigError::InvalidValue {
+            key: self.key(key),
+            expected: expected.to_string(),
+        }
+    }
+
+    /// Fails if `value`, read from `key`, is not there
+    pub fn required<T>(&self, key: &str, value: Option<T>) -> Result<T, ConfigError> {
+        value.ok_or_else(|| ConfigError::Missing(self.key(key)))
+    }
+
+    fn typed<T>(
+        &self,
+        key: &str,
+        expected: &'static str,
+        convert: impl Fn(&'a Value) -> Option<T>,
+    ) -> Read<T> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(v) => convert(v).map(Some).ok_or_else(|| ConfigError::WrongType {
+                key: self.key(key),
+                expected,
+                found: v.type_name(),
+            }),
+        }
+    }
+
+    pub fn string(&self, key: &str) -> Read<&'a str> {
+        self.typed(key, "a string", |v| match v {
+            Value::String(s) => Some(s.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn number(&self, key: &str) -> Read<f64> {
+        self.typed(key, "a number", |v| match v {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        })
+    }
+
+    pub fn bool(&self, key: &str) -> Read<bool> {
+        self.typed(key, "a boolean", |v| match v {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        })
+    }
+
+    /// A whole, non-negative number
+    pub fn integer(&self, key: &str) -> Read<u64> {
+        match self.number(key)? {
+            Some(n) if is_integer(n) => Ok(Some(n as u64)),
+            Some(_) => Err(self.invalid(key, "a whole, non-negative number")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn numbers(&self, key: &str) -> Read<Vec<f64>> {
+        self.typed(key, "an array of numbers", |v| match v {
+            Value::Array(values) => values
+                .iter()
+                .map(|v| match v {
+                    Value::Number(n) => Some(*n),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        })
+    }
+
+    pub fn integers(&self, key: &str) -> Read<Vec<u64>> {
+        match self.numbers(key)? {
+            Some(numbers) if numbers.iter().all(|n| is_integer(*n)) => {
+                Ok(Some(numbers.iter().map(|n| *n as u64).collect()))
+            }
+            Some(_) => Err(self.invalid(key, "an array of whole, non-negative numbers")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn table(&self, key: &str) -> Read<Fields<'a>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(v) => Fields::new(&self.key(key), v).map(Some),
+        }
+    }
+
+    /// An array of tables, named like `estimators[2]`
+    pub fn tables(&self, key: &str) -> Read<Vec<Fields<'a>>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Array(values)) => values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| Fields::new(&format!("{}[{}]", self.key(key), i), v))
+                .collect::<Result<_, _>>()
+                .map(Some),
+            Some(v) => Err(ConfigError::WrongType {
+                key: self.key(key),
+                expected: "an array of tables",
+                found: v.type_name(),
+            }),
+        }
+    }
+}
+
+fn is_integer(n: f64) -> bool {
+    n >= 0. && n.fract() == 0. && n < 2f64.powi(53)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+# An experiment
+name = "mis" # trailing comments are fine
+counts = [1, 2_048,
+    4.5e1, -3, ]  # across lines
+enabled = true
+point = { x = 1, label = "a\"b" }
+
+[pdfs.fa]
+steps = [0.5, 1]
+
+[[estimators]]
+kind = "plain"
+
+[[estimators]]
+kind = "mis"
+"#;
+
+    #[test]
+    fn toml() {
+        let value = parse(TOML, Format::Toml).unwrap();
+        let fields = Fields::new("", &value).unwrap();
+        assert_eq!(fields.string("name").unwrap(), Some("mis"));
+        assert_eq!(
+            fields.numbers("counts").unwrap().unwrap(),
+            vec![1., 2048., 45., -3.]
+        );
+        assert_eq!(fields.bool("enabled").unwrap(), Some(true));
+        assert_eq!(fields.string("missing").unwrap(), None);
+
+        let point = fields.table("point").unwrap().unwrap();
+        assert_eq!(point.string("label").unwrap(), Some("a\"b"));
+
+        let pdfs = fields.table("pdfs").unwrap().unwrap();
+        let names: Vec<&str> = pdfs.entries().map(|(k, _)| k).collect();
+        assert_eq!(names, vec!["fa"]);
+
+        let estimators = fields.tables("estimators").unwrap().unwrap();
+        assert_eq!(estimators.len(), 2);
+        assert_eq!(estimators[1].path(), "estimators[1]");
+        assert_eq!(estimators[1].string("kind").unwrap(), Some("mis"));
+        assert!(matches!(
+            estimators[1].required("heuristic", estimators[1].string("heuristic").unwrap()),
+            Err(ConfigError::Missing(key)) if key == "estimators[1].heuristic"
+        ));
+    }
+
+    #[test]
+    fn json() {
+        let json = r#"{
+            "name": "mis",
+            "counts": [1, 2048, 45, -3],
+            "nested": {"empty": [], "flag": false}
+        }"#;
+        let value = parse(json, Format::Json).unwrap();
+        let fields = Fields::new("", &value).unwrap();
+        assert_eq!(fields.string("name").unwrap(), Some("mis"));
+        assert_eq!(
+            fields.integers("counts").unwrap_err().to_string(),
+            "expecting an array of whole, non-negative numbers in 'counts'"
+        );
+        let nested = fields.table("nested").unwrap().unwrap();
+        assert_eq!(nested.numbers("empty").unwrap(), Some(vec![]));
+        assert!(matches!(
+            nested.number("flag"),
+            Err(ConfigError::WrongType { key, found: "a boolean", .. }) if key == "nested.flag"
+        ));
+        assert!(nested.only(&["empty"]).is_err());
+        assert!(nested.only(&["empty", "flag"]).is_ok());
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let error = |text: &str, format| match parse(text, format) {
+            Err(ConfigError::Syntax { line, kind }) => (line, kind),
+            r => panic!("expecting a syntax error... found {:?}", r),
+        };
+        assert_eq!(
+            error("a = 1\nb = 1.2.3", Format::Toml),
+            (2, SyntaxErrorKind::InvalidNumber("1.2.3".to_string()))
+        );
+        assert_eq!(
+            error("a = 1\na = 2", Format::Toml),
+            (2, SyntaxErrorKind::DuplicateKey("a".to_string()))
+        );
+        assert_eq!(
+            error("a = 1 2", Format::Toml).1,
+            SyntaxErrorKind::UnexpectedCharacter {
+                expected: "a new line",
+                found: '2'
+            }
+        );
+        assert_eq!(
+            error("a = 1\n[a.b]", Format::Toml).1,
+            SyntaxErrorKind::NotATable("a".to_string())
+        );
+        assert_eq!(
+            error("a = \"open", Format::Toml).1,
+            SyntaxErrorKind::UnexpectedEnd
+        );
+        assert_eq!(
+            error("a = [1, 2", Format::Toml).1,
+            SyntaxErrorKind::UnexpectedEnd
+        );
+        assert_eq!(
+            error("a = yes", Format::Toml).1,
+            SyntaxErrorKind::InvalidNumber("yes".to_string())
+        );
+        assert_eq!(
+            error("{\"a\": 1,\n \"b\" 2}", Format::Json),
+            (
+                2,
+                SyntaxErrorKind::UnexpectedCharacter {
+                    expected: "':'",
+                    found: '2'
+                }
+            )
+        );
+        assert_eq!(
+            error("{} {}", Format::Json).1,
+            SyntaxErrorKind::UnexpectedCharacter {
+                expected: "the end of the file",
+                found: '{'
+            }
+        );
+        let e = parse("x = @", Format::Toml).unwrap_err();
+        assert_eq!(e.to_string(), "line 1: expecting a value... found '@'");
+    }
+}
@@ -0,0 +1,621 @@
+//! Convergence studies described in a configuration file (see [`config`]),
+//! instead of in the code of a test. For instance, in TOML:
+//!
+//! ```toml
+//! name = "Multiple importance sampling"
+//! integrand = { kind = "product", a = "fa", b = "fb" }
+//! seeds = [0, 1]
+//! repetitions = 4
+//! schedule = { min_pow = 1, max_pow = 15 }
+//!
+//! [pdfs.fa]
+//! steps = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0]
+//! densities = [0.5, 1.4, 3.2, 3.0, 0.5, 0.1, 0.1, 0.5, 0.1, 0.6]
+//!
+//! [[estimators]]
+//! name = "A"
+//! kind = "plain"
+//! sampling = "fa"
+//!
+//! [[estimators]]
+//! name = "Power MIS"
+//! kind = "mis"
+//! heuristic = "power"
+//! na = 9
+//! nb = 5
+//! ```
+//!
+//! Every estimator runs once for each number of samples, seed and
+//! repetition, and each run becomes a [`Record`] of the results.
+
+use crate::config::{self, ConfigError, Fields, Value};
+use lesson1_sampling::step_pdf::DiscretePdf;
+use lesson2_montecarlo::importance_sampling::{Importance, Uniform, EXP_INTEGRAL};
+use lesson2_montecarlo::integrands::{Quad, Triangle};
+use lesson2_montecarlo::montecarlo_integrable::MontecarloIntegrable;
+use lesson2_montecarlo::multiple_importance_2::{MISHeuristic, MISSingle, MIS};
+use lesson3_russian_roulette::{B_WEIGHT, MIS as RouletteMIS};
+use rand::Rng;
+use results::{Column, Format, Metadata, ResultsError, ResultsWriter};
+use std::io::Write;
+use std::path::Path;
+
+/// A piecewise constant PDF over `[0, 1]`, as in [`DiscretePdf`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdfSpec {
+    pub steps: Vec<f64>,
+    pub densities: Vec<f64>,
+}
+
+/// Why a [`PdfSpec`] cannot be made into a [`DiscretePdf`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidPdf {
+    Steps,
+    Densities,
+}
+
+impl InvalidPdf {
+    /// What the values should look like
+    pub fn expected(&self) -> &'static str {
+        match self {
+            InvalidPdf::Steps => "increasing bounds, up to 1",
+            InvalidPdf::Densities => "one density per step, integrating to 1",
+        }
+    }
+}
+
+impl PdfSpec {
+    /// Checks what [`DiscretePdf`] would panic on (and that it is over
+    /// `[0, 1]`, where it inverts its CDF). NaN and infinities are
+    /// rejected first, as any comparison with NaN is false.
+    pub fn new(steps: Vec<f64>, densities: Vec<f64>) -> Result<Self, InvalidPdf> {
+        if steps.is_empty()
+            || steps.iter().any(|s| !s.is_finite())
+            || steps.windows(2).any(|w| w[0] >= w[1])
+            || steps[0] <= 0.
+            || *steps.last().unwrap() != 1.
+        {
+            return Err(InvalidPdf::Steps);
+        }
+        let spec = Self { steps, densities };
+        if spec.densities.len() != spec.steps.len()
+            || spec.densities.iter().any(|p| !p.is_finite() || *p < 0.)
+            || (spec.integrate(|_| 1.) - 1.).abs() > 1e-6
+        {
+            return Err(InvalidPdf::Densities);
+        }
+        Ok(spec)
+    }
+
+    pub fn pdf(&self) -> DiscretePdf {
+        DiscretePdf::new(0.0, self.steps.clone(), self.densities.clone())
+    }
+
+    /// The density at `x`, which is not on a bound
+    fn density(&self, x: f64) -> f64 {
+        let i = self.steps.iter().take_while(|s| **s < x).count();
+        self.densities.get(i).copied().unwrap_or(0.)
+    }
+
+    /// The exact integral of `pdf(x)*f(x)`, where `f` is also constant
+    /// between the bounds of the steps
+    fn integrate<F: Fn(f64) -> f64>(&self, f: F) -> f64 {
+        let mut previous = 0.;
+        let mut total = 0.;
+        for (step, density) in self.steps.iter().zip(self.densities.iter()) {
+            total += (step - previous) * density * f((step + previous) / 2.);
+            previous = *step;
+        }
+        total
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Integrand {
+    /// The spike of [`lesson2_montecarlo::importance_sampling::exp`]
+    Exp,
+    Triangle {
+        side: f64,
+    },
+    Quad {
+        a: f64,
+        b: f64,
+        c: f64,
+        range: f64,
+    },
+    /// `a(x)*b(x)`, where both are PDFs of the experiment
+    Product {
+        a: String,
+        b: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EstimatorKind {
+    /// Samples from a PDF of the experiment, or uniformly if `None`
+    Plain { sampling: Option<String> },
+    /// Combines samples of both factors of a product
+    Mis { power: bool, na: usize, nb: usize },
+    /// Russian roulette on `b(x)`, driven by `a(x)` (see lesson 3)
+    Roulette { sampling: Option<String> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EstimatorSpec {
+    pub name: String,
+    pub kind: EstimatorKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Experiment {
+    pub name: String,
+    pub integrand: Integrand,
+    /// The exact integral, which errors are relative to
+    pub expected: f64,
+    pub pdfs: Vec<(String, PdfSpec)>,
+    pub estimators: Vec<EstimatorSpec>,
+    /// The number of samples of each run
+    pub counts: Vec<usize>,
+    pub seeds: Vec<u64>,
+    pub repetitions: usize,
+    /// Where to write the results, relative to the working directory
+    pub output: Option<String>,
+}
+
+/// The result of running one estimator once
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub estimator: String,
+    pub samples: usize,
+    pub seed: u64,
+    pub repetition: usize,
+    pub estimate: f64,
+    /// Relative to the exact integral
+    pub error: f64,
+    /// The evaluations of the integrand. For products, as in lesson 3,
+    /// `b(x)` costs [`B_WEIGHT`] times more than `a(x)`.
+    pub cost: f64,
+}
+
+/// Runs `n` samples of an estimator, returning the estimate and its cost
+type Runner<'a> = Box<dyn Fn(usize, rand::Rng) -> (f64, f64) + 'a>;
+
+impl Experiment {
+    /// Reads an experiment from a TOML or JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Self::from_value(&config::load(path)?)
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, ConfigError> {
+        let root = Fields::new("", value)?;
+        root.only(&[
+            "name",
+            "integrand",
+            "expected",
+            "pdfs",
+            "estimators",
+            "schedule",
+            "seeds",
+            "repetitions",
+            "output",
+        ])?;
+
+        let mut pdfs = Vec::new();
+        if let Some(table) = root.table("pdfs")? {
+            for (name, value) in table.entries() {
+                let fields = Fields::new(&table.key(name), value)?;
+                fields.only(&["steps", "densities"])?;
+                let steps = fields.required("steps", fields.numbers("steps")?)?;
+                let densities = fields.required("densities", fields.numbers("densities")?)?;
+                let spec = PdfSpec::new(steps, densities).map_err(|e| match e {
+                    InvalidPdf::Steps => fields.invalid("steps", e.expected()),
+                    InvalidPdf::Densities => fields.invalid("densities", e.expected()),
+                })?;
+                pdfs.push((name.to_string(), spec));
+            }
+        }
+        let names: Vec<&str> = pdfs.iter().map(|(n, _)| n.as_str()).collect();
+        let pdf_name = |fields: &Fields, key: &str| -> Result<Option<String>, ConfigError> {
+            match fields.string(key)? {
+                None | Some("uniform") => Ok(None),
+                Some(name) if names.contains(&name) => Ok(Some(name.to_string())),
+                Some(_) => Err(fields.invalid(
+                    key,
+                    &format!("uniform, or one of the pdfs ({})", names.join(", ")),
+                )),
+            }
+        };
+
+        let fields = root.required("integrand", root.table("integrand")?)?;
+        let kind = fields.required("kind", fields.string("kind")?)?;
+        let number = |key, default| Ok::<_, ConfigError>(fields.number(key)?.unwrap_or(default));
+        let integrand = match kind {
+            "exp" => {
+                fields.only(&["kind"])?;
+                Integrand::Exp
+            }
+            "triangle" => {
+                fields.only(&["kind", "side"])?;
+                Integrand::Triangle {
+                    side: number("side", 4.0)?,
+                }
+            }
+            "quad" => {
+                fields.only(&["kind", "a", "b", "c", "range"])?;
+                Integrand::Quad {
+                    a: number("a", 1.0)?,
+                    b: number("b", 2.121)?,
+                    c: number("c", 3.0)?,
+                    range: number("range", 4.0)?,
+                }
+            }
+            "product" => {
+                fields.only(&["kind", "a", "b"])?;
+                let a = pdf_name(&fields, "a")?;
+                let b = pdf_name(&fields, "b")?;
+                match (a, b) {
+                    (Some(a), Some(b)) => Integrand::Product { a, b },
+                    _ => return Err(fields.invalid("a", "two pdfs in 'a' and 'b'")),
+                }
+            }
+            _ => return Err(fields.invalid("kind", "exp, triangle, quad or product")),
+        };
+
+        let mut estimators = Vec::new();
+        for fields in root.required("estimators", root.tables("estimators")?)? {
+            let name = fields.required("name", fields.string("name")?)?.to_string();
+            let kind = fields.required("kind", fields.string("kind")?)?;
+            let product = matches!(integrand, Integrand::Product { .. });
+            let kind = match kind {
+                "plain" | "roulette" => {
+                    fields.only(&["name", "kind", "sampling"])?;
+                    let sampling = pdf_name(&fields, "sampling")?;
+                    let domain = matches!(integrand, Integrand::Exp) || product;
+                    if sampling.is_some() && !domain {
+                        return Err(fields.invalid("sampling", "uniform, as it is not over [0, 1]"));
+                    }
+                    if kind == "plain" {
+                        EstimatorKind::Plain { sampling }
+                    } else if product {
+                        EstimatorKind::Roulette { sampling }
+                    } else {
+                        return Err(fields.invalid("kind", "plain, as it is not a product"));
+                    }
+                }
+                "mis" => {
+                    fields.only(&["name", "kind", "heuristic", "na", "nb"])?;
+                    if !product {
+                        return Err(fields.invalid("kind", "plain, as it is not a product"));
+                    }
+                    let power = match fields.string("heuristic")? {
+                        None | Some("balance") => false,
+                        Some("power") => true,
+                        Some(_) => return Err(fields.invalid("heuristic", "balance or power")),
+                    };
+                    EstimatorKind::Mis {
+                        power,
+                        na: fields.integer("na")?.unwrap_or(1) as usize,
+                        nb: fields.integer("nb")?.unwrap_or(1) as usize,
+                    }
+                }
+                _ => return Err(fields.invalid("kind", "plain, mis or roulette")),
+            };
+            estimators.push(EstimatorSpec { name, kind });
+        }
+
+        let schedule = root.required("schedule", root.table("schedule")?)?;
+        schedule.only(&["counts", "min_pow", "max_pow"])?;
+        let counts: Vec<usize> = match schedule.integers("counts")? {
+            Some(counts) => counts.into_iter().map(|n| n as usize).collect(),
+            None => {
+                let min = schedule.integer("min_pow")?.unwrap_or(1);
+                let max = schedule.required("max_pow", schedule.integer("max_pow")?)?;
+                if min > max || max > 40 {
+                    return Err(schedule.invalid("max_pow", "a power between min_pow and 40"));
+                }
+                (min..=max).map(|p| 2_usize.pow(p as u32)).collect()
+            }
+        };
+        if counts.is_empty() || counts.contains(&0) {
+            return Err(schedule.invalid("counts", "at least one run, of one sample or more"));
+        }
+
+        let seeds = root.integers("seeds")?.unwrap_or_else(|| vec![0]);
+        if seeds.is_empty() {
+            return Err(root.invalid("seeds", "at least one seed"));
+        }
+        let repetitions = root.integer("repetitions")?.unwrap_or(1) as usize;
+        if repetitions == 0 {
+            return Err(root.invalid("repetitions", "at least one repetition"));
+        }
+
+        let pdf = |name: &str| &pdfs.iter().find(|(n, _)| n == name).unwrap().1;
+        let expected = match root.number("expected")? {
+            Some(expected) => expected,
+            None => match &integrand {
+                Integrand::Exp => EXP_INTEGRAL,
+                Integrand::Triangle { side } => Triangle { side: *side }.analytically_integrate(),
+                Integrand::Quad { a, b, c, range } => Quad {
+                    a: *a,
+                    b: *b,
+                    c: *c,
+                    range: *range,
+                }
+                .analytically_integrate(),
+                Integrand::Product { a, b } => pdf(a).integrate(|x| pdf(b).density(x)),
+            },
+        };
+        if expected == 0. {
+            return Err(root.invalid("expected", "a non-zero integral, as errors are relative"));
+        }
+
+        Ok(Self {
+            name: root.string("name")?.unwrap_or("experiment").to_string(),
+            integrand,
+            expected,
+            pdfs,
+            estimators,
+            counts,
+            seeds,
+            repetitions,
+            output: root.string("output")?.map(String::from),
+        })
+    }
+
+    fn pdf(&self, name: &Option<String>) -> DiscretePdf {
+        match name {
+            None => DiscretePdf::new(0.0, vec![1.0], vec![1.]),
+            Some(name) => self.pdfs.iter().find(|(n, _)| n == name).unwrap().1.pdf(),
+        }
+    }
+
+    fn runner(&self, kind: &EstimatorKind) -> Runner<'_> {
+        fn plain<E: MontecarloIntegrable + 'static>(e: E, cost: f64) -> Runner<'static> {
+            Box::new(move |n, rng| (e.integrate(n, rng), n as f64 * cost))
+        }
+        let product_cost = 1.0 + B_WEIGHT;
+        match (&self.integrand, kind) {
+            (Integrand::Exp, EstimatorKind::Plain { sampling: None }) => plain(Uniform {}, 1.),
+            (Integrand::Exp, EstimatorKind::Plain { sampling }) => plain(
+                Importance {
+                    pdf: self.pdf(sampling),
+                },
+                1.,
+            ),
+            (Integrand::Triangle { side }, _) => plain(Triangle { side: *side }, 1.),
+            (Integrand::Quad { a, b, c, range }, _) => plain(
+                Quad {
+                    a: *a,
+                    b: *b,
+                    c: *c,
+                    range: *range,
+                },
+                1.,
+            ),
+            (Integrand::Product { a, b }, EstimatorKind::Plain { sampling }) => plain(
+                MISSingle {
+                    a: self.pdf(&Some(a.clone())),
+                    b: self.pdf(&Some(b.clone())),
+                    sampling: self.pdf(sampling),
+                },
+                product_cost,
+            ),
+            (Integrand::Product { a, b }, EstimatorKind::Mis { power, na, nb }) => plain(
+                MIS {
+                    a: self.pdf(&Some(a.clone())),
+                    b: self.pdf(&Some(b.clone())),
+                    na: *na,
+                    nb: *nb,
+                    heuristic: if *power {
+                        MISHeuristic::Power
+                    } else {
+                        MISHeuristic::Balance
+                    },
+                },
+                (na + nb) as f64 * product_cost,
+            ),
+            (Integrand::Product { a, b }, EstimatorKind::Roulette { sampling }) => {
+                let estimator = RouletteMIS::new(
+                    self.pdf(&Some(a.clone())),
+                    self.pdf(&Some(b.clone())),
+                    self.pdf(sampling),
+                    true,
+                );
+                Box::new(move |n, rng| {
+                    estimator.cost().reset();
+                    let estimate = estimator.integrate(n, rng);
+                    (estimate, estimator.cost().total())
+                })
+            }
+            (_, kind) => unreachable!("{:?} is checked when loading", kind),
+        }
+    }
+
+    /// What the experiment is, for the header of its results (but not
+    /// where, or when, it runs; see [`Metadata::with_environment`])
+    pub fn metadata(&self) -> Metadata {
+        let seeds: Vec<String> = self.seeds.iter().map(|s| s.to_string()).collect();
+        Metadata::new()
+            .with("experiment", &self.name)
+            .with("expected", format!("{:?}", self.expected))
+            .with("seeds", seeds.join(" "))
+            .with("repetitions", self.repetitions)
+            .with("generator", rand::GENERATOR)
+    }
+
+    /// Runs every estimator for each number of samples, seed and repetition
+    pub fn run(&self) -> Vec<Record> {
+        let mut records = Vec::new();
+        for (e, estimator) in self.estimators.iter().enumerate() {
+            let runner = self.runner(&estimator.kind);
+            for (c, samples) in self.counts.iter().enumerate() {
+                for seed in self.seeds.iter() {
+                    for repetition in 0..self.repetitions {
+                        let stream = (e * self.counts.len() + c) * self.repetitions + repetition;
+                        let (estimate, cost) =
+                            runner(*samples, Rng::with_stream(*seed, stream as u64));
+                        records.push(Record {
+                            estimator: estimator.name.clone(),
+                            samples: *samples,
+                            seed: *seed,
+                            repetition,
+                            estimate,
+                            error: (estimate - self.expected).abs() / self.expected.abs(),
+                            cost,
+                        });
+                    }
+                }
+            }
+        }
+        records
+    }
+}
+
+/// Writes `records`, with one row per run
+pub fn write_records<W: Write>(
+    writer: W,
+    format: Format,
+    metadata: &Metadata,
+    records: &[Record],
+) -> Result<W, ResultsError> {
+    let columns = vec![
+        Column::text("estimator"),
+        Column::integer("N"),
+        Column::integer("seed"),
+        Column::integer("repetition"),
+        Column::float("estimate"),
+        Column::float("error"),
+        Column::float("cost"),
+    ];
+    let mut writer = ResultsWriter::new(writer, format, columns, metadata)?;
+    for r in records {
+        writer.row(&[
+            r.estimator.as_str().into(),
+            r.samples.into(),
+            r.seed.into(),
+            r.repetition.into(),
+            r.estimate.into(),
+            r.error.into(),
+            r.cost.into(),
+        ])?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse;
+    use lesson2_montecarlo::multiple_importance_2::EXPECTED;
+
+    fn experiment(name: &str) -> Experiment {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("experiments")
+            .join(name);
+        Experiment::load(path).unwrap()
+    }
+
+    /// The mean error of each estimator with the most samples
+    fn final_errors(experiment: &Experiment, records: &[Record]) -> Vec<f64> {
+        let n = *experiment.counts.last().unwrap();
+        experiment
+            .estimators
+            .iter()
+            .map(|e| {
+                let errors: Vec<f64> = records
+                    .iter()
+                    .filter(|r| r.estimator == e.name && r.samples == n)
+                    .map(|r| r.error)
+                    .collect();
+                errors.iter().sum::<f64>() / errors.len() as f64
+            })
+            .collect()
+    }
+
+    #[test]
+    fn importance() {
+        let mut importance = experiment("importance.toml");
+        assert_eq!(importance.expected, EXP_INTEGRAL);
+        importance.counts = vec![4096];
+        let errors = final_errors(&importance, &importance.run());
+        // Uniform, following the spike and avoiding it
+        assert!(
+            errors[1] < errors[0] && errors[0] < errors[2],
+            "{:?}",
+            errors
+        );
+    }
+
+    #[test]
+    fn multiple_importance() {
+        let mut mis = experiment("mis.toml");
+        assert!((mis.expected - EXPECTED).abs() < 1e-12);
+        assert_eq!(mis.estimators.len(), 5);
+        mis.counts = vec![1024];
+        let records = mis.run();
+        assert_eq!(records.len(), 5 * mis.seeds.len() * mis.repetitions);
+        for error in final_errors(&mis, &records) {
+            assert!(error < 0.1, "{:.6}", error);
+        }
+        // Reproducible
+        assert_eq!(records, mis.run());
+    }
+
+    #[test]
+    fn roulette() {
+        let mut roulette = experiment("roulette.json");
+        roulette.counts = vec![256];
+        let records = roulette.run();
+        let cost = |name: &str| records.iter().find(|r| r.estimator == name).unwrap().cost;
+        assert_eq!(cost("No roulette"), 256. * (1. + B_WEIGHT));
+        assert!(cost("Roulette") < cost("No roulette"));
+
+        let metadata = roulette.metadata();
+        assert_eq!(metadata.get("experiment"), Some("Russian roulette"));
+        let csv = write_records(Vec::new(), Format::Csv, &metadata, &records[..1]).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(lines[0], "estimator,N,seed,repetition,estimate,error,cost");
+        assert!(lines[1].starts_with("No roulette,256,0,0,"));
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text: &str| {
+            Experiment::from_value(&parse(text, config::Format::Toml).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        let base = "integrand = { kind = \"exp\" }\nschedule = { max_pow = 4 }\n";
+        let with = |estimator: &str| format!("{}[[estimators]]\nname = \"e\"\n{}", base, estimator);
+        assert_eq!(error(base), "missing key 'estimators'");
+        assert_eq!(
+            error(&with("kind = \"mis\"")),
+            "expecting plain, as it is not a product in 'estimators[0].kind'"
+        );
+        assert_eq!(
+            error(&with("kind = \"plain\"\nsampling = \"fa\"")),
+            "expecting uniform, or one of the pdfs () in 'estimators[0].sampling'"
+        );
+        assert_eq!(
+            error(&with("kind = \"plain\"\nsamples = 3")),
+            "unknown key 'estimators[0].samples'"
+        );
+        assert_eq!(
+            error(&with("kind = 1")),
+            "expecting a string in 'estimators[0].kind'... found a number"
+        );
+        assert_eq!(
+            error(&format!(
+                "{}[pdfs.p]\nsteps = [0.5, 1]\ndensities = [1, 2]",
+                base
+            )),
+            "expecting one density per step, integrating to 1 in 'pdfs.p.densities'"
+        );
+        assert_eq!(
+            error("integrand = { kind = \"triangle\" }\nschedule = { min_pow = 5, max_pow = 4 }\n[[estimators]]\nname = \"e\"\nkind = \"plain\""),
+            "expecting a power between min_pow and 40 in 'schedule.max_pow'"
+        );
+    }
+}
@@ -1 +1,9 @@
+//! A command line runner for the experiments of every lesson, e.g.,
+//! `learning mis --max-pow 12 --format svg --output mis.svg`, or for
+//! experiments described in a file (see [`experiment`]).
+//! Run `learning help` to list the commands and their options.
 
+pub mod cli;
+pub mod commands;
+pub mod config;
+pub mod experiment;
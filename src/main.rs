@@ -1,3 +1,13 @@
-fn main() {
-    println!("Hello, world!");
+use learning::commands;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match commands::run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, commands::usage());
+            ExitCode::from(2)
+        }
+    }
 }